@@ -1,6 +1,6 @@
-use csv::table::Table;
+// use csv::table::Table;
 
-// fn main() {
+fn main() {
 //     let left = Table::from_csv(include_str!("data/left.csv"), Some("\t"));
 //     let right = Table::from_csv(include_str!("data/right.csv"), Some("\t"));
 //     println!("left:");
@@ -15,4 +15,4 @@ use csv::table::Table;
 //     println!("\nright join on name:");
 //     left.right_join(&right, "name", "name", true)
 //         .select("name,cowdung,value");
-// }
+}
@@ -1,4 +1,4 @@
-use csv::table::Table;
+// use csv::table::Table;
 
 fn main() {
     // let table = Table::from_csv(include_str!("data/table.csv"), Some("\t"));
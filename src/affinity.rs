@@ -0,0 +1,259 @@
+use anyhow::anyhow;
+
+use crate::datetime::{self, Interval, Temporal};
+use crate::decimal::Decimal;
+use crate::value::{Datatype, Value};
+use crate::vm::eval::truth;
+
+/// the preferred storage class of a column, like in SQLite
+/// values are converted to it when they are stored, as long as no information is lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Affinity {
+    Integer,
+    Real,
+    Numeric,
+    Text,
+    #[default]
+    Blob, // no conversion, also known as NONE
+}
+
+impl Affinity {
+    /// the affinity for a declared column type, with the rules of SQLite:
+    /// INT => Integer, CHAR, CLOB or TEXT => Text, BLOB or no type => Blob,
+    /// REAL, FLOA or DOUB => Real, anything else (NUMERIC, DECIMAL, DATE) => Numeric
+    pub fn from_type_name(name: &str) -> Self {
+        let name = name.to_uppercase();
+        if name.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"].iter().any(|t| name.contains(t)) {
+            Affinity::Text
+        } else if name.contains("BLOB") || name.trim().is_empty() {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"].iter().any(|t| name.contains(t)) {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    /// converts a value that is stored in a column with this affinity
+    /// text that is not a well-formed number stays text in a numeric column
+    pub fn apply(&self, value: Value) -> Value {
+        match self {
+            Affinity::Blob => value,
+            Affinity::Text => match value.datatype() {
+                Ok(Datatype::Integer | Datatype::Float | Datatype::Decimal) => {
+                    Value::from_text(value.to_string())
+                }
+                _ => value,
+            },
+            Affinity::Integer | Affinity::Numeric => {
+                let value = match value.as_str().and_then(parse_number) {
+                    Some(number) => number,
+                    None => value,
+                };
+                match value.datatype() {
+                    // without fraction: 1e3 is stored as 1000
+                    Ok(Datatype::Float) => integral(&value).unwrap_or(value),
+                    _ => value,
+                }
+            }
+            Affinity::Real => {
+                let value = match value.as_str().and_then(parse_number) {
+                    Some(number) => number,
+                    None => value,
+                };
+                match value.datatype() {
+                    Ok(Datatype::Integer | Datatype::Decimal) => {
+                        value.as_f64().map_or(value, Value::from_f64)
+                    }
+                    _ => value,
+                }
+            }
+        }
+    }
+}
+
+/// a well-formed integer, decimal or float, None for other text
+/// unlike Value::from, 'inf' and 'NaN' are not numbers
+fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if !text.bytes().any(|b| b.is_ascii_digit())
+        || text
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && !b.eq_ignore_ascii_case(&b'e'))
+    {
+        return None;
+    }
+    let value = Value::from(text);
+    (!value.is_text()).then_some(value)
+}
+
+/// the float as an integer, when it has no fraction and fits
+fn integral(value: &Value) -> Option<Value> {
+    let f = value.as_f64()?;
+    (f.fract() == 0.0 && f.abs() < 9.2e18).then(|| Value::from_i64(f as i64))
+}
+
+/// CAST(value AS type_name)
+/// type names are those for columns (see Affinity::from_type_name), and also
+/// decimal, boolean, date, time, timestamp and interval. Precision and scale, as in decimal(10, 2),
+/// are ignored. Text that is not a number cannot be cast to a numeric type
+pub(crate) fn cast(value: &Value, type_name: &str) -> anyhow::Result<Value> {
+    if value.is_null() {
+        return Ok(Value::null());
+    }
+    let name = type_name.split('(').next().unwrap_or_default().trim();
+    let cannot = || {
+        let shown = if value.is_text() {
+            format!("'{}'", value)
+        } else {
+            value.to_string()
+        };
+        anyhow!("Cannot cast {} to {}", shown, name.to_uppercase())
+    };
+    let text = match value.datatype()? {
+        Datatype::Text | Datatype::Blob => Some(String::from_utf8_lossy(&value.data).into_owned()),
+        _ => None,
+    };
+    // the numeric value of numbers and text
+    let number = || match &text {
+        Some(text) => parse_number(text).ok_or_else(cannot),
+        None if value.as_f64().is_some() => Ok(value.clone()),
+        None => Err(cannot()),
+    };
+    let result = match name.to_lowercase().as_str() {
+        "date" | "time" | "timestamp" | "datetime" => {
+            let function = if name.eq_ignore_ascii_case("timestamp") {
+                "datetime"
+            } else {
+                &name.to_lowercase()
+            };
+            let result = match &text {
+                Some(text) => datetime::call(function, &[Value::from_text(text.trim())]),
+                None if Temporal::from_value(value).is_some() => {
+                    datetime::call(function, std::slice::from_ref(value))
+                }
+                None => return Err(cannot()),
+            };
+            let result = result.unwrap_or_else(|| Err(cannot()))?;
+            if result.is_null() {
+                return Err(cannot());
+            }
+            result
+        }
+        "interval" => match (&text, Interval::from_value(value)) {
+            (_, Some(_)) => value.clone(),
+            (Some(text), _) => datetime::parse_interval(text)
+                .map(Value::from)
+                .ok_or_else(cannot)?,
+            _ => return Err(cannot()),
+        },
+        "decimal" | "dec" => {
+            let number = number()?;
+            let decimal = match number.datatype()? {
+                Datatype::Decimal => Decimal::from_value(&number),
+                Datatype::Integer => number.as_i64().map(Decimal::from),
+                _ => {
+                    let text = number.to_string();
+                    Decimal::parse(&text).or_else(|| text.parse::<i64>().ok().map(Decimal::from))
+                }
+            };
+            decimal.map(Value::from).ok_or_else(cannot)?
+        }
+        "bool" | "boolean" => match &text {
+            Some(text) => match text.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Value::from_bool(true),
+                "false" | "no" | "0" => Value::from_bool(false),
+                _ => return Err(cannot()),
+            },
+            None => Value::from_bool(truth(&number()?) == Some(true)),
+        },
+        _ => match Affinity::from_type_name(name) {
+            Affinity::Text => Value::from_text(text.unwrap_or_else(|| value.to_string())),
+            Affinity::Blob => {
+                Value::from_blob(text.unwrap_or_else(|| value.to_string()).as_bytes())
+            }
+            Affinity::Real => Value::from_f64(number()?.as_f64().ok_or_else(cannot)?),
+            Affinity::Numeric => {
+                let number = number()?;
+                integral(&number)
+                    .filter(|_| number.datatype().ok() == Some(Datatype::Float))
+                    .unwrap_or(number)
+            }
+            // truncated towards zero, like in SQLite
+            Affinity::Integer => {
+                let number = number()?;
+                match number.as_i64() {
+                    Some(i) => Value::from_i64(i),
+                    None => {
+                        let f = number.as_f64().ok_or_else(cannot)?.trunc();
+                        if !(-9.2e18..9.2e18).contains(&f) {
+                            return Err(cannot());
+                        }
+                        Value::from_i64(f as i64)
+                    }
+                }
+            }
+        },
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_type_name() {
+        assert_eq!(Affinity::from_type_name("BIGINT"), Affinity::Integer);
+        assert_eq!(Affinity::from_type_name("varchar(10)"), Affinity::Text);
+        assert_eq!(Affinity::from_type_name("double precision"), Affinity::Real);
+        assert_eq!(Affinity::from_type_name("DECIMAL(10,2)"), Affinity::Numeric);
+        assert_eq!(Affinity::from_type_name(""), Affinity::Blob);
+    }
+
+    #[test]
+    fn test_apply() {
+        let numeric = Affinity::Numeric;
+        assert_eq!(numeric.apply(Value::from_text(" 12 ")), Value::from(12));
+        assert_eq!(numeric.apply(Value::from_f64(1e3)).to_string(), "1000");
+        assert_eq!(
+            numeric.apply(Value::from_text("12.50")).to_string(),
+            "12.50"
+        );
+        assert!(numeric.apply(Value::from_text("inf")).is_text());
+        assert!(Affinity::Text.apply(Value::from(12)).is_text());
+        assert_eq!(Affinity::Real.apply(Value::from(2)).to_string(), "2");
+        assert_eq!(
+            Affinity::Real.apply(Value::from(2)).datatype().unwrap(),
+            Datatype::Float
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        let text = |t: &str| Value::from_text(t);
+        assert_eq!(cast(&text("42"), "integer").unwrap(), Value::from(42));
+        assert_eq!(
+            cast(&Value::from_f64(-2.7), "int").unwrap(),
+            Value::from(-2)
+        );
+        assert_eq!(
+            cast(&text("19.99"), "decimal(10,2)").unwrap().to_string(),
+            "19.99"
+        );
+        assert_eq!(cast(&Value::from(7), "text").unwrap(), text("7"));
+        assert_eq!(
+            cast(&text("2024-01-12"), "date").unwrap().to_string(),
+            "2024-01-12"
+        );
+        assert_eq!(cast(&text("yes"), "boolean").unwrap(), Value::from(true));
+        assert!(cast(&Value::null(), "integer").unwrap().is_null());
+        assert_eq!(
+            cast(&text("abc"), "integer").unwrap_err().to_string(),
+            "Cannot cast 'abc' to INTEGER"
+        );
+        assert!(cast(&text("2024-02-30"), "date").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use crate::value::{Datatype, Value};
+
+type CompareFn = dyn Fn(&str, &str) -> Ordering;
+
+/// compares text values. Other values (and text with other values) use the order of Value
+/// built in: binary, nocase (ascii case insensitive), rtrim (ignores trailing spaces)
+/// and unicode (case insensitive for all of unicode)
+#[derive(Clone)]
+pub struct Collation {
+    name: Rc<str>,
+    compare: Option<Rc<CompareFn>>, // None for binary
+}
+
+impl Collation {
+    /// a collation that compares text with the function
+    /// the function must define a total order
+    pub fn new(name: &str, compare: impl Fn(&str, &str) -> Ordering + 'static) -> Self {
+        Self {
+            name: name.to_lowercase().into(),
+            compare: Some(Rc::new(compare)),
+        }
+    }
+
+    /// the default: text is compared bytewise
+    pub fn binary() -> Self {
+        Self {
+            name: "binary".into(),
+            compare: None,
+        }
+    }
+
+    pub fn nocase() -> Self {
+        Self::new("nocase", |l, r| {
+            l.bytes()
+                .map(|b| b.to_ascii_lowercase())
+                .cmp(r.bytes().map(|b| b.to_ascii_lowercase()))
+        })
+    }
+
+    pub fn rtrim() -> Self {
+        Self::new("rtrim", |l, r| {
+            l.trim_end_matches(' ').cmp(r.trim_end_matches(' '))
+        })
+    }
+
+    pub fn unicode() -> Self {
+        Self::new("unicode", |l, r| {
+            l.chars()
+                .flat_map(char::to_lowercase)
+                .cmp(r.chars().flat_map(char::to_lowercase))
+        })
+    }
+
+    /// the built in collation with the name, case insensitive
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "binary" => Some(Self::binary()),
+            "nocase" => Some(Self::nocase()),
+            "rtrim" => Some(Self::rtrim()),
+            "unicode" => Some(Self::unicode()),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn compare(&self, left: &Value, right: &Value) -> Ordering {
+        match &self.compare {
+            Some(compare) if is_text(left) && is_text(right) => compare(
+                &String::from_utf8_lossy(&left.data),
+                &String::from_utf8_lossy(&right.data),
+            ),
+            _ => left.cmp(right),
+        }
+    }
+}
+
+fn is_text(value: &Value) -> bool {
+    matches!(value.datatype(), Ok(Datatype::Text))
+}
+
+impl Debug for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Collation({})", self.name)
+    }
+}
+
+/// a value that is ordered by a collation, for keys in ordered maps
+#[derive(Debug, Clone)]
+pub(crate) struct Collated {
+    pub(crate) value: Value,
+    pub(crate) collation: Collation,
+}
+
+impl Ord for Collated {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.collation.compare(&self.value, &other.value)
+    }
+}
+
+impl PartialOrd for Collated {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collated {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collated {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let (a, upper_a) = (Value::from_text("abc"), Value::from_text("ABC"));
+        assert_eq!(Collation::binary().compare(&a, &upper_a), Ordering::Greater);
+        assert_eq!(Collation::nocase().compare(&a, &upper_a), Ordering::Equal);
+        let padded = Value::from_text("abc  ");
+        assert_eq!(Collation::rtrim().compare(&a, &padded), Ordering::Equal);
+        let (e, upper_e) = (Value::from_text("été"), Value::from_text("ÉTÉ"));
+        assert_ne!(Collation::nocase().compare(&e, &upper_e), Ordering::Equal);
+        assert_eq!(Collation::unicode().compare(&e, &upper_e), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_other_values_keep_their_order() {
+        let number = Value::from_i64(1);
+        let text = Value::from_text("a");
+        assert_eq!(Collation::nocase().compare(&number, &text), Ordering::Less);
+        assert_eq!(
+            Collation::nocase().compare(&Value::null(), &number),
+            Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+
+use crate::affinity::Affinity;
+use crate::collation::Collation;
+use crate::database::Database;
+use crate::index::Key;
+use crate::record::Record;
+use crate::sql::ast::{Constraint, Expression};
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm::eval::{evaluate, truth};
+
+/// a constraint, resolved to the columns of the table
+/// unique constraints and primary keys are enforced by a unique index
+/// foreign keys are not enforced, see Database::check_foreign_keys
+#[derive(Debug, Clone)]
+pub(crate) enum Rule {
+    NotNull(usize),
+    Unique {
+        index: String,
+        primary_key: bool,
+    },
+    Check(Expression),
+    ForeignKey {
+        columns: Vec<usize>,
+        parent: String,
+        parent_columns: Vec<String>,
+    },
+}
+
+impl Table {
+    /// adds a constraint, the records that the table already has must satisfy it
+    /// a primary key also makes its columns not null
+    pub fn add_constraint(
+        &mut self,
+        constraint: &Constraint,
+        database: &Database,
+    ) -> anyhow::Result<()> {
+        let rules = match constraint {
+            Constraint::NotNull(column) => vec![Rule::NotNull(self.column(column)?)],
+            Constraint::Check(expression) => vec![Rule::Check(expression.clone())],
+            Constraint::Unique(columns) => vec![self.unique(columns, false)?],
+            Constraint::PrimaryKey(columns) => {
+                if self
+                    .constraints
+                    .iter()
+                    .any(|rule| matches!(rule, Rule::Unique { primary_key, .. } if *primary_key))
+                {
+                    return Err(anyhow!(
+                        "Table {} has more than one primary key",
+                        self.name()
+                    ));
+                }
+                let mut rules = vec![self.unique(columns, true)?];
+                for column in columns {
+                    rules.push(Rule::NotNull(self.column(column)?));
+                }
+                rules
+            }
+            Constraint::ForeignKey {
+                columns,
+                parent,
+                parent_columns,
+            } => {
+                if !parent_columns.is_empty() && parent_columns.len() != columns.len() {
+                    return Err(anyhow!(
+                            "Number of columns in foreign key does not match the number of columns in the referenced table"
+                        ));
+                }
+                vec![Rule::ForeignKey {
+                    columns: columns
+                        .iter()
+                        .map(|column| self.column(column))
+                        .collect::<anyhow::Result<_>>()?,
+                    parent: parent.clone(),
+                    parent_columns: parent_columns.clone(),
+                }]
+            }
+        };
+        let old = self.constraints.len();
+        self.constraints.extend(rules);
+        if let Some(error) = self
+            .iter()
+            .find_map(|record| self.check(&record, database).err())
+        {
+            for rule in self.constraints.drain(old..) {
+                if let Rule::Unique { index, .. } = rule {
+                    self.views.remove(&index);
+                }
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// an error for the first constraint that the record does not satisfy
+    /// the values are checked after conversion to the affinities of their columns
+    pub fn check(&self, record: &Record, database: &Database) -> anyhow::Result<()> {
+        let mut record = record.clone();
+        record.values.resize(self.cols.len(), Value::null());
+        self.apply_affinities(&mut record);
+        for rule in &self.constraints {
+            match rule {
+                Rule::NotNull(column) => {
+                    if record.get(*column).is_null() {
+                        return Err(anyhow!(
+                            "NOT NULL constraint failed: {}",
+                            self.qualified(&[*column])
+                        ));
+                    }
+                }
+                Rule::Unique { index, .. } => {
+                    let view = &self.views[index];
+                    if view.duplicate(&record).is_some() {
+                        return Err(anyhow!(
+                            "UNIQUE constraint failed: {}",
+                            self.qualified(view.columns())
+                        ));
+                    }
+                }
+                // NULL passes
+                Rule::Check(expression) => {
+                    if truth(&evaluate(expression, database, self, &record)?) == Some(false) {
+                        return Err(anyhow!("CHECK constraint failed: {}", expression));
+                    }
+                }
+                Rule::ForeignKey { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// inserts the record when it satisfies the constraints, returns its rowid
+    pub fn insert_checked(&mut self, record: Record, database: &Database) -> anyhow::Result<u64> {
+        self.check(&record, database)?;
+        self.append(record)
+    }
+
+    /// replaces the record when the new values satisfy the constraints,
+    /// false when the table has no record with its rowid
+    pub fn update_checked(&mut self, record: Record, database: &Database) -> anyhow::Result<bool> {
+        self.check(&record, database)?;
+        self.update(record)
+    }
+
+    /// removes the not null constraint of the column that is dropped
+    /// columns of unique, primary key, check and foreign key constraints cannot be dropped
+    pub(crate) fn drop_rules(&mut self, column: usize) -> anyhow::Result<()> {
+        let name = &self.cols[column];
+        for rule in &self.constraints {
+            let kind = match rule {
+                Rule::Unique { index, primary_key }
+                    if self.views[index].columns().contains(&column) =>
+                {
+                    if *primary_key {
+                        "PRIMARY KEY"
+                    } else {
+                        "UNIQUE"
+                    }
+                }
+                Rule::Check(expression) if mentions(expression, name) => "CHECK",
+                Rule::ForeignKey { columns, .. } if columns.contains(&column) => "FOREIGN KEY",
+                _ => continue,
+            };
+            return Err(anyhow!("Cannot drop {} column {}", kind, name));
+        }
+        self.constraints
+            .retain(|rule| !matches!(rule, Rule::NotNull(index) if *index == column));
+        for rule in &mut self.constraints {
+            let indexes = match rule {
+                Rule::NotNull(index) => std::slice::from_mut(index),
+                Rule::ForeignKey { columns, .. } => &mut columns[..],
+                _ => continue,
+            };
+            for index in indexes {
+                if *index > column {
+                    *index -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// check constraints follow the rename of a column
+    pub(crate) fn rename_rules(&mut self, name: &str, new_name: &str) {
+        for rule in &mut self.constraints {
+            if let Rule::Check(expression) = rule {
+                *expression = expression.transform(&|e| match e {
+                    Expression::Column(column) if column == name => {
+                        Some(Expression::Column(new_name.to_string()))
+                    }
+                    _ => None,
+                });
+            }
+        }
+    }
+
+    /// foreign keys follow the rename of their parent table
+    pub(crate) fn rename_parent(&mut self, name: &str, new_name: &str) {
+        for rule in &mut self.constraints {
+            if let Rule::ForeignKey { parent, .. } = rule {
+                if parent == name {
+                    *parent = new_name.to_string();
+                }
+            }
+        }
+    }
+
+    /// the foreign keys: their columns, parent table and parent columns
+    pub(crate) fn foreign_keys(&self) -> Vec<(Vec<usize>, String, Vec<String>)> {
+        self.constraints
+            .iter()
+            .filter_map(|rule| match rule {
+                Rule::ForeignKey {
+                    columns,
+                    parent,
+                    parent_columns,
+                } => Some((columns.clone(), parent.clone(), parent_columns.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the columns that a foreign key of the child table refers to in this table,
+    /// the primary key when none are named
+    pub(crate) fn referenced(&self, columns: &[String], child: &str) -> anyhow::Result<Vec<usize>> {
+        let mismatch = || {
+            anyhow!(
+                "Foreign key mismatch: {} referencing {}",
+                child,
+                self.name()
+            )
+        };
+        if !columns.is_empty() {
+            return columns
+                .iter()
+                .map(|column| self.find_index(column).ok_or_else(mismatch))
+                .collect();
+        }
+        self.constraints
+            .iter()
+            .find_map(|rule| match rule {
+                Rule::Unique {
+                    index,
+                    primary_key: true,
+                } => Some(self.views[index].columns().to_vec()),
+                _ => None,
+            })
+            .ok_or_else(mismatch)
+    }
+
+    /// the rowids of the records with a foreign key that matches no record of the parent table:
+    /// an anti-join of the records on the set of parent keys
+    /// records with NULL in one of the columns are no orphans
+    /// the values are compared with the affinities and collations of the parent columns
+    pub(crate) fn orphans(
+        &self,
+        columns: &[usize],
+        parent: &Table,
+        parent_columns: &[usize],
+    ) -> Vec<u64> {
+        let collations: Rc<[Collation]> = parent_columns
+            .iter()
+            .map(|column| parent.collation(*column).clone())
+            .collect();
+        let key = |record: &Record, columns: &[usize]| {
+            let values: Vec<Value> = columns
+                .iter()
+                .zip(parent_columns)
+                .map(
+                    |(column, parent_column)| match parent.affinity(*parent_column) {
+                        Affinity::Blob => record.get(*column).clone(),
+                        affinity => affinity.apply(record.get(*column).clone()),
+                    },
+                )
+                .collect();
+            Key::collated(values, Rc::clone(&collations))
+        };
+        let keys: BTreeSet<Key> = parent
+            .iter()
+            .map(|record| key(&record, parent_columns))
+            .collect();
+        self.iter()
+            .filter(|record| columns.iter().all(|column| !record.get(*column).is_null()))
+            .filter(|record| !keys.contains(&key(record, columns)))
+            .map(|record| record.rowid)
+            .collect()
+    }
+
+    /// a unique index on the columns, named after the table
+    fn unique(&mut self, columns: &[String], primary_key: bool) -> anyhow::Result<Rule> {
+        let columns = columns
+            .iter()
+            .map(|column| self.column(column))
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        let index = (1..)
+            .map(|n| format!("{}_autoindex_{}", self.name(), n))
+            .find(|name| !self.views.contains_key(name))
+            .unwrap();
+        self.create_index_on(index.clone(), columns);
+        Ok(Rule::Unique { index, primary_key })
+    }
+
+    fn column(&self, name: &str) -> anyhow::Result<usize> {
+        self.find_index(name)
+            .ok_or_else(|| anyhow!("No such column: {}", name))
+    }
+
+    /// table.column, .. as in the errors of SQLite
+    fn qualified(&self, columns: &[usize]) -> String {
+        columns
+            .iter()
+            .map(|column| match self.name() {
+                "" => self.cols[*column].clone(),
+                table => format!("{}.{}", table, self.cols[*column]),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// true if the expression refers to the column
+fn mentions(expression: &Expression, column: &str) -> bool {
+    let mut found = false;
+    expression.walk(&mut |e| {
+        if let Expression::Column(name) = e {
+            found |= name == column
+                || name
+                    .rsplit_once('.')
+                    .is_some_and(|(_, name)| name == column);
+        }
+    });
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sql::parser::parse_expression;
+
+    fn table(database: &Database) -> Table {
+        let mut table = Table::from_csv("id,email,amount\n1,a@x,10\n2,b@x,0\n", Some(","));
+        table.rename("t");
+        table
+            .add_constraint(&Constraint::PrimaryKey(vec!["id".to_string()]), database)
+            .unwrap();
+        table
+            .add_constraint(&Constraint::Unique(vec!["email".to_string()]), database)
+            .unwrap();
+        let check = parse_expression("amount >= 0").unwrap();
+        table
+            .add_constraint(&Constraint::Check(check), database)
+            .unwrap();
+        table
+    }
+
+    fn record(values: &[Value]) -> Record {
+        let mut record = Record::default();
+        for value in values {
+            record.add_value(value.clone());
+        }
+        record
+    }
+
+    #[test]
+    fn test_insert_checked() {
+        let database = Database::new();
+        let mut table = table(&database);
+        let error = |table: &mut Table, values: &[Value]| {
+            table
+                .insert_checked(record(values), &database)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&mut table, &[1.into(), "c@x".into(), 1.into()]),
+            "UNIQUE constraint failed: t.id"
+        );
+        assert_eq!(
+            error(&mut table, &[Value::null(), "c@x".into(), 1.into()]),
+            "NOT NULL constraint failed: t.id"
+        );
+        assert_eq!(
+            error(&mut table, &[3.into(), "a@x".into(), 1.into()]),
+            "UNIQUE constraint failed: t.email"
+        );
+        assert_eq!(
+            error(&mut table, &[3.into(), "c@x".into(), (-1).into()]),
+            "CHECK constraint failed: amount >= 0"
+        );
+        // NULL is unique, and passes a check
+        let values = [3.into(), Value::null(), Value::null()];
+        assert_eq!(table.insert_checked(record(&values), &database).unwrap(), 3);
+        let values = [4.into(), Value::null(), Value::null()];
+        assert_eq!(table.insert_checked(record(&values), &database).unwrap(), 4);
+        assert_eq!(table.iter().count(), 4);
+    }
+
+    #[test]
+    fn test_update_checked() {
+        let database = Database::new();
+        let mut table = table(&database);
+        let mut first = table.get(1).unwrap();
+        // the record does not conflict with itself
+        assert!(table.update_checked(first.clone(), &database).unwrap());
+        first.values[1] = "b@x".into();
+        assert!(table.update_checked(first, &database).is_err());
+        assert_eq!(table.get(1).unwrap().get(1).to_string(), "a@x");
+    }
+
+    #[test]
+    fn test_existing_records() {
+        let database = Database::new();
+        let mut table = table(&database);
+        let check = parse_expression("amount > 0").unwrap();
+        assert!(table
+            .add_constraint(&Constraint::Check(check), &database)
+            .is_err());
+        assert!(table
+            .add_constraint(
+                &Constraint::PrimaryKey(vec!["email".to_string()]),
+                &database
+            )
+            .is_err());
+        let views = table.views.len();
+        let mut duplicate = Table::from_csv("a\n1\n1\n", Some(","));
+        assert!(duplicate
+            .add_constraint(&Constraint::Unique(vec!["a".to_string()]), &database)
+            .is_err());
+        assert!(duplicate.views.is_empty());
+        assert_eq!(table.views.len(), views);
+        assert!(table.drop_column("email").is_err());
+        assert!(table.drop_column("amount").is_err());
+        table.rename_column("amount", "total").unwrap();
+        let values = [3.into(), "c@x".into(), (-1).into()];
+        assert_eq!(
+            table
+                .insert_checked(record(&values), &database)
+                .unwrap_err()
+                .to_string(),
+            "CHECK constraint failed: total >= 0"
+        );
+    }
+}
@@ -0,0 +1,2028 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::anyhow;
+
+use crate::affinity::Affinity;
+use crate::collation::Collation;
+use crate::functions::Registry;
+use crate::pager::Pager;
+use crate::read::CsvOptions;
+use crate::record::Record;
+use crate::sql::ast::{
+    AlterAction, ColumnDefinition, CommonTableExpression, Expression, InsertSource, Select,
+    Statement, TableReference,
+};
+use crate::sql::parser;
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm::eval::{evaluate, truth};
+use crate::vm::{compiler, Vm};
+
+/// the most times the step of a recursive common table expression runs
+const MAX_RECURSION: usize = 10_000;
+
+/// a page file is checkpointed when its log holds this many pages
+const CHECKPOINT_PAGES: usize = 1000;
+
+/// named tables that can be queried with sql
+#[derive(Debug, Default)]
+pub struct Database {
+    tables: HashMap<String, Table>,
+    collations: HashMap<String, Collation>, // registered collations, by lowercase name
+    functions: Registry,                    // built in and registered scalar functions
+    savepoints: Vec<Savepoint>,             // of the open transaction, the outermost first
+    pagers: HashMap<String, Pager>,         // page files of the attached tables, by table name
+}
+
+/// the tables as they were when the savepoint was set,
+/// each saved when a statement changes it for the first time after that
+#[derive(Debug)]
+struct Savepoint {
+    name: Option<String>,                  // None for the one of 'begin'
+    saved: HashMap<String, Option<Table>>, // None for a table that did not exist
+}
+
+/// a row with a foreign key that refers to no row of its parent table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orphan {
+    pub table: String,
+    pub rowid: u64,
+    pub parent: String,
+    pub fkid: usize, // the position of the foreign key among the foreign keys of the table
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds the table under name, replacing any table with the same name
+    pub fn add_table(&mut self, name: impl Into<String>, table: Table) {
+        self.tables.insert(name.into(), table);
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Option<&mut Table> {
+        self.tables.get_mut(name)
+    }
+
+    /// stores the table with the name in the page file at path, see Table::save
+    /// a page file that holds a table is read under the name, an empty one gets the table
+    /// from then on, its changes are written to the file on commit, or after each statement
+    /// outside a transaction. A dropped table keeps its last committed contents in the file
+    /// every table is written atomically, but a transaction that changes several attached
+    /// tables is not: they are written one after the other, in name order, and a crash or
+    /// an error in between leaves the ones before written and the others not
+    pub fn attach(&mut self, name: &str, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        if self.in_transaction() {
+            return Err(anyhow!("Cannot attach a table inside a transaction"));
+        }
+        let mut pager = Pager::open(path)?;
+        if pager.page_count() > 0 {
+            let mut table = Table::open(&mut pager)?;
+            table.rename(name);
+            table.save(&mut pager)?;
+            self.tables.insert(name.to_string(), table);
+        } else {
+            self.load_table(name)?;
+            self.tables
+                .get_mut(name)
+                .ok_or_else(|| anyhow!("No such table: {}", name))?
+                .save(&mut pager)?;
+        }
+        self.pagers.insert(name.to_string(), pager);
+        Ok(())
+    }
+
+    /// registers a collation for use with 'collate name', replacing any built in with the same name
+    /// compare must define a total order on text
+    pub fn register_collation(
+        &mut self,
+        name: &str,
+        compare: impl Fn(&str, &str) -> std::cmp::Ordering + 'static,
+    ) {
+        let collation = Collation::new(name, compare);
+        self.collations
+            .insert(collation.name().to_string(), collation);
+    }
+
+    /// the registered or built in collation with the name
+    pub fn collation(&self, name: &str) -> anyhow::Result<Collation> {
+        self.collations
+            .get(&name.to_lowercase())
+            .cloned()
+            .or_else(|| Collation::builtin(name))
+            .ok_or_else(|| anyhow!("No such collation: {}", name))
+    }
+
+    /// registers a scalar function for use in sql, replacing any function with the same name
+    /// arity is the number of arguments, None for any number
+    /// a deterministic function always returns the same value for the same arguments,
+    /// so that calls with constant arguments can be computed once, when a statement is compiled
+    pub fn register_scalar(
+        &mut self,
+        name: &str,
+        arity: Option<usize>,
+        deterministic: bool,
+        function: impl Fn(&[Value]) -> anyhow::Result<Value> + 'static,
+    ) {
+        let arity = match arity {
+            Some(n) => (n, Some(n)),
+            None => (0, None),
+        };
+        self.functions.add(name, arity, deterministic, function);
+    }
+
+    /// registers an aggregate function for use in sql, replacing any with the same name
+    /// for every group, init creates the state, step is called with the arguments for each record
+    /// (NULLs included) and finalize computes the result from the state
+    /// the built in aggregates (count, sum, avg, min, max) cannot be replaced
+    pub fn register_aggregate<S: 'static>(
+        &mut self,
+        name: &str,
+        init: impl Fn() -> S + 'static,
+        step: impl Fn(&mut S, &[Value]) -> anyhow::Result<()> + 'static,
+        finalize: impl Fn(S) -> anyhow::Result<Value> + 'static,
+    ) {
+        self.functions.add_aggregate(name, init, step, finalize);
+    }
+
+    pub(crate) fn functions(&self) -> &Registry {
+        &self.functions
+    }
+
+    /// runs a sql statement and returns the result as a table
+    /// statements that do not return rows return an empty table
+    /// tables that are not known by name are loaded from the csv file with that name
+    /// outside a transaction, the changes to attached tables are written to their page files
+    pub fn execute(&mut self, sql: &str) -> anyhow::Result<Table> {
+        self.functions.clear_caches();
+        let result = self.run(parser::parse(sql)?)?;
+        if !self.in_transaction() {
+            self.write_attached()?;
+        }
+        Ok(result)
+    }
+
+    /// writes the changes of the attached tables to their page files, in name order
+    /// after an error, the tables from the one that failed on keep their changes
+    /// in memory, and the next statement outside a transaction writes them again
+    fn write_attached(&mut self) -> anyhow::Result<()> {
+        let mut names: Vec<String> = self.pagers.keys().cloned().collect();
+        names.sort();
+        for (index, name) in names.iter().enumerate() {
+            let (Some(table), Some(pager)) = (self.tables.get_mut(name), self.pagers.get_mut(name))
+            else {
+                continue;
+            };
+            let written = table.save(pager).and_then(|_| {
+                if pager.log_pages() >= CHECKPOINT_PAGES {
+                    pager.checkpoint()?;
+                }
+                Ok(())
+            });
+            if let Err(error) = written {
+                return Err(anyhow!(
+                    "Could not write table {}: {}; tables not written: {}",
+                    name,
+                    error,
+                    names[index..].join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, statement: Statement) -> anyhow::Result<Table> {
+        match statement {
+            Statement::Select(select) => self.select(&select),
+            Statement::CreateIndex {
+                name,
+                table,
+                columns,
+            } => {
+                self.load_table(&table)?;
+                self.save(&table);
+                self.tables
+                    .get_mut(&table)
+                    .ok_or_else(|| anyhow!("No such table: {}", table))?
+                    .create_index(name, &columns.join(","))?;
+                Ok(Table::new(""))
+            }
+            Statement::Insert {
+                table,
+                columns,
+                source,
+            } => {
+                self.load_table(&table)?;
+                self.save(&table);
+                self.insert(&table, &columns, &source)?;
+                Ok(Table::new(""))
+            }
+            Statement::Update {
+                table,
+                assignments,
+                where_clause,
+            } => {
+                self.load_table(&table)?;
+                self.save(&table);
+                self.update(&table, &assignments, where_clause.as_ref())?;
+                Ok(Table::new(""))
+            }
+            Statement::Delete {
+                table,
+                where_clause,
+            } => {
+                self.load_table(&table)?;
+                self.save(&table);
+                self.delete(&table, where_clause.as_ref())?;
+                Ok(Table::new(""))
+            }
+            Statement::CreateTable {
+                name,
+                if_not_exists,
+                columns,
+                constraints,
+                select,
+            } => {
+                if self.tables.contains_key(&name) {
+                    return if if_not_exists {
+                        Ok(Table::new(""))
+                    } else {
+                        Err(anyhow!("Table {} already exists", name))
+                    };
+                }
+                self.save(&name);
+                let mut table = match select {
+                    Some(select) => self.select(&select)?,
+                    None => {
+                        let mut table = Table::new(&name);
+                        for column in &columns {
+                            table.append_column(&column.name)?;
+                            self.define_column(&mut table, column)?;
+                        }
+                        for constraint in &constraints {
+                            table.add_constraint(constraint, self)?;
+                        }
+                        table
+                    }
+                };
+                table.rename(&name);
+                self.tables.insert(name, table);
+                Ok(Table::new(""))
+            }
+            Statement::DropTable { name, if_exists } => {
+                self.load_table(&name)?;
+                self.save(&name);
+                if self.tables.remove(&name).is_none() && !if_exists {
+                    return Err(anyhow!("No such table: {}", name));
+                }
+                Ok(Table::new(""))
+            }
+            Statement::AlterTable { name, action } => {
+                if matches!(action, AlterAction::RenameTo(_)) && self.pagers.contains_key(&name) {
+                    return Err(anyhow!(
+                        "Cannot rename {}, it is attached to a page file",
+                        name
+                    ));
+                }
+                self.load_table(&name)?;
+                self.save(&name);
+                if let AlterAction::RenameTo(new_name) = &action {
+                    self.save(new_name);
+                    // their foreign keys follow the rename
+                    let children: Vec<String> = self
+                        .tables
+                        .iter()
+                        .filter(|(_, table)| {
+                            table
+                                .foreign_keys()
+                                .iter()
+                                .any(|(_, parent, _)| *parent == name)
+                        })
+                        .map(|(child, _)| child.clone())
+                        .collect();
+                    for child in children {
+                        self.save(&child);
+                    }
+                }
+                self.alter_table(&name, &action)?;
+                Ok(Table::new(""))
+            }
+            Statement::Begin => {
+                if !self.savepoints.is_empty() {
+                    return Err(anyhow!("A transaction is already active"));
+                }
+                self.savepoints.push(Savepoint {
+                    name: None,
+                    saved: HashMap::new(),
+                });
+                Ok(Table::new(""))
+            }
+            Statement::Commit => {
+                if self.savepoints.is_empty() {
+                    return Err(anyhow!("No transaction is active"));
+                }
+                self.savepoints.clear();
+                Ok(Table::new(""))
+            }
+            Statement::Rollback(None) => {
+                if self.savepoints.is_empty() {
+                    return Err(anyhow!("No transaction is active"));
+                }
+                for savepoint in std::mem::take(&mut self.savepoints).into_iter().rev() {
+                    self.restore(savepoint.saved);
+                }
+                Ok(Table::new(""))
+            }
+            // the savepoint stays, the ones after it are removed
+            Statement::Rollback(Some(name)) => {
+                let position = self.savepoint(&name)?;
+                let later: Vec<Savepoint> = self.savepoints.drain(position + 1..).collect();
+                for savepoint in later.into_iter().rev() {
+                    self.restore(savepoint.saved);
+                }
+                let saved = std::mem::take(&mut self.savepoints[position].saved);
+                self.restore(saved);
+                Ok(Table::new(""))
+            }
+            // a savepoint outside a transaction starts one
+            Statement::Savepoint(name) => {
+                self.savepoints.push(Savepoint {
+                    name: Some(name),
+                    saved: HashMap::new(),
+                });
+                Ok(Table::new(""))
+            }
+            // the changes since the savepoint become part of the one before it,
+            // or are committed when there is none
+            Statement::Release(name) => {
+                let position = self.savepoint(&name)?;
+                let released: Vec<Savepoint> = self.savepoints.drain(position..).collect();
+                if let Some(previous) = self.savepoints.last_mut() {
+                    for savepoint in released {
+                        for (table, saved) in savepoint.saved {
+                            previous.saved.entry(table).or_insert(saved);
+                        }
+                    }
+                }
+                Ok(Table::new(""))
+            }
+            Statement::Pragma { name, argument } => self.pragma(&name, argument.as_deref()),
+        }
+    }
+
+    /// the rows with a foreign key that refers to no row of its parent table,
+    /// by table name and rowid
+    /// foreign keys are not checked on insert, so that the tables can be loaded in any order
+    pub fn check_foreign_keys(&mut self) -> anyhow::Result<Vec<Orphan>> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        let mut orphans = vec![];
+        for name in names {
+            orphans.extend(self.foreign_key_check(&name)?);
+        }
+        Ok(orphans)
+    }
+
+    /// the orphans of one table, see check_foreign_keys
+    fn foreign_key_check(&mut self, name: &str) -> anyhow::Result<Vec<Orphan>> {
+        self.load_table(name)?;
+        let foreign_keys = self
+            .table(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?
+            .foreign_keys();
+        for (_, parent, _) in &foreign_keys {
+            self.load_table(parent)?;
+        }
+        let table = &self.tables[name];
+        let mut orphans = vec![];
+        for (fkid, (columns, parent_name, parent_columns)) in foreign_keys.into_iter().enumerate() {
+            let parent = self
+                .table(&parent_name)
+                .ok_or_else(|| anyhow!("No such table: {}", parent_name))?;
+            let parent_columns = parent.referenced(&parent_columns, name)?;
+            if parent_columns.len() != columns.len() {
+                return Err(anyhow!(
+                    "Foreign key mismatch: {} referencing {}",
+                    name,
+                    parent_name
+                ));
+            }
+            for rowid in table.orphans(&columns, parent, &parent_columns) {
+                orphans.push(Orphan {
+                    table: name.to_string(),
+                    rowid,
+                    parent: parent_name.clone(),
+                    fkid,
+                });
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// pragma foreign_key_check [(table)]: the orphans as a table, see check_foreign_keys
+    fn pragma(&mut self, name: &str, argument: Option<&str>) -> anyhow::Result<Table> {
+        if !name.eq_ignore_ascii_case("foreign_key_check") {
+            return Err(anyhow!("Unknown pragma: {}", name));
+        }
+        let orphans = match argument {
+            Some(table) => self.foreign_key_check(table)?,
+            None => self.check_foreign_keys()?,
+        };
+        let mut result = Table::new("");
+        for column in ["table", "rowid", "parent", "fkid"] {
+            result.add_column(column, false);
+        }
+        for orphan in orphans {
+            let mut record = Record::default();
+            record.add_value(Value::from_text(orphan.table));
+            record.add_value(Value::from_i64(orphan.rowid as i64));
+            record.add_value(Value::from_text(orphan.parent));
+            record.add_value(Value::from_i64(orphan.fkid as i64));
+            result.append(record)?;
+        }
+        Ok(result)
+    }
+
+    /// true inside a transaction
+    pub fn in_transaction(&self) -> bool {
+        !self.savepoints.is_empty()
+    }
+
+    /// the table as it was before the open transaction changed it, as other readers see it
+    /// outside a transaction, the same as table()
+    pub fn committed(&self, name: &str) -> Option<&Table> {
+        for savepoint in &self.savepoints {
+            if let Some(saved) = savepoint.saved.get(name) {
+                return saved.as_ref();
+            }
+        }
+        self.tables.get(name)
+    }
+
+    /// keeps a copy of the table for rollback, when it changes for the first time since the last savepoint
+    /// the copy shares its pages with the table until one of them is written
+    /// a table that is loaded from a csv file must be loaded before
+    fn save(&mut self, name: &str) {
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            if !savepoint.saved.contains_key(name) {
+                savepoint
+                    .saved
+                    .insert(name.to_string(), self.tables.get(name).cloned());
+            }
+        }
+    }
+
+    fn restore(&mut self, saved: HashMap<String, Option<Table>>) {
+        for (name, table) in saved {
+            match table {
+                Some(table) => self.tables.insert(name, table),
+                None => self.tables.remove(&name),
+            };
+        }
+    }
+
+    /// the position of the last savepoint with the name
+    fn savepoint(&self, name: &str) -> anyhow::Result<usize> {
+        self.savepoints
+            .iter()
+            .rposition(|savepoint| savepoint.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("No such savepoint: {}", name))
+    }
+
+    fn alter_table(&mut self, name: &str, action: &AlterAction) -> anyhow::Result<()> {
+        self.load_table(name)?;
+        let mut table = self
+            .tables
+            .remove(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?;
+        let result = match action {
+            // the records must satisfy the constraints of the column, or it is not added
+            AlterAction::AddColumn(column) => {
+                let mut altered = table.clone();
+                altered
+                    .append_column(&column.name)
+                    .and_then(|_| self.define_column(&mut altered, column))
+                    .map(|_| table = altered)
+            }
+            AlterAction::DropColumn(column) => table.drop_column(column),
+            AlterAction::RenameColumn(column, new_name) => table.rename_column(column, new_name),
+            AlterAction::RenameTo(new_name) if self.tables.contains_key(new_name) => {
+                Err(anyhow!("Table {} already exists", new_name))
+            }
+            AlterAction::RenameTo(new_name) => {
+                for child in self.tables.values_mut() {
+                    child.rename_parent(name, new_name);
+                }
+                table.rename_parent(name, new_name);
+                table.rename(new_name);
+                self.tables.insert(new_name.clone(), table);
+                return Ok(());
+            }
+        };
+        self.tables.insert(name.to_string(), table);
+        result
+    }
+
+    /// the affinity, collation and constraints of a column that was added to the table
+    fn define_column(&self, table: &mut Table, column: &ColumnDefinition) -> anyhow::Result<()> {
+        if let Some(type_name) = &column.type_name {
+            table.set_affinity(&column.name, Affinity::from_type_name(type_name))?;
+        }
+        if let Some(collation) = &column.collation {
+            table.set_collation(&column.name, self.collation(collation)?)?;
+        }
+        for constraint in &column.constraints {
+            table.add_constraint(constraint, self)?;
+        }
+        Ok(())
+    }
+
+    /// computes all new records before changing any, so every expression sees the old values
+    fn update(
+        &mut self,
+        name: &str,
+        assignments: &[(String, Expression)],
+        where_clause: Option<&Expression>,
+    ) -> anyhow::Result<()> {
+        self.load_table(name)?;
+        let table = self
+            .table(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?;
+        let assignments = assignments
+            .iter()
+            .map(|(column, expression)| {
+                table
+                    .find_index(column)
+                    .map(|index| (index, expression))
+                    .ok_or_else(|| anyhow!("Table {} has no column named {}", name, column))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut updated = vec![];
+        for record in self.matching(table, where_clause)? {
+            let mut new = record.clone();
+            for (index, expression) in &assignments {
+                new.values[*index] = evaluate(expression, self, table, &record)?;
+            }
+            updated.push(new);
+        }
+        // all or nothing: when a record breaks a constraint, the ones before it are restored
+        let mut table = self.tables.remove(name).unwrap();
+        let mut done = vec![];
+        let mut result = Ok(());
+        for record in updated {
+            let old = table.get(record.rowid);
+            match table.update_checked(record, self) {
+                Ok(_) => done.extend(old),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        if result.is_err() {
+            for record in done.into_iter().rev() {
+                table
+                    .update(record)
+                    .expect("a record that was in the table fits in a page");
+            }
+        }
+        self.tables.insert(name.to_string(), table);
+        result
+    }
+
+    fn delete(&mut self, name: &str, where_clause: Option<&Expression>) -> anyhow::Result<()> {
+        self.load_table(name)?;
+        let table = self
+            .table(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?;
+        let rowids: Vec<u64> = self
+            .matching(table, where_clause)?
+            .iter()
+            .map(|record| record.rowid)
+            .collect();
+        let table = self.tables.get_mut(name).unwrap();
+        for rowid in rowids {
+            table.delete(rowid);
+        }
+        Ok(())
+    }
+
+    /// the records of the table for which the where clause is true, all without one
+    fn matching(
+        &self,
+        table: &Table,
+        where_clause: Option<&Expression>,
+    ) -> anyhow::Result<Vec<Record>> {
+        let mut records = vec![];
+        for record in table.iter() {
+            let matches = match where_clause {
+                Some(predicate) => truth(&evaluate(predicate, self, table, &record)?) == Some(true),
+                None => true,
+            };
+            if matches {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    fn select(&mut self, select: &Select) -> anyhow::Result<Table> {
+        // common table expressions are tables until the end of the statement
+        let mut shadowed = vec![];
+        let result = self
+            .common_tables(select, &mut shadowed)
+            .and_then(|_| self.query(select));
+        for (name, table) in shadowed.into_iter().rev() {
+            match table {
+                Some(table) => self.tables.insert(name, table),
+                None => self.tables.remove(&name),
+            };
+        }
+        result
+    }
+
+    /// inserts all rows, or none when one of them is invalid
+    /// columns that are not listed get NULL
+    fn insert(
+        &mut self,
+        name: &str,
+        columns: &[String],
+        source: &InsertSource,
+    ) -> anyhow::Result<()> {
+        self.load_table(name)?;
+        let table = self
+            .table(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?;
+        let indexes = if columns.is_empty() {
+            (0..table.cols.len()).collect()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    table
+                        .find_index(column)
+                        .ok_or_else(|| anyhow!("Table {} has no column named {}", name, column))
+                })
+                .collect::<anyhow::Result<Vec<usize>>>()?
+        };
+        let width = table.cols.len();
+
+        let rows: Vec<Vec<Value>> = match source {
+            InsertSource::Values(rows) => {
+                let (empty, record) = (Table::new(""), Record::default());
+                rows.iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|expression| evaluate(expression, self, &empty, &record))
+                            .collect()
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+            InsertSource::Select(select) => self
+                .select(select)?
+                .iter()
+                .map(|record| record.values)
+                .collect(),
+        };
+        let mut records = vec![];
+        for row in rows {
+            if row.len() != indexes.len() {
+                return Err(anyhow!(
+                    "Table {} has {} values for {} columns",
+                    name,
+                    row.len(),
+                    indexes.len()
+                ));
+            }
+            let mut values = vec![Value::null(); width];
+            for (index, value) in indexes.iter().zip(row) {
+                values[*index] = value;
+            }
+            let mut record = Record::default();
+            for value in values {
+                record.add_value(value);
+            }
+            records.push(record);
+        }
+
+        let mut table = self
+            .tables
+            .remove(name)
+            .ok_or_else(|| anyhow!("No such table: {}", name))?;
+        let mut inserted = vec![];
+        let mut result = Ok(());
+        for record in records {
+            match table.insert_checked(record, self) {
+                Ok(rowid) => inserted.push(rowid),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        if result.is_err() {
+            for rowid in inserted {
+                table.delete(rowid);
+            }
+        }
+        self.tables.insert(name.to_string(), table);
+        result
+    }
+
+    fn query(&mut self, select: &Select) -> anyhow::Result<Table> {
+        self.load_tables(select)?;
+        Vm::new(self, compiler::compile(select, self)?).run()
+    }
+
+    /// adds the common table expressions in the select and its subqueries as tables
+    /// the tables they replace are added to shadowed, to be restored after the statement
+    fn common_tables(
+        &mut self,
+        select: &Select,
+        shadowed: &mut Vec<(String, Option<Table>)>,
+    ) -> anyhow::Result<()> {
+        if let Some(with) = &select.with {
+            for (index, cte) in with.tables.iter().enumerate() {
+                self.common_tables(&cte.select, shadowed)?;
+                if let Some((step, _)) = &cte.step {
+                    self.common_tables(step, shadowed)?;
+                }
+                // the limit of the select counts, when nothing else reads the table
+                let needed = match &select.from {
+                    Some(TableReference::Table(name, _))
+                        if *name == cte.name
+                            && !with.tables[index + 1..]
+                                .iter()
+                                .any(|later| cte_reads(later, name))
+                            && !subqueries(select).iter().any(|s| reads(s, name)) =>
+                    {
+                        compiler::needed_records(select, self)?
+                    }
+                    _ => None,
+                };
+                let table = self.common_table(cte, needed, shadowed)?;
+                shadowed.push((
+                    cte.name.clone(),
+                    self.tables.insert(cte.name.clone(), table),
+                ));
+            }
+        }
+        for subquery in subqueries(select) {
+            self.common_tables(&subquery, shadowed)?;
+        }
+        Ok(())
+    }
+
+    /// the rows of the select, and for recursive ones those of the step after union [all]
+    /// the step is run until it adds no more rows, or there are as many as needed,
+    /// each time with only the rows it added the previous time in the table
+    fn common_table(
+        &mut self,
+        cte: &CommonTableExpression,
+        needed: Option<usize>,
+        shadowed: &mut Vec<(String, Option<Table>)>,
+    ) -> anyhow::Result<Table> {
+        let initial = self.query(&cte.select)?;
+        let names = if cte.columns.is_empty() {
+            initial.cols.clone()
+        } else if cte.columns.len() == initial.cols.len() {
+            cte.columns.clone()
+        } else {
+            return Err(anyhow!(
+                "Table {} has {} values for {} columns",
+                cte.name,
+                initial.cols.len(),
+                cte.columns.len()
+            ));
+        };
+        let mut result = Table::new(cte.name.as_str());
+        for (index, name) in names.iter().enumerate() {
+            result.add_column(name, true);
+            let name = result.cols[index].clone();
+            result.set_collation(&name, initial.collation(index).clone())?;
+        }
+
+        let Some((step, all)) = &cte.step else {
+            for record in initial.iter() {
+                result.insert(record)?;
+            }
+            return Ok(result);
+        };
+        // union without all: only rows that were not seen before
+        let mut seen = BTreeSet::new();
+        let mut add = |result: &mut Table, rows: &Table| -> anyhow::Result<Table> {
+            if rows.cols.len() != result.cols.len() {
+                return Err(anyhow!(
+                    "Selects to the left and right of union do not have the same number of result columns"
+                ));
+            }
+            let mut added = result.empty_copy();
+            for record in rows.iter() {
+                if *all || seen.insert(record.values.clone()) {
+                    result.append(record.clone())?;
+                    added.insert(record)?;
+                }
+            }
+            Ok(added)
+        };
+        let mut added = add(&mut result, &initial)?;
+        let mut count = added.iter().count();
+        shadowed.push((cte.name.clone(), self.tables.remove(&cte.name)));
+        let mut steps = 0;
+        while added.iter().next().is_some() && needed.is_none_or(|needed| count < needed) {
+            if steps == MAX_RECURSION {
+                return Err(anyhow!(
+                    "Recursive table {} did not end after {} steps",
+                    cte.name,
+                    MAX_RECURSION
+                ));
+            }
+            steps += 1;
+            self.tables.insert(cte.name.clone(), added);
+            let rows = self.query(step)?;
+            added = add(&mut result, &rows)?;
+            count += added.iter().count();
+        }
+        Ok(result)
+    }
+
+    /// loads the tables of the select and its subqueries
+    fn load_tables(&mut self, select: &Select) -> anyhow::Result<()> {
+        if let Some(TableReference::Table(name, _)) = &select.from {
+            self.load_table(name)?;
+        }
+        for subquery in subqueries(select) {
+            self.load_tables(&subquery)?;
+        }
+        Ok(())
+    }
+
+    /// a file with rows that cannot be loaded, like ones larger than a page, is an error
+    fn load_table(&mut self, name: &str) -> anyhow::Result<()> {
+        if !self.tables.contains_key(name) && Path::new(name).is_file() {
+            let csv = std::fs::read_to_string(name)?;
+            let (table, rejected) = Table::from_csv_checked(csv, &CsvOptions::new())?;
+            if let Some(first) = rejected.first() {
+                return Err(anyhow!(
+                    "{} rows of {} could not be loaded, the first on line {}: {}",
+                    rejected.len(),
+                    name,
+                    first.line,
+                    first.message
+                ));
+            }
+            self.tables.insert(name.to_string(), table);
+        }
+        Ok(())
+    }
+}
+
+/// the subqueries in the expressions of the select, not those within them,
+/// and the parts of a compound select
+fn subqueries(select: &Select) -> Vec<Select> {
+    let mut subqueries: Vec<Select> = select
+        .compound
+        .iter()
+        .map(|(_, part)| (**part).clone())
+        .collect();
+    for expression in select.expressions() {
+        expression.walk(&mut |e| match e {
+            Expression::Subquery(select)
+            | Expression::Exists(select)
+            | Expression::InSelect(_, select, _) => subqueries.push((**select).clone()),
+            _ => {}
+        });
+    }
+    subqueries
+}
+
+/// true when the select, its common table expressions or its subqueries read the table
+fn reads(select: &Select, name: &str) -> bool {
+    matches!(&select.from, Some(TableReference::Table(from, _)) if from == name)
+        || select
+            .with
+            .iter()
+            .flat_map(|with| &with.tables)
+            .any(|cte| cte_reads(cte, name))
+        || subqueries(select).iter().any(|s| reads(s, name))
+}
+
+fn cte_reads(cte: &CommonTableExpression, name: &str) -> bool {
+    reads(&cte.select, name) || cte.step.as_ref().is_some_and(|(step, _)| reads(step, name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::read::CsvOptions;
+    use crate::vm::Opcode;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn database() -> Database {
+        let mut database = Database::new();
+        let sales = Table::from_csv(
+            "region,product,amount\nnorth,apples,10\nnorth,pears,5\nsouth,apples,7\nsouth,pears,1\n",
+            Some(","),
+        );
+        database.add_table("sales", sales);
+        database
+    }
+
+    fn column(table: &Table, index: usize) -> Vec<String> {
+        table.iter().map(|r| r.get(index).to_string()).collect()
+    }
+
+    #[test]
+    fn test_rollup_subtotals() {
+        let result = database()
+            .execute(
+                "select region, product, sum(amount) as total, grouping(region, product) as level
+                 from sales
+                 group by rollup(region, product)
+                 having sum(amount) > 5
+                 order by level desc, region",
+            )
+            .unwrap();
+        assert_eq!(
+            result.iter_colums().cloned().collect::<Vec<String>>(),
+            vec!["region", "product", "total", "level"]
+        );
+        assert_eq!(
+            column(&result, 0),
+            vec!["NULL", "north", "south", "north", "south"]
+        );
+        assert_eq!(column(&result, 2), vec!["23", "15", "8", "10", "7"]);
+        assert_eq!(column(&result, 3), vec!["3", "1", "1", "0", "0"]);
+    }
+
+    #[test]
+    fn test_aggregate_without_group_by() {
+        let result = database()
+            .execute("select count(*), max(amount) from sales where region = 'south'")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["2"]);
+        assert_eq!(column(&result, 1), vec!["7"]);
+    }
+
+    #[test]
+    fn test_aggregate_of_empty_input() {
+        let result = database()
+            .execute("select count(*), sum(amount) from sales where region = 'x'")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["0"]);
+        assert_eq!(column(&result, 1), vec!["NULL"]);
+
+        let result = database()
+            .execute(
+                "select region, count(*) from sales where region = 'x' group by rollup(region)",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["NULL"]);
+        assert_eq!(column(&result, 1), vec!["0"]);
+    }
+
+    #[test]
+    fn test_index_scan_and_order() {
+        let mut database = database();
+        database
+            .execute("create index idx_amount on sales (amount)")
+            .unwrap();
+        let result = database
+            .execute("select product, amount from sales where amount >= 5 order by amount desc")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["10", "7", "5"]);
+
+        let Statement::Select(select) =
+            parser::parse("select * from sales where amount >= 5 order by amount").unwrap()
+        else {
+            panic!("expected select");
+        };
+        let code = compiler::compile(&select, &database).unwrap();
+        assert!(code.iter().any(|op| matches!(op, Opcode::ApplyIndex(_))));
+        assert!(!code.iter().any(|op| matches!(op, Opcode::Sort(_))));
+    }
+
+    fn names() -> Database {
+        let mut database = Database::new();
+        let names = Table::from_csv("name\nbob\nAlice\nBOB\nalice  \n", Some(","));
+        database.add_table("names", names);
+        database
+    }
+
+    #[test]
+    fn test_collate() {
+        let mut database = names();
+        let result = database
+            .execute("select name from names where name = 'BOB' collate nocase")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["bob", "BOB"]);
+        let result = database
+            .execute("select name collate nocase, count(*) from names group by name collate nocase order by 1")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["Alice", "alice  ", "bob"]);
+        assert_eq!(column(&result, 1), vec!["1", "1", "2"]);
+        assert!(database
+            .execute("select name from names order by name collate nope")
+            .is_err());
+    }
+
+    #[test]
+    fn test_column_collation_and_index() {
+        let mut database = names();
+        database
+            .execute("create index idx_name on names (name)")
+            .unwrap();
+        database
+            .table_mut("names")
+            .unwrap()
+            .set_collation("name", Collation::unicode())
+            .unwrap();
+        let result = database
+            .execute("select name from names where name >= 'ALICE' and name < 'b' order by name")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["Alice", "alice  "]);
+        let result = database
+            .execute("select name from names where name = 'alice' collate rtrim")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["alice  "]);
+    }
+
+    #[test]
+    fn test_register_collation() {
+        let mut database = names();
+        database.register_collation("length", |l, r| l.len().cmp(&r.len()));
+        let result = database
+            .execute("select name from names order by name collate length desc, name")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["alice  ", "Alice", "BOB", "bob"]);
+    }
+
+    #[test]
+    fn test_dates() {
+        let mut database = Database::new();
+        let orders = Table::from_csv(
+            "id,ordered\n1,2024-01-12\n2,2023-02-01\n3,2024-03-31 18:30\n",
+            Some(","),
+        );
+        database.add_table("orders", orders);
+        let result = database
+            .execute(
+                "select id, ordered + interval '1 month' as due, date_trunc('month', ordered)
+                 from orders
+                 where ordered >= '2024-01-01'
+                 order by ordered desc",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["3", "1"]);
+        assert_eq!(
+            column(&result, 1),
+            vec!["2024-04-30 18:30:00", "2024-02-12"]
+        );
+        assert_eq!(
+            column(&result, 2),
+            vec!["2024-03-01 00:00:00", "2024-01-01"]
+        );
+        let result = database
+            .execute(
+                "select strftime('%Y-%m', ordered), date_diff('day', date '2024-01-01', ordered)
+                 from orders where id = 1",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["2024-01"]);
+        assert_eq!(column(&result, 1), vec!["11"]);
+    }
+
+    #[test]
+    fn test_decimals() {
+        let mut database = Database::new();
+        let prices = Table::from_csv("item,price\na,19.99\nb,0.10\nc,0.20\n", Some(","));
+        database.add_table("prices", prices);
+        let result = database
+            .execute(
+                "select sum(price), avg(price), sum(price) * 3, sum(price) = 20.29 from prices",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["20.29"]);
+        assert_eq!(column(&result, 1), vec!["6.76333333"]);
+        assert_eq!(column(&result, 2), vec!["60.87"]);
+        assert_eq!(column(&result, 3), vec!["1"]);
+        let result = database
+            .execute("select item from prices where price > 0.1 order by price")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["c", "a"]);
+    }
+
+    #[test]
+    fn test_booleans() {
+        let mut database = Database::new();
+        let users = Table::from_csv("name,active\nann,yes\nbob,no\n", Some(","));
+        database.add_table("users", users);
+        let result = database
+            .execute("select name from users where active")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["ann"]);
+        let result = database
+            .execute(
+                "select name, active is not true from users where not active or active is false",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["bob"]);
+        assert_eq!(column(&result, 1), vec!["1"]);
+        let result = database
+            .execute("select name from users where active = true and active is not false")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["ann"]);
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut database = database();
+        let result = database
+            .execute(
+                "select cast(amount as varchar(10)), cast('2.5' as real) * 2, try_cast(region as integer)
+                 from sales where product = 'apples' and region = 'north'",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["10"]);
+        assert_eq!(column(&result, 1), vec!["5"]);
+        assert_eq!(column(&result, 2), vec!["NULL"]);
+        let error = database
+            .execute("select cast(region as integer) from sales")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Cannot cast 'north' to INTEGER");
+    }
+
+    #[test]
+    fn test_functions() {
+        let result = database()
+            .execute(
+                "select upper(region), printf('%s: %03d', product, amount), round(amount / 3.0, 2)
+                 from sales where region = 'north' order by 2",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["NORTH", "NORTH"]);
+        assert_eq!(column(&result, 1), vec!["apples: 010", "pears: 005"]);
+        assert_eq!(column(&result, 2), vec!["3.33", "1.67"]);
+        assert!(database().execute("select nope(1)").is_err());
+    }
+
+    #[test]
+    fn test_register_scalar() {
+        let mut database = database();
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        database.register_scalar("double", Some(1), true, move |args| {
+            counter.set(counter.get() + 1);
+            Ok(Value::from_i64(args[0].as_i64().unwrap_or_default() * 2))
+        });
+        let result = database
+            .execute("select product, double(amount) from sales where amount > double(3)")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["20", "14"]);
+        // once for the constant, once per selected record
+        assert_eq!(calls.get(), 3);
+        assert!(database.execute("select double(1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_register_aggregate() {
+        let mut database = database();
+        database.register_aggregate(
+            "product",
+            || 1i64,
+            |state, args| {
+                *state *= args[0].as_i64().unwrap_or(1);
+                Ok(())
+            },
+            |state| Ok(Value::from_i64(state)),
+        );
+        let result = database
+            .execute("select region, product(amount) from sales group by region order by 1")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["50", "7"]);
+    }
+
+    #[test]
+    fn test_regexp() {
+        let mut database = Database::new();
+        let phones = Table::from_csv("phone\n+31 6-1234\n06 1234\nunknown\n", Some(","));
+        database.add_table("phones", phones);
+        let result = database
+            .execute(
+                r"select regexp_replace(phone, '[^0-9+]', '') from phones
+                  where phone regexp '\d' and phone not regexp '^0'",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["+3161234"]);
+        assert!(database
+            .execute("select phone from phones where phone regexp '('")
+            .is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let mut database = Database::new();
+        let orders = Table::from_csv(
+            "id;doc\n1;{\"customer\": {\"name\": \"Ann\"}, \"items\": [3, 4]}\n2;{\"items\": []}\n",
+            Some(";"),
+        );
+        database.add_table("orders", orders);
+        let result = database
+            .execute(
+                "select id, doc -> 'customer' ->> 'name', json_array_length(doc, '$.items')
+                 from orders where doc ->> '$.items[0]' = 3",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["Ann"]);
+        assert_eq!(column(&result, 2), vec!["2"]);
+        let result = database
+            .execute("select key, value, type from json_each('{\"a\": [1, 2], \"b\": \"x\"}')")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["a", "b"]);
+        assert_eq!(column(&result, 1), vec!["[1,2]", "x"]);
+        assert_eq!(column(&result, 2), vec!["array", "text"]);
+        let result = database
+            .execute("select sum(value) from json_tree('[1, [2, 3]]') where atom is not null")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["6"]);
+        assert!(database.execute("select * from json_nope('[]')").is_err());
+    }
+
+    #[test]
+    fn test_case() {
+        let result = database()
+            .execute(
+                "select case when amount >= 10 then 'high' when amount >= 5 then 'mid' else 'low' end,
+                        case region when 'north' then 'N' end
+                 from sales",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["high", "mid", "mid", "low"]);
+        assert_eq!(column(&result, 1), vec!["N", "N", "NULL", "NULL"]);
+    }
+
+    #[test]
+    fn test_subqueries() {
+        let mut database = database();
+        let regions = Table::from_csv("region,manager\nnorth,Ann\nwest,Bob\n", Some(","));
+        database.add_table("regions", regions);
+        let result = database
+            .execute(
+                "select product, amount from sales
+                 where amount > (select avg(amount) from sales)
+                   and region in (select region from regions)",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["apples"]);
+        let result = database
+            .execute(
+                "select manager from regions
+                 where not exists (select * from sales where sales.region = regions.region)",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["Bob"]);
+        // correlated: the best selling product per region
+        let result = database
+            .execute(
+                "select region, product, (select count(*) from regions where manager = 'Ann') as n
+                 from sales
+                 where amount = (select max(amount) from sales as s where s.region = sales.region)",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["apples", "apples"]);
+        assert_eq!(column(&result, 2), vec!["1", "1"]);
+        assert!(database
+            .execute("select (select region, product from sales)")
+            .is_err());
+    }
+
+    #[test]
+    fn test_with() {
+        let mut database = database();
+        let result = database
+            .execute(
+                "with totals as (select region, sum(amount) as total from sales group by region),
+                      best(name) as (select region from totals where total = (select max(total) from totals))
+                 select name from best",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["north"]);
+        // the table replaced by a common table expression is back after the statement
+        let result = database
+            .execute(
+                "with sales as (select * from sales where amount > 5) select count(*) from sales",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["2"]);
+        let result = database.execute("select count(*) from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["4"]);
+        assert!(database.table("totals").is_none());
+        assert!(database
+            .execute("with t(a, b) as (select 1) select * from t")
+            .is_err());
+    }
+
+    #[test]
+    fn test_distinct() {
+        let mut database = database();
+        let result = database
+            .execute("select distinct region from sales order by amount")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["south", "north"]);
+        let result = database
+            .execute(
+                "select count(distinct product), count(product), sum(distinct amount) from sales",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["2"]);
+        assert_eq!(column(&result, 1), vec!["4"]);
+        assert_eq!(column(&result, 2), vec!["23"]);
+        // the first row for every region, by the order by
+        let result = database
+            .execute(
+                "select distinct on (region) region, product from sales order by region, amount desc",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["apples", "apples"]);
+        let result = database
+            .execute("select distinct on (1) product as p, region from sales order by p, amount")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["south", "south"]);
+        let result = database
+            .execute(r#"select distinct value collate nocase from json_each('["a", "A", "b"]')"#)
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["a", "b"]);
+        assert!(database
+            .execute("select distinct region from sales where distinct amount")
+            .is_err());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut database = database();
+        database
+            .execute("create index by_amount on sales (amount)")
+            .unwrap();
+        database
+            .execute("insert into sales values ('east', 'plums', 3), ('east', 'kiwis', 1 + 1)")
+            .unwrap();
+        database
+            .execute("insert into sales (amount, region) values (4, 'west')")
+            .unwrap();
+        let result = database
+            .execute("select region, product from sales where amount = 4")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["west"]);
+        assert!(result.iter().next().unwrap().get(1).is_null());
+        database
+            .execute("insert into sales select region, product, amount * 10 from sales where region = 'east'")
+            .unwrap();
+        let result = database
+            .execute("select sum(amount), count(*) from sales")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["82"]);
+        assert_eq!(column(&result, 1), vec!["9"]);
+        // every record has its own rowid
+        let rowids: Vec<u64> = database
+            .table("sales")
+            .unwrap()
+            .iter()
+            .map(|r| r.rowid)
+            .collect();
+        assert_eq!(rowids, (1..=9).collect::<Vec<u64>>());
+
+        // nothing is inserted when a row is invalid
+        assert!(database
+            .execute("insert into sales values ('x', 'y', 1), ('z')")
+            .is_err());
+        assert!(database
+            .execute("insert into sales (region, colour) values ('x', 'y')")
+            .is_err());
+        assert!(database
+            .execute("insert into sales select region from sales")
+            .is_err());
+        assert!(database.execute("insert into nothing values (1)").is_err());
+        let result = database.execute("select count(*) from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["9"]);
+
+        // a record larger than a page is an error, not a panic
+        assert!(database
+            .execute("insert into sales values ('x', printf('%5000s', 'y'), 1)")
+            .is_err());
+        // also when its length does not fit in 16 bits
+        assert!(database
+            .execute("insert into sales values (printf('%40000s', 'x'), printf('%40000s', 'y'), 1)")
+            .is_err());
+        let result = database.execute("select count(*) from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["9"]);
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let mut database = database();
+        database
+            .execute("create index by_product on sales (product)")
+            .unwrap();
+        database
+            .execute("update sales set amount = amount * 2, product = upper(product) where region = 'north'")
+            .unwrap();
+        let result = database
+            .execute("select product, amount from sales where product = 'APPLES'")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["20"]);
+        let result = database
+            .execute("select count(*) from sales where product = 'apples'")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["1"]);
+        // every expression sees the old values
+        database
+            .execute("update sales set amount = (select max(amount) from sales) - amount")
+            .unwrap();
+        let result = database.execute("select amount from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["0", "10", "13", "19"]);
+
+        database
+            .execute("delete from sales where amount > 10")
+            .unwrap();
+        let result = database
+            .execute("select region, product from sales")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["APPLES", "PEARS"]);
+        assert!(database.execute("update sales set colour = 1").is_err());
+        // a record larger than a page is an error, and no record is changed
+        assert!(database
+            .execute("update sales set product = printf('%5000s', 'x') where region = 'north'")
+            .is_err());
+        let result = database
+            .execute("select product from sales where region = 'north'")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["APPLES", "PEARS"]);
+        assert!(database.execute("delete from nothing").is_err());
+        database.execute("delete from sales").unwrap();
+        let result = database.execute("select count(*) from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["0"]);
+    }
+
+    #[test]
+    fn test_update_and_delete_of_sorted_copy() {
+        let mut database = database();
+        database
+            .execute("create table sorted as select * from sales order by amount desc")
+            .unwrap();
+        database
+            .execute("update sorted set amount = 0 where product = 'pears'")
+            .unwrap();
+        database
+            .execute("delete from sorted where region = 'south' and amount > 0")
+            .unwrap();
+        let result = database
+            .execute("select product, amount from sorted")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["apples", "pears", "pears"]);
+        assert_eq!(column(&result, 1), vec!["10", "0", "0"]);
+        // a record cannot be inserted before the last one
+        let mut record = database.table("sorted").unwrap().iter().next().unwrap();
+        record.rowid = 2;
+        assert!(database
+            .table_mut("sorted")
+            .unwrap()
+            .insert(record)
+            .is_err());
+    }
+
+    #[test]
+    fn test_create_and_alter_table() {
+        let mut database = database();
+        database
+            .execute("create table people (name text collate nocase, age integer)")
+            .unwrap();
+        database
+            .execute("insert into people values ('Ann', '42'), ('bob', 7)")
+            .unwrap();
+        let result = database
+            .execute("select name, age + 1 from people where name = 'ANN'")
+            .unwrap();
+        assert_eq!(column(&result, 1), vec!["43"]);
+        assert!(database.execute("create table people (a)").is_err());
+        database
+            .execute("create table if not exists people (a)")
+            .unwrap();
+        assert!(database.execute("create table t (a, a)").is_err());
+
+        // existing records get NULL for an added column, and lose the values of a dropped one
+        database
+            .execute("create index by_age on people (age)")
+            .unwrap();
+        database
+            .execute("alter table people add column score real")
+            .unwrap();
+        database
+            .execute("update people set score = 1 where age = 7")
+            .unwrap();
+        database
+            .execute("alter table people drop column name")
+            .unwrap();
+        let result = database.execute("select * from people").unwrap();
+        assert_eq!(result.cols, vec!["age", "score"]);
+        assert_eq!(column(&result, 1), vec!["NULL", "1"]);
+        let result = database
+            .execute("select score from people where age = 42")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["NULL"]);
+
+        database
+            .execute("alter table people rename age to years")
+            .unwrap();
+        database
+            .execute("alter table people rename to persons")
+            .unwrap();
+        let result = database.execute("select years from persons").unwrap();
+        assert_eq!(column(&result, 0), vec!["42", "7"]);
+        assert!(database.execute("select * from people").is_err());
+        assert!(database
+            .execute("alter table persons rename to sales")
+            .is_err());
+        assert!(database
+            .execute("alter table persons drop column colour")
+            .is_err());
+
+        database
+            .execute("create table totals as select region, sum(amount) as total from sales group by region")
+            .unwrap();
+        let result = database.execute("select total from totals").unwrap();
+        assert_eq!(column(&result, 0), vec!["15", "8"]);
+        database.execute("drop table totals").unwrap();
+        assert!(database.table("totals").is_none());
+        assert!(database.execute("drop table totals").is_err());
+        database.execute("drop table if exists totals").unwrap();
+    }
+
+    #[test]
+    fn test_constraints() {
+        let mut database = database();
+        database
+            .execute(
+                "create table accounts (id integer primary key, email text not null collate nocase, \
+                 amount numeric check (amount >= 0), unique (email))",
+            )
+            .unwrap();
+        database
+            .execute("insert into accounts values (1, 'a@x', 10), (2, 'b@x', 0)")
+            .unwrap();
+        let error =
+            |database: &mut Database, sql: &str| database.execute(sql).unwrap_err().to_string();
+        assert_eq!(
+            error(
+                &mut database,
+                "insert into accounts values (3, 'c@x', 1), ('1', 'd@x', 1)"
+            ),
+            "UNIQUE constraint failed: accounts.id"
+        );
+        assert_eq!(
+            error(&mut database, "insert into accounts values (3, 'A@X', 1)"),
+            "UNIQUE constraint failed: accounts.email"
+        );
+        assert_eq!(
+            error(
+                &mut database,
+                "insert into accounts (id, amount) values (3, 1)"
+            ),
+            "NOT NULL constraint failed: accounts.email"
+        );
+        assert_eq!(
+            error(&mut database, "update accounts set amount = amount - 5"),
+            "CHECK constraint failed: amount >= 0"
+        );
+        // nothing changed
+        let result = database.execute("select id, amount from accounts").unwrap();
+        assert_eq!(column(&result, 0), vec!["1", "2"]);
+        assert_eq!(column(&result, 1), vec!["10", "0"]);
+
+        database
+            .execute("update accounts set id = 3 where id = 1")
+            .unwrap();
+        assert!(database
+            .execute("alter table accounts add column owner text not null")
+            .is_err());
+        database
+            .execute("alter table accounts add column owner text")
+            .unwrap();
+        assert!(database.execute("alter table accounts drop id").is_err());
+        assert!(database
+            .execute("create table t (a primary key, b primary key)")
+            .is_err());
+        // the primary key is an index
+        let result = database
+            .execute("select email from accounts where id = 3")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["a@x"]);
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let mut database = Database::new();
+        let customers = CsvOptions::new().separator(",").primary_key(&["id"]);
+        database.add_table(
+            "customers",
+            Table::from_csv_with_options("id,name\n1,ann\n2,bob\n", &customers).unwrap(),
+        );
+        let orders =
+            CsvOptions::new()
+                .separator(",")
+                .foreign_key(&["customer_id"], "customers", &[]);
+        database.add_table(
+            "orders",
+            Table::from_csv_with_options("id,customer_id\n10,1\n11,3\n12,\n13,2\n", &orders)
+                .unwrap(),
+        );
+        database
+            .execute(
+                "create table returns (order_id integer references orders (id), \
+                 customer text, foreign key (customer) references customers (name))",
+            )
+            .unwrap();
+        // not checked on insert
+        database
+            .execute(
+                "insert into returns values (10, 'ann'), (14, 'bob'), (null, 'cyd'), ('13', null)",
+            )
+            .unwrap();
+
+        let orphans = database.check_foreign_keys().unwrap();
+        let found: Vec<(&str, u64, &str, usize)> = orphans
+            .iter()
+            .map(|o| (o.table.as_str(), o.rowid, o.parent.as_str(), o.fkid))
+            .collect();
+        // an empty customer_id is text, a NULL one would be no orphan
+        assert_eq!(
+            found,
+            vec![
+                ("orders", 2, "customers", 0),
+                ("orders", 3, "customers", 0),
+                ("returns", 2, "orders", 0),
+                ("returns", 3, "customers", 1),
+            ]
+        );
+        let result = database
+            .execute("pragma foreign_key_check(returns)")
+            .unwrap();
+        assert_eq!(result.cols, vec!["table", "rowid", "parent", "fkid"]);
+        assert_eq!(column(&result, 1), vec!["2", "3"]);
+        assert_eq!(column(&result, 2), vec!["orders", "customers"]);
+
+        database
+            .execute("alter table customers rename to clients")
+            .unwrap();
+        assert_eq!(database.check_foreign_keys().unwrap().len(), 4);
+        database.execute("drop table orders").unwrap();
+        assert!(database.check_foreign_keys().is_err());
+        assert!(database.execute("pragma journal_mode").is_err());
+        assert!(database
+            .execute("alter table returns drop column customer")
+            .is_err());
+        // without parent columns, the parent needs a primary key
+        database
+            .execute("create table notes (customer references returns)")
+            .unwrap();
+        assert!(database.execute("pragma foreign_key_check(notes)").is_err());
+    }
+
+    #[test]
+    fn test_transactions() {
+        let mut database = database();
+        let count = |database: &mut Database| {
+            let result = database.execute("select count(*) from sales").unwrap();
+            column(&result, 0)[0].clone()
+        };
+        database.execute("begin").unwrap();
+        database
+            .execute("delete from sales where amount > 5")
+            .unwrap();
+        database.execute("create table log (line)").unwrap();
+        assert_eq!(count(&mut database), "2");
+        // other readers see the committed table
+        assert_eq!(database.committed("sales").unwrap().iter().count(), 4);
+        assert!(database.committed("log").is_none());
+        database.execute("rollback").unwrap();
+        assert_eq!(count(&mut database), "4");
+        assert!(database.table("log").is_none());
+        assert!(!database.in_transaction());
+
+        database.execute("begin").unwrap();
+        assert!(database.execute("begin").is_err());
+        database
+            .execute("insert into sales values ('east', 'kiwis', 2)")
+            .unwrap();
+        database.execute("savepoint a").unwrap();
+        database.execute("delete from sales").unwrap();
+        database.execute("savepoint b").unwrap();
+        database
+            .execute("insert into sales values ('west', 'plums', 3)")
+            .unwrap();
+        database.execute("rollback to a").unwrap();
+        assert_eq!(count(&mut database), "5");
+        database.execute("update sales set amount = 0").unwrap();
+        database.execute("release a").unwrap();
+        database.execute("commit").unwrap();
+        let result = database
+            .execute("select sum(amount), count(*) from sales")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["0"]);
+        assert_eq!(column(&result, 1), vec!["5"]);
+        assert_eq!(database.committed("sales").unwrap().iter().count(), 5);
+
+        // a savepoint outside a transaction starts one, releasing it commits
+        database.execute("savepoint s").unwrap();
+        database.execute("delete from sales").unwrap();
+        database.execute("release s").unwrap();
+        assert!(!database.in_transaction());
+        assert_eq!(count(&mut database), "0");
+        assert!(database.execute("commit").is_err());
+        assert!(database.execute("rollback").is_err());
+        assert!(database.execute("release s").is_err());
+    }
+
+    #[test]
+    fn test_attach() {
+        let file = crate::pager::TempFile::new("database");
+        let open = || {
+            let mut database = Database::new();
+            database.attach("sales", &file.path).unwrap();
+            database
+        };
+        let count = |database: &mut Database| {
+            let result = database.execute("select count(*) from sales").unwrap();
+            column(&result, 0)[0].clone()
+        };
+        let mut database = database();
+        database.attach("sales", &file.path).unwrap();
+        database
+            .execute("insert into sales values ('east', 'kiwis', 2)")
+            .unwrap();
+        database.execute("begin").unwrap();
+        database.execute("delete from sales").unwrap();
+        assert!(database.attach("other", &file.path).is_err());
+        // an open transaction is not written
+        drop(database);
+        let mut database = open();
+        assert_eq!(count(&mut database), "5");
+
+        database.execute("begin").unwrap();
+        database
+            .execute("delete from sales where region = 'north'")
+            .unwrap();
+        database.execute("commit").unwrap();
+        database.execute("savepoint s").unwrap();
+        database
+            .execute("update sales set amount = amount * 10")
+            .unwrap();
+        database.execute("release s").unwrap();
+        assert!(database
+            .execute("alter table sales rename to sold")
+            .is_err());
+        drop(database);
+        let mut database = open();
+        let result = database.execute("select amount from sales").unwrap();
+        assert_eq!(column(&result, 0), vec!["70", "10", "20"]);
+
+        // a new table with the name replaces the one in the file
+        database.execute("drop table sales").unwrap();
+        database.execute("create table sales (n)").unwrap();
+        database.execute("insert into sales values (1)").unwrap();
+        drop(database);
+        let mut database = open();
+        assert_eq!(count(&mut database), "1");
+    }
+
+    #[test]
+    fn test_csv_with_rows_larger_than_page() {
+        let file = crate::pager::TempFile::new("large.csv");
+        let large = "x".repeat(5000);
+        let csv = format!("id,text\n1,a\n2,{}\n3,{}\n", large, large);
+        std::fs::write(&file.path, csv).unwrap();
+        let mut database = Database::new();
+        let sql = format!("select count(*) from \"{}\"", file.path.to_str().unwrap());
+        let error = database.execute(&sql).err().unwrap().to_string();
+        assert!(error.starts_with("2 rows of"), "{}", error);
+        assert!(error.contains("the first on line 3"), "{}", error);
+    }
+
+    #[test]
+    fn test_commit_of_several_attached_tables() {
+        let first = crate::pager::TempFile::new("first");
+        let second = crate::pager::TempFile::new("second");
+        let mut database = Database::new();
+        database.execute("create table a (n)").unwrap();
+        database.execute("create table b (n)").unwrap();
+        database.attach("a", &first.path).unwrap();
+        database.attach("b", &second.path).unwrap();
+
+        // b cannot be written: its description no longer fits in a page
+        database.execute("begin").unwrap();
+        database.execute("insert into a values (1)").unwrap();
+        database.execute("insert into b values (1)").unwrap();
+        let sql = format!("alter table b add column {}", "x".repeat(5000));
+        database.execute(&sql).unwrap();
+        let error = database.execute("commit").err().unwrap().to_string();
+        assert!(error.starts_with("Could not write table b"), "{}", error);
+        assert!(error.ends_with("tables not written: b"), "{}", error);
+        assert!(!database.in_transaction());
+        drop(database);
+
+        // the commit is atomic for a, and for b, but not for both
+        let mut database = Database::new();
+        database.attach("a", &first.path).unwrap();
+        database.attach("b", &second.path).unwrap();
+        let count = |database: &mut Database, table: &str| {
+            let result = database
+                .execute(&format!("select count(*) from {}", table))
+                .unwrap();
+            column(&result, 0)[0].clone()
+        };
+        assert_eq!(count(&mut database, "a"), "1");
+        assert_eq!(count(&mut database, "b"), "0");
+        assert_eq!(database.table("b").unwrap().iter_colums().count(), 1);
+    }
+
+    #[test]
+    fn test_rollback_of_csv_table() {
+        let path = std::env::temp_dir().join(format!("rollback_{}.csv", std::process::id()));
+        std::fs::write(&path, "id,name\n1,a\n2,b\n").unwrap();
+        let name = path.to_str().unwrap();
+        let mut database = Database::new();
+        // the table is loaded from the file by the first statement of the transaction
+        database.execute("begin").unwrap();
+        database
+            .execute(&format!("delete from \"{}\" where id = 1", name))
+            .unwrap();
+        assert_eq!(database.committed(name).unwrap().iter().count(), 2);
+        std::fs::remove_file(&path).unwrap();
+        database.execute("rollback").unwrap();
+        let result = database
+            .execute(&format!("select name from \"{}\"", name))
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut database = database();
+        let result = database
+            .execute("select amount from sales order by amount limit 2 offset 1")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["5", "7"]);
+        let result = database
+            .execute("select amount from sales offset 3 rows fetch first 2 rows only")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["1"]);
+        let result = database
+            .execute("select region from sales fetch next row only")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["north"]);
+        let result = database
+            .execute("select region, count(*) from sales group by region limit 1 + 0")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["north"]);
+        let result = database
+            .execute("select region from sales union select 'east' order by 1 limit 1 offset 1")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["north"]);
+        assert_eq!(
+            database
+                .execute("select * from sales limit 0")
+                .unwrap()
+                .iter()
+                .count(),
+            0
+        );
+        assert!(database.execute("select * from sales limit -1").is_err());
+        assert!(database
+            .execute("select * from sales limit amount")
+            .is_err());
+
+        // the scan stops at the limit: the malformed json in the last row is never read
+        let events = Table::from_csv(
+            "data
+{\"a\": 1}
+{\"a\": 2}
+not json
+",
+            Some(","),
+        );
+        database.add_table("events", events);
+        let result = database
+            .execute(
+                "select data ->> 'a' from events where json(data) is not null limit 1 offset 1",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["2"]);
+        assert!(database
+            .execute("select data ->> 'a' from events where json(data) is not null")
+            .is_err());
+    }
+
+    #[test]
+    fn test_compound() {
+        let mut database = database();
+        let result = database
+            .execute("select region from sales union select 'east' order by 1 desc")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["south", "north", "east"]);
+        let result = database
+            .execute("select product from sales where amount > 5 union all select product from sales where amount < 5")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["apples", "apples", "pears"]);
+        let result = database
+            .execute("select product as p from sales where region = 'north' intersect select product from sales where amount < 5")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["pears"]);
+        let result = database
+            .execute("select region from sales except select region from sales where amount = 1 order by region")
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["north"]);
+        assert!(database
+            .execute("select region, product from sales union select region from sales")
+            .is_err());
+        // the declared types are compared, not the values
+        let options = CsvOptions::new()
+            .separator(",")
+            .column_type("region", "TEXT")
+            .column_type("amount", "INTEGER");
+        let typed = Table::from_csv_with_options("region,amount\n", &options).unwrap();
+        database.add_table("typed", typed);
+        assert!(database
+            .execute("select region from typed union select amount from typed")
+            .is_err());
+        assert!(database
+            .execute("select region from typed union select region from sales")
+            .is_ok());
+        assert!(database
+            .execute("select region from sales union select amount from sales")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_with_recursive() {
+        let mut database = Database::new();
+        let employees = Table::from_csv(
+            "id,name,manager\n1,Ann,\n2,Bob,1\n3,Cid,2\n4,Dan,1\n5,Eve,9\n",
+            Some(","),
+        );
+        database.add_table("employees", employees);
+        let result = database
+            .execute(
+                "with recursive chart(id, name, level) as (
+                     select id, name, 0 from employees where id = 1
+                     union all
+                     select id, name, (select level from chart where chart.id = e.manager) + 1
+                     from employees e
+                     where manager in (select id from chart)
+                 )
+                 select name, level from chart order by level, name",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["Ann", "Bob", "Dan", "Cid"]);
+        assert_eq!(column(&result, 1), vec!["0", "1", "1", "2"]);
+        let result = database
+            .execute(
+                "with recursive n(x) as (select 1 union select mod(x, 3) + 1 from n)
+                 select sum(x) from n",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["6"]);
+
+        // the limit stops the recursion
+        let result = database
+            .execute(
+                "with recursive n(x) as (select 1 union all select x + 1 from n)
+                 select * from n limit 2 offset 3",
+            )
+            .unwrap();
+        assert_eq!(column(&result, 0), vec!["4", "5"]);
+        // also with a cycle, which without a limit is an error
+        database.add_table("links", Table::from_csv("id,next\n1,2\n2,1\n", Some(",")));
+        let path = "with recursive path(id) as (
+                        select 1 union all select next from links where id in (select id from path)
+                    )
+                    select * from path";
+        let result = database.execute(&format!("{} limit 5", path)).unwrap();
+        assert_eq!(column(&result, 0), vec!["1", "2", "1", "2", "1"]);
+        let error = database.execute(path).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Recursive table path did not end after 10000 steps"
+        );
+    }
+
+    #[test]
+    fn test_unknown_table() {
+        assert!(database().execute("select * from nope").is_err());
+    }
+}
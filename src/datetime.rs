@@ -0,0 +1,841 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::value::Value;
+use crate::vm::eval::{number, Number};
+
+// dates, times, timestamps and intervals are stored with serial type 10 (reserved in SQLite),
+// as a tag byte followed by the microseconds since 1970-01-01 00:00 UTC (since midnight for times)
+// or for intervals the months followed by the microseconds
+pub(crate) const TEMPORAL: u64 = 10;
+const TEMPORAL_LEN: usize = 9;
+const INTERVAL_TAG: u8 = 3;
+const INTERVAL_LEN: usize = 13;
+
+/// the length of the data of a value with serial type 10, from its tag byte
+pub(crate) fn encoded_len(tag: u8) -> usize {
+    if tag == INTERVAL_TAG {
+        INTERVAL_LEN
+    } else {
+        TEMPORAL_LEN
+    }
+}
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// a point in time, without time zone (all times are UTC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Temporal {
+    Date(i64),      // microseconds since the epoch, at midnight
+    Time(i64),      // microseconds since midnight
+    Timestamp(i64), // microseconds since the epoch
+}
+
+/// a duration in months and microseconds. Months vary in length, so they are kept apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub micros: i64,
+}
+
+impl Temporal {
+    /// a date from year, month (1-12) and day (1-31), None when it does not exist
+    pub fn date(year: i64, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Temporal::Date(
+            days_from_civil(year, month, day) * MICROS_PER_DAY,
+        ))
+    }
+
+    pub fn micros(&self) -> i64 {
+        match *self {
+            Temporal::Date(micros) | Temporal::Time(micros) | Temporal::Timestamp(micros) => micros,
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Temporal::Date(_) => 0,
+            Temporal::Time(_) => 1,
+            Temporal::Timestamp(_) => 2,
+        }
+    }
+
+    /// the date, time or timestamp stored in the Value, None for other values
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if value.datatype != TEMPORAL || value.data.len() != TEMPORAL_LEN {
+            return None;
+        }
+        let micros = BigEndian::read_i64(&value.data[1..]);
+        match value.data[0] {
+            0 => Some(Temporal::Date(micros)),
+            1 => Some(Temporal::Time(micros)),
+            2 => Some(Temporal::Timestamp(micros)),
+            _ => None,
+        }
+    }
+
+    /// times sort before dates and timestamps. Dates compare with timestamps at midnight
+    pub(crate) fn sort_key(&self) -> (bool, i64) {
+        (!matches!(self, Temporal::Time(_)), self.micros())
+    }
+
+    /// a date at midnight, a time within the day
+    pub(crate) fn truncated(self) -> Self {
+        self.with_micros(self.micros())
+    }
+
+    fn with_micros(&self, micros: i64) -> Self {
+        match self {
+            Temporal::Date(_) => Temporal::Date(micros.div_euclid(MICROS_PER_DAY) * MICROS_PER_DAY),
+            Temporal::Time(_) => Temporal::Time(micros.rem_euclid(MICROS_PER_DAY)),
+            Temporal::Timestamp(_) => Temporal::Timestamp(micros),
+        }
+    }
+}
+
+impl From<Temporal> for Value {
+    fn from(temporal: Temporal) -> Self {
+        let mut data = vec![temporal.tag(); TEMPORAL_LEN];
+        BigEndian::write_i64(&mut data[1..], temporal.micros());
+        Value::new(TEMPORAL, data)
+    }
+}
+
+impl Display for Temporal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Temporal::Date(micros) => write!(f, "{}", format_date(micros)),
+            Temporal::Time(micros) => write!(f, "{}", format_time(micros)),
+            Temporal::Timestamp(micros) => {
+                write!(f, "{} {}", format_date(micros), format_time(micros))
+            }
+        }
+    }
+}
+
+impl Interval {
+    pub fn new(months: i32, micros: i64) -> Self {
+        Self { months, micros }
+    }
+
+    /// the interval stored in the Value, None for other values
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if value.datatype != TEMPORAL
+            || value.data.len() != INTERVAL_LEN
+            || value.data[0] != INTERVAL_TAG
+        {
+            return None;
+        }
+        Some(Interval::new(
+            BigEndian::read_i32(&value.data[1..5]),
+            BigEndian::read_i64(&value.data[5..]),
+        ))
+    }
+
+    /// for comparisons a month counts as 30 days, like in postgres
+    pub(crate) fn sort_key(&self) -> i128 {
+        self.months as i128 * 30 * MICROS_PER_DAY as i128 + self.micros as i128
+    }
+
+    fn negate(self) -> Self {
+        Interval::new(-self.months, -self.micros)
+    }
+}
+
+impl From<Interval> for Value {
+    fn from(interval: Interval) -> Self {
+        let mut data = vec![INTERVAL_TAG; INTERVAL_LEN];
+        BigEndian::write_i32(&mut data[1..5], interval.months);
+        BigEndian::write_i64(&mut data[5..], interval.micros);
+        Value::new(TEMPORAL, data)
+    }
+}
+
+/// eg '1 year 2 months 3 days 04:05:06'
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        let (years, months) = (self.months / 12, self.months % 12);
+        let (days, micros) = (self.micros / MICROS_PER_DAY, self.micros % MICROS_PER_DAY);
+        for (n, unit) in [
+            (years as i64, "year"),
+            (months as i64, "month"),
+            (days, "day"),
+        ] {
+            if n != 0 {
+                let plural = if n.abs() == 1 { "" } else { "s" };
+                parts.push(format!("{} {}{}", n, unit, plural));
+            }
+        }
+        if micros != 0 || parts.is_empty() {
+            let sign = if micros < 0 { "-" } else { "" };
+            parts.push(format!("{}{}", sign, format_time(micros.abs())));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// parses ISO 8601: '2024-01-12', '13:45', '13:45:00.5', '2024-01-12 13:45:00',
+/// '2024-01-12T13:45:00Z' and '2024-01-12T13:45:00+01:00' (converted to UTC)
+pub fn parse_iso(text: &str) -> Option<Temporal> {
+    let text = text.trim();
+    if let Some(time) = parse_time(text) {
+        return Some(Temporal::Time(time));
+    }
+    if text.len() < 10 || !text.is_char_boundary(10) {
+        return None;
+    }
+    let (date, rest) = text.split_at(10);
+    let date = parse_date(date)?;
+    if rest.is_empty() {
+        return Some(date);
+    }
+    let rest = rest.strip_prefix(['T', ' '])?;
+    let (time, offset) = match rest.find(['Z', '+', '-']) {
+        Some(index) => (&rest[..index], parse_offset(&rest[index..])?),
+        None => (rest, 0),
+    };
+    Some(Temporal::Timestamp(
+        date.micros() + parse_time(time)? - offset,
+    ))
+}
+
+fn parse_date(text: &str) -> Option<Temporal> {
+    let mut parts = text.splitn(3, '-');
+    let year = digits(parts.next()?, 4, 4)?;
+    let month = digits(parts.next()?, 2, 2)?;
+    let day = digits(parts.next()?, 2, 2)?;
+    Temporal::date(year, month as u32, day as u32)
+}
+
+/// HH:MM[:SS[.ffffff]], in microseconds since midnight
+fn parse_time(text: &str) -> Option<i64> {
+    let (text, fraction) = match text.split_once('.') {
+        Some((text, fraction)) => (text, parse_fraction(fraction)?),
+        None => (text, 0),
+    };
+    let mut parts = text.split(':');
+    let hours = digits(parts.next()?, 2, 2)?;
+    let minutes = digits(parts.next()?, 2, 2)?;
+    let seconds = match parts.next() {
+        Some(seconds) => digits(seconds, 2, 2)?,
+        None if fraction == 0 => 0,
+        None => return None,
+    };
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(
+        hours * MICROS_PER_HOUR
+            + minutes * MICROS_PER_MINUTE
+            + seconds * MICROS_PER_SECOND
+            + fraction,
+    )
+}
+
+fn parse_fraction(text: &str) -> Option<i64> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = text.chars().chain("000000".chars()).take(6).collect();
+    digits.parse().ok()
+}
+
+/// Z, +HH:MM, -HH:MM or +HHMM, in microseconds
+fn parse_offset(text: &str) -> Option<i64> {
+    if text == "Z" {
+        return Some(0);
+    }
+    let sign = if text.starts_with('-') { -1 } else { 1 };
+    let text = text[1..].replace(':', "");
+    let hours = digits(text.get(..2)?, 2, 2)?;
+    let minutes = digits(text.get(2..)?, 2, 2)?;
+    Some(sign * (hours * MICROS_PER_HOUR + minutes * MICROS_PER_MINUTE))
+}
+
+fn digits(text: &str, min: usize, max: usize) -> Option<i64> {
+    if text.len() < min || text.len() > max || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// parses text with a format like '%d/%m/%Y' or '%m/%d/%Y %H:%M'
+/// %Y year, %m month, %d day, %H hour, %M minute, %S second (one or two digits),
+/// %f fraction of a second, %b month name ('Jan'), %% a percent sign
+/// yields a timestamp when the format has a time, a time when it only has a time, else a date
+pub fn parse_with_format(text: &str, format: &str) -> Option<Temporal> {
+    let (mut year, mut month, mut day) = (None, None, None);
+    let (mut hours, mut minutes, mut seconds, mut fraction) = (None, None, None, 0);
+    let mut input = text.trim();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            input = input.strip_prefix(c)?;
+            continue;
+        }
+        let spec = chars.next()?;
+        if spec == '%' {
+            input = input.strip_prefix('%')?;
+            continue;
+        }
+        if spec == 'b' {
+            let name = input.get(..3)?.to_lowercase();
+            month = Some(MONTHS.iter().position(|m| *m == name)? as i64 + 1);
+            input = &input[3..];
+            continue;
+        }
+        let max = match spec {
+            'Y' => 4,
+            'f' => 9,
+            'm' | 'd' | 'H' | 'M' | 'S' => 2,
+            _ => return None,
+        };
+        let len = input
+            .bytes()
+            .take(max)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        let (number, rest) = input.split_at(len);
+        input = rest;
+        match spec {
+            'Y' => year = Some(digits(number, 4, 4)?),
+            'f' => fraction = parse_fraction(number)?,
+            _ => {
+                let number = digits(number, 1, 2)?;
+                match spec {
+                    'm' => month = Some(number),
+                    'd' => day = Some(number),
+                    'H' => hours = Some(number),
+                    'M' => minutes = Some(number),
+                    _ => seconds = Some(number),
+                }
+            }
+        }
+    }
+    if !input.is_empty() {
+        return None;
+    }
+    let time = match (hours, minutes, seconds) {
+        (None, None, None) => None,
+        (h, m, s) => {
+            let (h, m, s) = (h.unwrap_or(0), m.unwrap_or(0), s.unwrap_or(0));
+            if h > 23 || m > 59 || s > 59 {
+                return None;
+            }
+            Some(h * MICROS_PER_HOUR + m * MICROS_PER_MINUTE + s * MICROS_PER_SECOND + fraction)
+        }
+    };
+    match (year, month, day, time) {
+        (None, None, None, Some(time)) => Some(Temporal::Time(time)),
+        (Some(year), Some(month), Some(day), time) => {
+            let date = Temporal::date(year, month as u32, day as u32)?;
+            Some(match time {
+                Some(time) => Temporal::Timestamp(date.micros() + time),
+                None => date,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// parses '1 year 2 months', '3 days 04:00:00', '-2 hours', '90 minutes'
+pub fn parse_interval(text: &str) -> Option<Interval> {
+    let mut interval = Interval::default();
+    let mut words = text.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        if word.contains(':') {
+            let (sign, time) = match word.strip_prefix('-') {
+                Some(time) => (-1, time),
+                None => (1, word),
+            };
+            interval.micros += sign * parse_time(time)?;
+            continue;
+        }
+        let n: f64 = word.parse().ok()?;
+        let unit = words.next()?.to_lowercase();
+        let unit = unit.trim_end_matches('s');
+        match unit {
+            "year" => interval.months += (n * 12.0) as i32,
+            "month" | "mon" => interval.months += n as i32,
+            _ => interval.micros += (n * unit_micros(unit)? as f64) as i64,
+        }
+    }
+    Some(interval)
+}
+
+fn unit_micros(unit: &str) -> Option<i64> {
+    match unit {
+        "week" => Some(7 * MICROS_PER_DAY),
+        "day" => Some(MICROS_PER_DAY),
+        "hour" => Some(MICROS_PER_HOUR),
+        "minute" | "min" => Some(MICROS_PER_MINUTE),
+        "second" | "sec" => Some(MICROS_PER_SECOND),
+        "millisecond" | "ms" => Some(1000),
+        "microsecond" | "u" => Some(1),
+        _ => None,
+    }
+}
+
+/// days since 1970-01-01 for a date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// year, month and day for the days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn format_date(micros: i64) -> String {
+    let (year, month, day) = civil_from_days(micros.div_euclid(MICROS_PER_DAY));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// HH:MM:SS, with the fraction of a second when there is one
+fn format_time(micros: i64) -> String {
+    let micros = micros.rem_euclid(MICROS_PER_DAY);
+    let seconds = micros / MICROS_PER_SECOND;
+    let text = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match micros % MICROS_PER_SECOND {
+        0 => text,
+        fraction => format!(
+            "{}.{}",
+            text,
+            format!("{:06}", fraction).trim_end_matches('0')
+        ),
+    }
+}
+
+/// adds months to the date part, keeping the time of day
+/// the day is clamped to the end of the month: jan 31 + 1 month is feb 28 (or 29)
+fn add_months(micros: i64, months: i64) -> i64 {
+    let (days, time) = (
+        micros.div_euclid(MICROS_PER_DAY),
+        micros.rem_euclid(MICROS_PER_DAY),
+    );
+    let (year, month, day) = civil_from_days(days);
+    let total = year * 12 + month as i64 - 1 + months;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    let day = day.min(days_in_month(year, month));
+    days_from_civil(year, month, day) * MICROS_PER_DAY + time
+}
+
+/// temporal + interval, date + interval stays a date when the interval has whole days
+pub(crate) fn add_interval(temporal: Temporal, interval: Interval) -> Temporal {
+    if let Temporal::Time(micros) = temporal {
+        return Temporal::Time((micros + interval.micros).rem_euclid(MICROS_PER_DAY));
+    }
+    let micros = add_months(temporal.micros(), interval.months as i64) + interval.micros;
+    match temporal {
+        Temporal::Date(_) if interval.micros % MICROS_PER_DAY == 0 => Temporal::Date(micros),
+        _ => Temporal::Timestamp(micros),
+    }
+}
+
+/// arithmetic with temporals and intervals, None when op does not apply to the operands
+/// temporal +/- interval, interval + temporal, temporal - temporal, interval +/- interval,
+/// interval * number and number * interval
+pub(crate) fn arithmetic(op: char, left: &Value, right: &Value) -> Option<Value> {
+    let (lt, rt) = (Temporal::from_value(left), Temporal::from_value(right));
+    let (li, ri) = (Interval::from_value(left), Interval::from_value(right));
+    let integer = |value: &Value| match number(value) {
+        Some(Number::Integer(i)) => Some(i),
+        _ => None,
+    };
+    Some(match (op, lt, rt, li, ri) {
+        ('+', Some(t), _, _, Some(i)) | ('+', _, Some(t), Some(i), _) => add_interval(t, i).into(),
+        ('-', Some(t), _, _, Some(i)) => add_interval(t, i.negate()).into(),
+        ('-', Some(l), Some(r), _, _) => Interval::new(0, l.micros() - r.micros()).into(),
+        ('+', _, _, Some(l), Some(r)) => {
+            Interval::new(l.months + r.months, l.micros + r.micros).into()
+        }
+        ('-', _, _, Some(l), Some(r)) => {
+            Interval::new(l.months - r.months, l.micros - r.micros).into()
+        }
+        ('*', _, _, Some(i), None) => scale(i, integer(right)?).into(),
+        ('*', _, _, None, Some(i)) => scale(i, integer(left)?).into(),
+        _ => return None,
+    })
+}
+
+fn scale(interval: Interval, n: i64) -> Interval {
+    Interval::new(interval.months * n as i32, interval.micros * n)
+}
+
+pub(crate) fn negate(value: &Value) -> Option<Value> {
+    Interval::from_value(value).map(|i| i.negate().into())
+}
+
+/// text that looks like a date or time, for comparing text with temporal values
+pub(crate) fn coerce(value: &Value, other: &Value) -> Option<Value> {
+    if Temporal::from_value(other).is_some() && value.is_text() {
+        return parse_iso(&value.to_string()).map(Value::from);
+    }
+    if Interval::from_value(other).is_some() && value.is_text() {
+        return parse_interval(&value.to_string()).map(Value::from);
+    }
+    None
+}
+
+/// the date and time functions
+/// returns None when there is no function with the name
+pub(crate) fn call(name: &str, args: &[Value]) -> Option<anyhow::Result<Value>> {
+    let temporal = |kind: fn(i64) -> Temporal| {
+        Ok(with_modifiers(args).map_or_else(Value::null, |micros| kind(micros).into()))
+    };
+    Some(match name {
+        "date" => temporal(|micros| Temporal::Date(0).with_micros(micros)),
+        "time" => temporal(|micros| Temporal::Time(0).with_micros(micros)),
+        "datetime" => temporal(Temporal::Timestamp),
+        "julianday" => Ok(with_modifiers(args).map_or_else(Value::null, |micros| {
+            Value::from_f64(micros as f64 / MICROS_PER_DAY as f64 + UNIX_EPOCH_JULIAN_DAY)
+        })),
+        "strftime" => strftime(args),
+        "date_trunc" => date_trunc(args),
+        "date_diff" => date_diff(args),
+        _ => return None,
+    })
+}
+
+/// the time value with the modifiers applied, in microseconds since the epoch
+/// None when a time value or a modifier is not valid, like in SQLite
+fn with_modifiers(args: &[Value]) -> Option<i64> {
+    let Some((time, modifiers)) = args.split_first() else {
+        return Some(now());
+    };
+    let modifiers: Vec<String> = modifiers
+        .iter()
+        .map(|m| m.to_string().to_lowercase())
+        .collect();
+    let mut modifiers = modifiers.iter().map(String::as_str).peekable();
+    let mut micros = if modifiers.peek() == Some(&"unixepoch") {
+        modifiers.next();
+        (number(time)?.as_f64() * MICROS_PER_SECOND as f64) as i64
+    } else {
+        to_micros(time)?
+    };
+    for modifier in modifiers {
+        micros = apply_modifier(micros, modifier)?;
+    }
+    Some(micros)
+}
+
+/// a time value: a temporal, 'now', ISO 8601 text, or a number as julian day
+fn to_micros(value: &Value) -> Option<i64> {
+    if let Some(temporal) = Temporal::from_value(value) {
+        return Some(temporal.micros());
+    }
+    if value.is_null() {
+        return None;
+    }
+    if value.is_text() {
+        let text = value.to_string();
+        if text.eq_ignore_ascii_case("now") {
+            return Some(now());
+        }
+        return parse_iso(&text).map(|t| t.micros());
+    }
+    let julian_day = number(value)?.as_f64();
+    Some(((julian_day - UNIX_EPOCH_JULIAN_DAY) * MICROS_PER_DAY as f64).round() as i64)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_micros() as i64)
+}
+
+/// '+3 days', '-1 month', 'start of month', 'start of year', 'start of day'
+fn apply_modifier(micros: i64, modifier: &str) -> Option<i64> {
+    if let Some(unit) = modifier.strip_prefix("start of ") {
+        return truncate(micros, unit.trim());
+    }
+    let interval = parse_interval(modifier)?;
+    Some(add_interval(Temporal::Timestamp(micros), interval).micros())
+}
+
+/// truncates to the start of the year, quarter, month, week (monday), day, hour, minute or second
+fn truncate(micros: i64, unit: &str) -> Option<i64> {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, _) = civil_from_days(days);
+    let floor = |unit: i64| micros.div_euclid(unit) * unit;
+    Some(match unit.trim_end_matches('s') {
+        "year" => days_from_civil(year, 1, 1) * MICROS_PER_DAY,
+        "quarter" => days_from_civil(year, (month - 1) / 3 * 3 + 1, 1) * MICROS_PER_DAY,
+        "month" => days_from_civil(year, month, 1) * MICROS_PER_DAY,
+        // 1970-01-01 was a thursday
+        "week" => (days - (days + 3).rem_euclid(7)) * MICROS_PER_DAY,
+        "day" => floor(MICROS_PER_DAY),
+        "hour" => floor(MICROS_PER_HOUR),
+        "minute" => floor(MICROS_PER_MINUTE),
+        "second" => floor(MICROS_PER_SECOND),
+        _ => return None,
+    })
+}
+
+/// date_trunc(unit, time value), keeps the type of a date or time
+fn date_trunc(args: &[Value]) -> anyhow::Result<Value> {
+    let [unit, value] = args else {
+        return Err(anyhow!("date_trunc() takes 2 arguments"));
+    };
+    if unit.is_null() || value.is_null() {
+        return Ok(Value::null());
+    }
+    let temporal = match Temporal::from_value(value) {
+        Some(temporal) => temporal,
+        None => match to_micros(value) {
+            Some(micros) => Temporal::Timestamp(micros),
+            None => return Ok(Value::null()),
+        },
+    };
+    let unit = unit.to_string().to_lowercase();
+    let micros = truncate(temporal.micros(), &unit)
+        .ok_or_else(|| anyhow!("Invalid unit for date_trunc(): {}", unit))?;
+    Ok(temporal.with_micros(micros).into())
+}
+
+/// date_diff(unit, start, end): the number of unit boundaries between start and end
+/// date_diff('month', '2024-01-31', '2024-02-01') is 1
+fn date_diff(args: &[Value]) -> anyhow::Result<Value> {
+    let [unit, start, end] = args else {
+        return Err(anyhow!("date_diff() takes 3 arguments"));
+    };
+    let (Some(start), Some(end)) = (to_micros(start), to_micros(end)) else {
+        return Ok(Value::null());
+    };
+    if unit.is_null() {
+        return Ok(Value::null());
+    }
+    let unit = unit.to_string().to_lowercase();
+    let months = |micros: i64| {
+        let (year, month, _) = civil_from_days(micros.div_euclid(MICROS_PER_DAY));
+        year * 12 + month as i64 - 1
+    };
+    let boundaries = |size: i64| end.div_euclid(size) - start.div_euclid(size);
+    let diff = match unit.trim_end_matches('s') {
+        "year" => months(end).div_euclid(12) - months(start).div_euclid(12),
+        "quarter" => months(end).div_euclid(3) - months(start).div_euclid(3),
+        "month" => months(end) - months(start),
+        "week" => {
+            let week = |micros| truncate(micros, "week").unwrap_or(micros);
+            (week(end) - week(start)) / (7 * MICROS_PER_DAY)
+        }
+        "day" => boundaries(MICROS_PER_DAY),
+        "hour" => boundaries(MICROS_PER_HOUR),
+        "minute" => boundaries(MICROS_PER_MINUTE),
+        "second" => boundaries(MICROS_PER_SECOND),
+        _ => return Err(anyhow!("Invalid unit for date_diff(): {}", unit)),
+    };
+    Ok(Value::from_i64(diff))
+}
+
+/// strftime(format, time value, modifiers..) with the SQLite format specifiers:
+/// %d %f %H %j %J %m %M %s %S %w %W %Y %%
+fn strftime(args: &[Value]) -> anyhow::Result<Value> {
+    let Some((format, args)) = args.split_first() else {
+        return Err(anyhow!("strftime() needs a format"));
+    };
+    let Some(micros) = with_modifiers(args) else {
+        return Ok(Value::null());
+    };
+    if format.is_null() {
+        return Ok(Value::null());
+    }
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let time = micros.rem_euclid(MICROS_PER_DAY);
+    let day_of_year = days - days_from_civil(year, 1, 1);
+    let weekday = (days + 4).rem_euclid(7); // sunday is 0
+
+    let mut text = String::new();
+    let format = format.to_string();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('d') => text.push_str(&format!("{:02}", day)),
+            Some('f') => text.push_str(&format!(
+                "{:02}.{:03}",
+                time / MICROS_PER_SECOND % 60,
+                time % MICROS_PER_SECOND / 1000
+            )),
+            Some('H') => text.push_str(&format!("{:02}", time / MICROS_PER_HOUR)),
+            Some('j') => text.push_str(&format!("{:03}", day_of_year + 1)),
+            Some('J') => text.push_str(&format!(
+                "{}",
+                micros as f64 / MICROS_PER_DAY as f64 + UNIX_EPOCH_JULIAN_DAY
+            )),
+            Some('m') => text.push_str(&format!("{:02}", month)),
+            Some('M') => text.push_str(&format!("{:02}", time / MICROS_PER_MINUTE % 60)),
+            Some('s') => text.push_str(&format!("{}", micros.div_euclid(MICROS_PER_SECOND))),
+            Some('S') => text.push_str(&format!("{:02}", time / MICROS_PER_SECOND % 60)),
+            Some('w') => text.push_str(&format!("{}", weekday)),
+            Some('W') => {
+                text.push_str(&format!("{:02}", (day_of_year + 7 - (weekday + 6) % 7) / 7))
+            }
+            Some('Y') => text.push_str(&format!("{:04}", year)),
+            Some('%') => text.push('%'),
+            Some(other) => return Err(anyhow!("Invalid strftime() format: %{}", other)),
+            None => return Err(anyhow!("Invalid strftime() format: trailing %")),
+        }
+    }
+    Ok(Value::from_text(text))
+}
+
+/// orders two temporal values, or two intervals
+pub(crate) fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if let (Some(l), Some(r)) = (Temporal::from_value(left), Temporal::from_value(right)) {
+        return Some(l.sort_key().cmp(&r.sort_key()));
+    }
+    if let (Some(l), Some(r)) = (Interval::from_value(left), Interval::from_value(right)) {
+        return Some(l.sort_key().cmp(&r.sort_key()));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call_text(name: &str, args: &[&str]) -> String {
+        let args: Vec<Value> = args.iter().map(|a| Value::from_text(*a)).collect();
+        call(name, &args).unwrap().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse_iso() {
+        assert_eq!(parse_iso("2024-02-29").unwrap().to_string(), "2024-02-29");
+        assert!(parse_iso("2023-02-29").is_none());
+        assert_eq!(parse_iso("13:45").unwrap().to_string(), "13:45:00");
+        assert_eq!(
+            parse_iso("2024-01-12T13:45:00.25+01:00")
+                .unwrap()
+                .to_string(),
+            "2024-01-12 12:45:00.25"
+        );
+        assert_eq!(
+            parse_iso("1969-12-31 23:59:59Z").unwrap().micros(),
+            -1_000_000
+        );
+        assert!(parse_iso("2024-1-12").is_none());
+    }
+
+    #[test]
+    fn test_parse_with_format() {
+        let date = parse_with_format("1/12/2024", "%m/%d/%Y").unwrap();
+        assert_eq!(date.to_string(), "2024-01-12");
+        let timestamp = parse_with_format("12-Jan-2024 9:05", "%d-%b-%Y %H:%M").unwrap();
+        assert_eq!(timestamp.to_string(), "2024-01-12 09:05:00");
+        assert!(parse_with_format("13/12/2024", "%m/%d/%Y").is_none());
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            call_text("date", &["2024-01-31 10:00", "+1 month"]),
+            "2024-02-29"
+        );
+        assert_eq!(
+            call_text("datetime", &["2024-01-31", "start of month", "-1 second"]),
+            "2023-12-31 23:59:59"
+        );
+        assert_eq!(call_text("julianday", &["2000-01-01 12:00"]), "2451545");
+        assert_eq!(
+            call_text("strftime", &["%Y/%j %H:%M:%S %w", "2024-03-01 07:08:09"]),
+            "2024/061 07:08:09 5"
+        );
+        assert_eq!(
+            call_text("date_trunc", &["quarter", "2024-05-17 10:00"]),
+            "2024-04-01 00:00:00"
+        );
+        assert_eq!(
+            call_text("date_diff", &["month", "2024-01-31", "2024-02-01"]),
+            "1"
+        );
+        assert_eq!(
+            call_text("date_diff", &["day", "2024-03-01", "2024-02-01"]),
+            "-29"
+        );
+        assert_eq!(call_text("date", &["nonsense"]), "NULL");
+    }
+
+    #[test]
+    fn test_interval_arithmetic() {
+        let date: Value = parse_iso("2024-01-31").unwrap().into();
+        let month: Value = parse_interval("1 month").unwrap().into();
+        assert_eq!(
+            arithmetic('+', &date, &month).unwrap().to_string(),
+            "2024-02-29"
+        );
+        let hours: Value = parse_interval("36 hours").unwrap().into();
+        assert_eq!(
+            arithmetic('-', &date, &hours).unwrap().to_string(),
+            "2024-01-29 12:00:00"
+        );
+        let later: Value = parse_iso("2024-03-01 06:00").unwrap().into();
+        assert_eq!(
+            arithmetic('-', &later, &date).unwrap().to_string(),
+            "30 days 06:00:00"
+        );
+        assert_eq!(
+            parse_interval("1 year 14 months -2 days")
+                .unwrap()
+                .to_string(),
+            "2 years 2 months -2 days"
+        );
+    }
+}
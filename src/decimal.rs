@@ -0,0 +1,394 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::value::Value;
+
+// decimals are stored with serial type 11 (reserved in SQLite), as the scale,
+// the length of the mantissa and the mantissa in big endian two's complement, without redundant sign bytes
+pub(crate) const DECIMAL: u64 = 11;
+const MAX_SCALE: u32 = 38;
+
+/// exact fixed point number: mantissa * 10^-scale
+/// '19.90' has mantissa 1990 and scale 2. The scale is kept, so that values print like they were read
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Self { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// parses plain decimal notation with a decimal point: '19.99', '-0.5', '.25'
+    /// None for integers, exponents ('1e5') and more digits than fit
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = unsigned.split_once('.')?;
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+            || fraction.len() > MAX_SCALE as usize
+        {
+            return None;
+        }
+        let digits = format!("{}{}", integer, fraction);
+        let mantissa: i128 = if digits.is_empty() {
+            0
+        } else {
+            digits.parse().ok()?
+        };
+        Some(Decimal::new(
+            if negative { -mantissa } else { mantissa },
+            fraction.len() as u32,
+        ))
+    }
+
+    /// the decimal stored in the Value, None for other values
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if value.datatype != DECIMAL || value.data.len() < 2 {
+            return None;
+        }
+        let bytes = &value.data[2..];
+        let fill = if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+            0xff
+        } else {
+            0
+        };
+        let mut buf = [fill; 16];
+        buf[16 - bytes.len()..].copy_from_slice(bytes);
+        Some(Decimal::new(i128::from_be_bytes(buf), value.data[0] as u32))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        // via text, to get the nearest float
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// the integer value, None when there is a fraction
+    pub(crate) fn to_i64(self) -> Option<i64> {
+        match self.normalized() {
+            (mantissa, 0) => i64::try_from(mantissa).ok(),
+            _ => None,
+        }
+    }
+
+    /// exact comparison with a float, without rounding the decimal to a float
+    /// NaN is smaller than any decimal
+    pub(crate) fn cmp_f64(&self, f: f64) -> Ordering {
+        if f.is_nan() {
+            return Ordering::Greater;
+        }
+        let sign = self.mantissa.signum();
+        let f_sign = if f == 0.0 { 0 } else { f.signum() as i128 };
+        if sign != f_sign || sign == 0 {
+            return sign.cmp(&f_sign);
+        }
+        if f.is_infinite() {
+            return f_sign.cmp(&0).reverse();
+        }
+        // |f| = m * 2^e
+        let bits = f.to_bits();
+        let (exponent, fraction) = (((bits >> 52) & 0x7ff) as i32, bits & ((1 << 52) - 1));
+        let (m, e) = if exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 1 << 52, exponent - 1075)
+        };
+        // |self| = |mantissa| / 10^scale
+        let magnitude = self.mantissa.unsigned_abs();
+        let ordering = if e >= 0 {
+            cmp_scaled(magnitude, 0, 0, m as u128, e as u32, self.scale)
+        } else {
+            cmp_scaled(magnitude, -e as u32, 0, m as u128, 0, self.scale)
+        };
+        if sign < 0 {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// the same value without trailing zeros in the fraction: 1.50 is (15, 1)
+    pub(crate) fn normalized(self) -> (i128, u32) {
+        let (mut mantissa, mut scale) = (self.mantissa, self.scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        (mantissa, scale)
+    }
+
+    /// the mantissa at a larger scale
+    fn rescale(&self, scale: u32) -> Option<i128> {
+        self.mantissa
+            .checked_mul(10i128.checked_pow(scale.checked_sub(self.scale)?)?)
+    }
+
+    /// operands at the same scale
+    fn align(&self, other: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.rescale(scale)?, other.rescale(scale)?, scale))
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let (l, r, scale) = self.align(other)?;
+        Some(Decimal::new(l.checked_add(r)?, scale))
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        let (l, r, scale) = self.align(other)?;
+        Some(Decimal::new(l.checked_sub(r)?, scale))
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale + other.scale;
+        if scale > MAX_SCALE {
+            return None;
+        }
+        Some(Decimal::new(
+            self.mantissa.checked_mul(other.mantissa)?,
+            scale,
+        ))
+    }
+
+    /// division rounded (half away from zero) to 6 more decimals than the operands,
+    /// without trailing zeros beyond the scale of the operands: 10.00 / 4 is 2.50
+    /// None when dividing by zero or on overflow
+    pub fn checked_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.mantissa == 0 {
+            return None;
+        }
+        let min_scale = self.scale.max(other.scale);
+        let scale = (min_scale + 6).min(MAX_SCALE);
+        // self / other = (l * 10^(scale + other.scale - self.scale)) / r at scale
+        let numerator = self
+            .mantissa
+            .checked_mul(10i128.checked_pow(scale + other.scale - self.scale)?)?;
+        let (quotient, remainder) = (numerator / other.mantissa, numerator % other.mantissa);
+        let round_up = remainder.unsigned_abs() * 2 >= other.mantissa.unsigned_abs();
+        let mut mantissa = if round_up {
+            quotient + numerator.signum() * other.mantissa.signum()
+        } else {
+            quotient
+        };
+        let mut scale = scale;
+        while scale > min_scale && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        Some(Decimal::new(mantissa, scale))
+    }
+
+    pub fn checked_neg(&self) -> Option<Decimal> {
+        Some(Decimal::new(self.mantissa.checked_neg()?, self.scale))
+    }
+
+    pub fn abs(&self) -> Option<Decimal> {
+        Some(Decimal::new(self.mantissa.checked_abs()?, self.scale))
+    }
+
+    /// rounded half away from zero to at most digits decimals
+    pub fn round(&self, digits: u32) -> Decimal {
+        if digits >= self.scale {
+            return *self;
+        }
+        let unit = 10i128.pow(self.scale - digits);
+        let (quotient, remainder) = (self.mantissa / unit, self.mantissa % unit);
+        if remainder.unsigned_abs() * 2 >= unit.unsigned_abs() {
+            Decimal::new(quotient + self.mantissa.signum(), digits)
+        } else {
+            Decimal::new(quotient, digits)
+        }
+    }
+
+    /// the largest integral decimal not larger than self
+    pub fn floor(&self) -> Decimal {
+        Decimal::new(self.mantissa.div_euclid(10i128.pow(self.scale)), 0)
+    }
+
+    /// the smallest integral decimal not smaller than self
+    pub fn ceil(&self) -> Decimal {
+        Decimal::new(-(-self.mantissa).div_euclid(10i128.pow(self.scale)), 0)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Decimal::new(value as i128, 0)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(decimal: Decimal) -> Self {
+        let bytes = decimal.mantissa.to_be_bytes();
+        // leave out leading bytes that only repeat the sign
+        let mut start = 0;
+        while start < 15
+            && (bytes[start] == 0 && bytes[start + 1] & 0x80 == 0
+                || bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0)
+        {
+            start += 1;
+        }
+        let mut data = vec![decimal.scale as u8, (16 - start) as u8];
+        data.extend_from_slice(&bytes[start..]);
+        Value::new(DECIMAL, data)
+    }
+}
+
+/// exact, 1.5 equals 1.50
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if let Some((l, r, _)) = self.align(other) {
+            return l.cmp(&r);
+        }
+        let (sign, other_sign) = (self.mantissa.signum(), other.mantissa.signum());
+        if sign != other_sign {
+            return sign.cmp(&other_sign);
+        }
+        // l / 10^ls against r / 10^rs is l * 10^rs against r * 10^ls
+        let ordering = cmp_scaled(
+            self.mantissa.unsigned_abs(),
+            0,
+            other.scale,
+            other.mantissa.unsigned_abs(),
+            0,
+            self.scale,
+        );
+        if sign < 0 {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+/// compares l * 2^l_twos * 10^l_tens with r * 2^r_twos * 10^r_tens, exactly
+fn cmp_scaled(l: u128, l_twos: u32, l_tens: u32, r: u128, r_twos: u32, r_tens: u32) -> Ordering {
+    let l = big(l, l_twos, l_tens);
+    let r = big(r, r_twos, r_tens);
+    l.len()
+        .cmp(&r.len())
+        .then_with(|| l.iter().rev().cmp(r.iter().rev()))
+}
+
+/// n * 2^twos * 10^tens as 32 bit digits, least significant first, without leading zeros
+fn big(n: u128, twos: u32, tens: u32) -> Vec<u32> {
+    let mut digits: Vec<u32> = (0..4).map(|i| (n >> (32 * i)) as u32).collect();
+    let multiply = |digits: &mut Vec<u32>, factor: u64| {
+        let mut carry = 0;
+        for digit in digits.iter_mut() {
+            let product = *digit as u64 * factor + carry;
+            *digit = product as u32;
+            carry = product >> 32;
+        }
+        if carry > 0 {
+            digits.push(carry as u32);
+        }
+    };
+    for _ in 0..tens {
+        multiply(&mut digits, 10);
+    }
+    multiply(&mut digits, 1 << (twos % 32));
+    digits.splice(0..0, std::iter::repeat_n(0, (twos / 32) as usize));
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decimal(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(decimal("19.90").to_string(), "19.90");
+        assert_eq!(decimal("-0.05").to_string(), "-0.05");
+        assert_eq!(decimal(".5").to_string(), "0.5");
+        assert!(Decimal::parse("12").is_none());
+        assert!(Decimal::parse("1e5").is_none());
+        assert!(Decimal::parse(".").is_none());
+    }
+
+    #[test]
+    fn test_encoding() {
+        for text in [
+            "0.00",
+            "19.99",
+            "-0.05",
+            "-128.0",
+            "170141183460469231731687303715884105.727",
+        ] {
+            let value: Value = decimal(text).into();
+            assert_eq!(Decimal::from_value(&value).unwrap().to_string(), text);
+        }
+        let value: Value = decimal("1.27").into();
+        assert_eq!(value.data.len(), 3);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let sum = decimal("0.1").checked_add(&decimal("0.20")).unwrap();
+        assert_eq!(sum.to_string(), "0.30");
+        assert_eq!(sum, decimal("0.3"));
+        let product = decimal("19.99").checked_mul(&Decimal::from(3)).unwrap();
+        assert_eq!(product.to_string(), "59.97");
+        let quotient = decimal("10.00").checked_div(&Decimal::from(3)).unwrap();
+        assert_eq!(quotient.to_string(), "3.33333333");
+        let quotient = decimal("-10.00").checked_div(&Decimal::from(4)).unwrap();
+        assert_eq!(quotient.to_string(), "-2.50");
+        assert!(decimal("1.0").checked_div(&Decimal::from(0)).is_none());
+        assert_eq!(decimal("2.345").round(2).to_string(), "2.35");
+        assert_eq!(decimal("-2.345").round(1).to_string(), "-2.3");
+        assert_eq!(decimal("-2.5").floor().to_string(), "-3");
+        assert_eq!(decimal("-2.5").ceil().to_string(), "-2");
+    }
+}
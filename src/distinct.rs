@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use crate::collation::Collated;
+use crate::database::Database;
+use crate::sql::ast::Expression;
+use crate::table::Table;
+use crate::vm::eval::{evaluate, expression_collation};
+
+impl Table {
+    /// returns a copy of the table with only the first record for every distinct key
+    /// the key is the values of the expressions, or the whole record when there are none
+    /// text is compared with the collation of the expression or column
+    pub(crate) fn distinct(
+        &self,
+        expressions: &[Expression],
+        database: &Database,
+    ) -> anyhow::Result<Table> {
+        let collations = if expressions.is_empty() {
+            (0..self.cols.len())
+                .map(|index| self.collation(index).clone())
+                .collect()
+        } else {
+            expressions
+                .iter()
+                .map(|expression| expression_collation(expression, database, self))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        let mut seen = BTreeSet::new();
+        let mut result = self.empty_copy();
+        for record in self.iter() {
+            let values = if expressions.is_empty() {
+                record.values.clone()
+            } else {
+                expressions
+                    .iter()
+                    .map(|expression| evaluate(expression, database, self, &record))
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            };
+            let key: Vec<Collated> = values
+                .into_iter()
+                .zip(&collations)
+                .map(|(value, collation)| Collated {
+                    value,
+                    collation: collation.clone(),
+                })
+                .collect();
+            if seen.insert(key) {
+                result.insert(record)?;
+            }
+        }
+        Ok(result)
+    }
+}
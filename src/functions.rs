@@ -0,0 +1,833 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+
+use crate::datetime;
+use crate::decimal::Decimal;
+use crate::json;
+use crate::regexp::{self, RegexCache};
+use crate::value::{Datatype, Value};
+use crate::vm::eval::{number, truth, Number};
+
+type ScalarFn = dyn Fn(&[Value]) -> anyhow::Result<Value>;
+type AggregateFn = dyn Fn(&[Vec<Value>]) -> anyhow::Result<Value>;
+type Builtin = fn(&[Value]) -> anyhow::Result<Value>;
+pub(crate) type Arity = (usize, Option<usize>); // minimum and maximum number of arguments
+
+/// a scalar function: computes a value from the values of its arguments
+/// deterministic functions always return the same value for the same arguments
+#[derive(Clone)]
+struct Scalar {
+    arity: Arity,
+    deterministic: bool,
+    function: Rc<ScalarFn>,
+}
+
+/// the scalar functions, initially the built in ones, and the registered aggregate functions,
+/// by lowercase name
+#[derive(Clone)]
+pub(crate) struct Registry {
+    scalars: HashMap<String, Scalar>,
+    aggregates: HashMap<String, Rc<AggregateFn>>, // computes the value for the arguments of all records in a group
+    regexes: Rc<RegexCache>,                      // shared with the regexp functions
+}
+
+impl Registry {
+    /// adds or replaces a scalar function
+    pub(crate) fn add(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        deterministic: bool,
+        function: impl Fn(&[Value]) -> anyhow::Result<Value> + 'static,
+    ) {
+        self.scalars.insert(
+            name.to_lowercase(),
+            Scalar {
+                arity,
+                deterministic,
+                function: Rc::new(function),
+            },
+        );
+    }
+
+    /// adds or replaces an aggregate function: init creates the state for a group,
+    /// step adds the arguments for each record in the group and finalize computes the result
+    pub(crate) fn add_aggregate<S: 'static>(
+        &mut self,
+        name: &str,
+        init: impl Fn() -> S + 'static,
+        step: impl Fn(&mut S, &[Value]) -> anyhow::Result<()> + 'static,
+        finalize: impl Fn(S) -> anyhow::Result<Value> + 'static,
+    ) {
+        let aggregate = move |rows: &[Vec<Value>]| {
+            let mut state = init();
+            for args in rows {
+                step(&mut state, args)?;
+            }
+            finalize(state)
+        };
+        self.aggregates
+            .insert(name.to_lowercase(), Rc::new(aggregate));
+    }
+
+    pub(crate) fn call(&self, name: &str, args: &[Value]) -> anyhow::Result<Value> {
+        let scalar = self
+            .scalars
+            .get(name)
+            .ok_or_else(|| anyhow!("No such function: {}", name))?;
+        let (min_args, max_args) = scalar.arity;
+        if args.len() < min_args || max_args.is_some_and(|max| args.len() > max) {
+            return Err(anyhow!("Wrong number of arguments to function {}()", name));
+        }
+        (scalar.function)(args)
+    }
+
+    /// true for known scalar functions that always return the same value for the same arguments
+    pub(crate) fn is_deterministic(&self, name: &str) -> bool {
+        self.scalars
+            .get(name)
+            .is_some_and(|scalar| scalar.deterministic)
+    }
+
+    /// forgets what was cached for the previous statement
+    pub(crate) fn clear_caches(&self) {
+        self.regexes.clear();
+    }
+
+    pub(crate) fn is_aggregate(&self, name: &str) -> bool {
+        self.aggregates.contains_key(name)
+    }
+
+    /// computes a registered aggregate, for the arguments of each record in the group
+    pub(crate) fn aggregate(&self, name: &str, rows: &[Vec<Value>]) -> anyhow::Result<Value> {
+        let aggregate = self
+            .aggregates
+            .get(name)
+            .ok_or_else(|| anyhow!("No such aggregate function: {}", name))?;
+        aggregate(rows)
+    }
+}
+
+impl Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Registry({} functions, {} aggregates)",
+            self.scalars.len(),
+            self.aggregates.len()
+        )
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            scalars: HashMap::new(),
+            aggregates: HashMap::new(),
+            regexes: Rc::new(RegexCache::default()),
+        };
+        let fixed = |n| (n, Some(n));
+        let builtins: &[(&str, Arity, Builtin)] = &[
+            ("abs", fixed(1), abs),
+            ("round", (1, Some(2)), round),
+            ("ceil", fixed(1), |args| {
+                Ok(integral(&args[0], f64::ceil, Decimal::ceil))
+            }),
+            ("floor", fixed(1), |args| {
+                Ok(integral(&args[0], f64::floor, Decimal::floor))
+            }),
+            ("power", fixed(2), |args| Ok(float2(args, f64::powf))),
+            ("sqrt", fixed(1), |args| {
+                Ok(float(&args[0], |x| (x >= 0.0).then(|| x.sqrt())))
+            }),
+            ("ln", fixed(1), |args| {
+                Ok(float(&args[0], |x| (x > 0.0).then(|| x.ln())))
+            }),
+            ("log", (1, Some(2)), log),
+            ("mod", fixed(2), modulo),
+            ("sign", fixed(1), sign),
+            ("length", fixed(1), length),
+            ("lower", fixed(1), |args| {
+                Ok(text(&args[0], |t| t.to_lowercase()))
+            }),
+            ("upper", fixed(1), |args| {
+                Ok(text(&args[0], |t| t.to_uppercase()))
+            }),
+            ("trim", (1, Some(2)), |args| trim(args, Trim::Both)),
+            ("ltrim", (1, Some(2)), |args| trim(args, Trim::Start)),
+            ("rtrim", (1, Some(2)), |args| trim(args, Trim::End)),
+            ("substr", (2, Some(3)), substr),
+            ("replace", fixed(3), replace),
+            ("instr", fixed(2), instr),
+            ("printf", (1, None), printf),
+            ("concat", (1, None), |args| Ok(concat("", args))),
+            ("concat_ws", (2, None), concat_ws),
+            ("lpad", (2, Some(3)), |args| pad(args, true)),
+            ("rpad", (2, Some(3)), |args| pad(args, false)),
+            ("reverse", fixed(1), |args| {
+                Ok(text(&args[0], |t| t.chars().rev().collect()))
+            }),
+            ("split_part", fixed(3), split_part),
+            ("coalesce", (2, None), coalesce),
+            ("ifnull", fixed(2), coalesce),
+            ("nullif", fixed(2), nullif),
+            ("iif", fixed(3), iif),
+            ("greatest", (1, None), |args| {
+                Ok(extreme(args, |l, r| l > r))
+            }),
+            ("least", (1, None), |args| Ok(extreme(args, |l, r| l < r))),
+            ("typeof", fixed(1), |args| {
+                Ok(Value::from_text(type_name(&args[0])))
+            }),
+            // aliases
+            ("format", (1, None), printf),
+            ("substring", (2, Some(3)), substr),
+        ];
+        for &(name, arity, function) in builtins {
+            registry.add(name, arity, true, function);
+        }
+        // not deterministic, because of 'now'
+        for (name, arity) in [
+            ("date", (0, None)),
+            ("time", (0, None)),
+            ("datetime", (0, None)),
+            ("julianday", (0, None)),
+            ("strftime", (1, None)),
+            ("date_trunc", fixed(2)),
+            ("date_diff", fixed(3)),
+        ] {
+            registry.add(name, arity, false, move |args| {
+                datetime::call(name, args)
+                    .unwrap_or_else(|| Err(anyhow!("No such function: {}", name)))
+            });
+        }
+        let regexes = Rc::clone(&registry.regexes);
+        regexp::register(&mut registry, &regexes);
+        json::register(&mut registry);
+        registry
+    }
+}
+
+/// the name of the type of the value: null, integer, real, decimal, text, blob, date, time,
+/// timestamp or interval
+fn type_name(value: &Value) -> &'static str {
+    match value.datatype() {
+        Ok(Datatype::Null) => "null",
+        Ok(Datatype::Integer) => "integer",
+        Ok(Datatype::Float) => "real",
+        Ok(Datatype::Decimal) => "decimal",
+        Ok(Datatype::Text) => "text",
+        Ok(Datatype::Blob) | Err(_) => "blob",
+        Ok(Datatype::Date) => "date",
+        Ok(Datatype::Time) => "time",
+        Ok(Datatype::Timestamp) => "timestamp",
+        Ok(Datatype::Interval) => "interval",
+    }
+}
+
+fn abs(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(match number(&args[0]) {
+        None => Value::null(),
+        Some(Number::Integer(i)) => Value::from_i64(
+            i.checked_abs()
+                .ok_or_else(|| anyhow!("Integer overflow in abs()"))?,
+        ),
+        Some(Number::Float(f)) => Value::from_f64(f.abs()),
+        Some(Number::Decimal(d)) => d
+            .abs()
+            .map_or_else(|| Value::from_f64(d.to_f64().abs()), Value::from),
+    })
+}
+
+/// round(x [, digits]), half away from zero. Integers and decimals stay exact
+fn round(args: &[Value]) -> anyhow::Result<Value> {
+    let digits = match args.get(1).map(number) {
+        None => 0,
+        Some(None) => return Ok(Value::null()),
+        Some(Some(digits)) => digits.as_f64().clamp(0.0, 30.0) as u32,
+    };
+    Ok(match number(&args[0]) {
+        None => Value::null(),
+        Some(Number::Integer(i)) => Value::from_i64(i),
+        Some(Number::Decimal(d)) => d.round(digits).into(),
+        Some(Number::Float(f)) => {
+            let unit = 10f64.powi(digits as i32);
+            Value::from_f64((f * unit).round() / unit)
+        }
+    })
+}
+
+/// ceil and floor: integers for integers and decimals, floats for floats
+fn integral(value: &Value, float: fn(f64) -> f64, decimal: fn(&Decimal) -> Decimal) -> Value {
+    match number(value) {
+        None => Value::null(),
+        Some(Number::Integer(i)) => Value::from_i64(i),
+        Some(Number::Float(f)) => Value::from_f64(float(f)),
+        Some(Number::Decimal(d)) => {
+            let d = decimal(&d);
+            d.to_i64().map_or_else(|| d.into(), Value::from_i64)
+        }
+    }
+}
+
+/// a float function of a number, NULL when f returns None
+fn float(value: &Value, f: impl Fn(f64) -> Option<f64>) -> Value {
+    number(value)
+        .and_then(|x| f(x.as_f64()))
+        .map_or_else(Value::null, Value::from_f64)
+}
+
+fn float2(args: &[Value], f: fn(f64, f64) -> f64) -> Value {
+    match (number(&args[0]), number(&args[1])) {
+        (Some(x), Some(y)) => Value::from_f64(f(x.as_f64(), y.as_f64())),
+        _ => Value::null(),
+    }
+}
+
+/// log(x) is the base 10 logarithm, log(b, x) the base b logarithm
+fn log(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(match args {
+        [x] => float(x, |x| (x > 0.0).then(|| x.log10())),
+        [base, x] => match number(base) {
+            Some(base) if base.as_f64() > 0.0 && base.as_f64() != 1.0 => {
+                float(x, |x| (x > 0.0).then(|| x.log(base.as_f64())))
+            }
+            _ => Value::null(),
+        },
+        _ => unreachable!("checked by the registry"),
+    })
+}
+
+/// the remainder, with the sign of the dividend. NULL when dividing by zero
+fn modulo(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(match (number(&args[0]), number(&args[1])) {
+        (Some(Number::Integer(x)), Some(Number::Integer(y))) => {
+            x.checked_rem(y).map_or_else(Value::null, Value::from_i64)
+        }
+        (Some(x), Some(y)) if y.as_f64() != 0.0 => Value::from_f64(x.as_f64() % y.as_f64()),
+        _ => Value::null(),
+    })
+}
+
+fn sign(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(match number(&args[0]) {
+        None => Value::null(),
+        Some(Number::Integer(i)) => Value::from_i64(i.signum()),
+        Some(Number::Decimal(d)) => Value::from_i64(d.mantissa().signum() as i64),
+        Some(Number::Float(f)) if f.is_nan() => Value::null(),
+        Some(Number::Float(f)) => Value::from_i64(if f == 0.0 { 0 } else { f.signum() as i64 }),
+    })
+}
+
+/// the number of characters, or bytes for a blob
+fn length(args: &[Value]) -> anyhow::Result<Value> {
+    let value = &args[0];
+    Ok(match value.datatype()? {
+        Datatype::Null => Value::null(),
+        Datatype::Blob => Value::from(value.data.len()),
+        _ => Value::from(value.to_string().chars().count()),
+    })
+}
+
+/// a text function, NULL for NULL. Other values are converted to text
+fn text(value: &Value, f: impl Fn(&str) -> String) -> Value {
+    if value.is_null() {
+        Value::null()
+    } else {
+        Value::from_text(f(&value.to_string()))
+    }
+}
+
+enum Trim {
+    Start,
+    End,
+    Both,
+}
+
+/// trim(x [, characters]), removes spaces or any of the characters
+fn trim(args: &[Value], side: Trim) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let characters: Vec<char> = match args.get(1) {
+        Some(characters) => characters.to_string().chars().collect(),
+        None => vec![' '],
+    };
+    let text = args[0].to_string();
+    let trimmed = match side {
+        Trim::Start => text.trim_start_matches(characters.as_slice()),
+        Trim::End => text.trim_end_matches(characters.as_slice()),
+        Trim::Both => text.trim_matches(characters.as_slice()),
+    };
+    Ok(Value::from_text(trimmed))
+}
+
+/// substr(x, start [, length]) with the rules of SQLite: the first character is at 1,
+/// a negative start counts from the end, a negative length takes the characters before start
+fn substr(args: &[Value]) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let integer = |value: &Value| number(value).map_or(0, |n| n.as_f64() as i64);
+    let chars: Vec<char> = args[0].to_string().chars().collect();
+    let len = chars.len() as i64;
+    let mut start = integer(&args[1]);
+    let mut count = args.get(2).map_or(len.max(1) * 2, integer);
+    let negative_count = count < 0;
+    if negative_count {
+        count = -count;
+    }
+    if start < 0 {
+        start += len;
+        if start < 0 {
+            count = (count + start).max(0);
+            start = 0;
+        }
+    } else if start > 0 {
+        start -= 1;
+    } else if count > 0 {
+        count -= 1;
+    }
+    if negative_count {
+        start -= count;
+        if start < 0 {
+            count += start;
+            start = 0;
+        }
+    }
+    let start = start.min(len) as usize;
+    let end = (start as i64 + count.max(0)).min(len) as usize;
+    Ok(Value::from_text(
+        chars[start..end].iter().collect::<String>(),
+    ))
+}
+
+fn replace(args: &[Value]) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let (text, from, to) = (
+        args[0].to_string(),
+        args[1].to_string(),
+        args[2].to_string(),
+    );
+    if from.is_empty() {
+        return Ok(Value::from_text(text));
+    }
+    Ok(Value::from_text(text.replace(&from, &to)))
+}
+
+/// the position (starting at 1) of the first occurrence of y in x, 0 when it does not occur
+fn instr(args: &[Value]) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let (text, search) = (args[0].to_string(), args[1].to_string());
+    Ok(Value::from(
+        text.find(&search)
+            .map_or(0, |index| text[..index].chars().count() + 1),
+    ))
+}
+
+/// concatenates the values, with separator in between. NULLs are skipped
+fn concat(separator: &str, args: &[Value]) -> Value {
+    let texts: Vec<String> = args
+        .iter()
+        .filter(|value| !value.is_null())
+        .map(Value::to_string)
+        .collect();
+    Value::from_text(texts.join(separator))
+}
+
+fn concat_ws(args: &[Value]) -> anyhow::Result<Value> {
+    if args[0].is_null() {
+        return Ok(Value::null());
+    }
+    Ok(concat(&args[0].to_string(), &args[1..]))
+}
+
+/// lpad(x, length [, fill]) and rpad: pads with fill (default a space) up to length characters,
+/// longer text is truncated
+fn pad(args: &[Value], left: bool) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let text: Vec<char> = args[0].to_string().chars().collect();
+    let length = number(&args[1]).map_or(0, |n| n.as_f64().max(0.0) as usize);
+    let fill: Vec<char> = match args.get(2) {
+        Some(fill) => fill.to_string().chars().collect(),
+        None => vec![' '],
+    };
+    if text.len() >= length || fill.is_empty() {
+        return Ok(Value::from_text(
+            text[..length.min(text.len())].iter().collect::<String>(),
+        ));
+    }
+    let padding: String = fill.iter().cycle().take(length - text.len()).collect();
+    let text: String = text.into_iter().collect();
+    Ok(Value::from_text(if left {
+        padding + &text
+    } else {
+        text + &padding
+    }))
+}
+
+/// split_part(x, delimiter, n): the nth part (starting at 1, negative from the end),
+/// empty when there is no such part
+fn split_part(args: &[Value]) -> anyhow::Result<Value> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::null());
+    }
+    let (text, delimiter) = (args[0].to_string(), args[1].to_string());
+    let n = number(&args[2]).map_or(0, |n| n.as_f64() as i64);
+    if n == 0 {
+        return Err(anyhow!("Field position must not be zero"));
+    }
+    let parts: Vec<&str> = if delimiter.is_empty() {
+        vec![&text]
+    } else {
+        text.split(delimiter.as_str()).collect()
+    };
+    let index = if n > 0 { n - 1 } else { parts.len() as i64 + n };
+    let part = usize::try_from(index)
+        .ok()
+        .and_then(|index| parts.get(index))
+        .copied()
+        .unwrap_or_default();
+    Ok(Value::from_text(part))
+}
+
+/// the first value that is not NULL
+fn coalesce(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(args
+        .iter()
+        .find(|value| !value.is_null())
+        .cloned()
+        .unwrap_or_else(Value::null))
+}
+
+/// NULL when both are equal, the first otherwise
+fn nullif(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(if args[0] == args[1] {
+        Value::null()
+    } else {
+        args[0].clone()
+    })
+}
+
+/// iif(condition, then, else)
+fn iif(args: &[Value]) -> anyhow::Result<Value> {
+    Ok(if truth(&args[0]) == Some(true) {
+        args[1].clone()
+    } else {
+        args[2].clone()
+    })
+}
+
+/// greatest and least: NULL when any of the values is NULL, like the multi argument
+/// max and min of SQLite
+fn extreme(args: &[Value], better: fn(&Value, &Value) -> bool) -> Value {
+    if args.iter().any(Value::is_null) {
+        return Value::null();
+    }
+    args.iter()
+        .fold(None, |best: Option<&Value>, value| match best {
+            Some(best) if !better(value, best) => Some(best),
+            _ => Some(value),
+        })
+        .cloned()
+        .unwrap_or_else(Value::null)
+}
+
+/// printf(format, args...) like in SQLite: %d %i %f %e %g %s %x %X %o %c and %%,
+/// with the flags '-', '+', ' ' and '0', a width and a precision
+fn printf(args: &[Value]) -> anyhow::Result<Value> {
+    if args[0].is_null() {
+        return Ok(Value::null());
+    }
+    let format: Vec<char> = args[0].to_string().chars().collect();
+    let mut args = args[1..].iter();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < format.len() {
+        if format[i] != '%' {
+            result.push(format[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let mut spec = Spec::default();
+        while let Some(&flag) = format.get(i).filter(|c| "-+ 0".contains(**c)) {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.sign = Some('+'),
+                ' ' => spec.sign = spec.sign.or(Some(' ')),
+                _ => spec.zero = true,
+            }
+            i += 1;
+        }
+        spec.width = digits(&format, &mut i);
+        if format.get(i) == Some(&'.') {
+            i += 1;
+            spec.precision = Some(digits(&format, &mut i));
+        }
+        let Some(&conversion) = format.get(i) else {
+            result.push('%');
+            break;
+        };
+        i += 1;
+        if conversion == '%' {
+            result.push('%');
+            continue;
+        }
+        let arg = args.next().cloned().unwrap_or_else(Value::null);
+        result.push_str(&spec.format(conversion, &arg)?);
+    }
+    Ok(Value::from_text(result))
+}
+
+fn digits(format: &[char], i: &mut usize) -> usize {
+    let mut n = 0;
+    while let Some(d) = format.get(*i).and_then(|c| c.to_digit(10)) {
+        n = n * 10 + d as usize;
+        *i += 1;
+    }
+    n
+}
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    sign: Option<char>,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn format(&self, conversion: char, arg: &Value) -> anyhow::Result<String> {
+        let float = || number(arg).map_or(0.0, Number::as_f64);
+        let integer = || match number(arg) {
+            Some(Number::Integer(i)) => i,
+            Some(n) => n.as_f64() as i64,
+            None => 0,
+        };
+        let (negative, digits) = match conversion {
+            'd' | 'i' => (integer() < 0, integer().unsigned_abs().to_string()),
+            'f' | 'F' => {
+                let f = float();
+                (
+                    f.is_sign_negative() && f != 0.0,
+                    format!("{:.*}", self.precision.unwrap_or(6), f.abs()),
+                )
+            }
+            'e' | 'E' => {
+                let f = float();
+                let text = exponent(f.abs(), self.precision.unwrap_or(6));
+                let text = if conversion == 'E' {
+                    text.to_uppercase()
+                } else {
+                    text
+                };
+                (f.is_sign_negative() && f != 0.0, text)
+            }
+            'g' | 'G' => {
+                let f = float();
+                (f.is_sign_negative() && f != 0.0, f.abs().to_string())
+            }
+            'x' => (false, format!("{:x}", integer())),
+            'X' => (false, format!("{:X}", integer())),
+            'o' => (false, format!("{:o}", integer())),
+            'c' => (false, arg.to_string().chars().take(1).collect()),
+            's' => {
+                let text = if arg.is_null() {
+                    String::new()
+                } else {
+                    arg.to_string()
+                };
+                let text = match self.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text,
+                };
+                return Ok(self.pad(text));
+            }
+            _ => return Err(anyhow!("Unknown conversion '%{}' in printf", conversion)),
+        };
+        let sign = if negative {
+            "-".to_string()
+        } else {
+            self.sign.map(String::from).unwrap_or_default()
+        };
+        if self.zero && !self.left {
+            let width = self.width.saturating_sub(sign.chars().count());
+            return Ok(format!("{}{:0>width$}", sign, digits, width = width));
+        }
+        Ok(self.pad(sign + &digits))
+    }
+
+    /// pads to the width, on the left unless the '-' flag was given
+    fn pad(&self, text: String) -> String {
+        let count = text.chars().count();
+        if count >= self.width {
+            return text;
+        }
+        let padding = " ".repeat(self.width - count);
+        if self.left {
+            text + &padding
+        } else {
+            padding + &text
+        }
+    }
+}
+
+/// like C: 1.500000e+03
+fn exponent(f: f64, precision: usize) -> String {
+    let text = format!("{:.*e}", precision, f);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let (sign, digits) = match exponent.strip_prefix('-') {
+                Some(digits) => ('-', digits),
+                None => ('+', exponent),
+            };
+            format!("{}e{}{:0>2}", mantissa, sign, digits)
+        }
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> String {
+        Registry::default().call(name, args).unwrap().to_string()
+    }
+
+    fn text(t: &str) -> Value {
+        Value::from_text(t)
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(call("abs", &[Value::from(-3)]), "3");
+        assert_eq!(call("round", &[Value::from_f64(2.5)]), "3");
+        assert_eq!(
+            call("round", &[Value::from("2.345"), Value::from(2)]),
+            "2.35"
+        );
+        assert_eq!(call("ceil", &[Value::from_f64(1.2)]), "2");
+        assert_eq!(call("floor", &[Value::from("-1.5")]), "-2");
+        assert_eq!(call("power", &[Value::from(2), Value::from(10)]), "1024");
+        assert_eq!(call("sqrt", &[Value::from(-1)]), "NULL");
+        assert_eq!(call("log", &[Value::from(2), Value::from(8)]), "3");
+        assert_eq!(call("mod", &[Value::from(-7), Value::from(3)]), "-1");
+        assert_eq!(call("mod", &[Value::from(7), Value::from(0)]), "NULL");
+        assert_eq!(call("sign", &[Value::from_f64(-0.5)]), "-1");
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(call("length", &[text("héllo")]), "5");
+        assert_eq!(call("upper", &[text("abc")]), "ABC");
+        assert_eq!(call("trim", &[text("xxaxx"), text("x")]), "a");
+        assert_eq!(
+            call("substr", &[text("abcdef"), Value::from(2), Value::from(3)]),
+            "bcd"
+        );
+        assert_eq!(call("substr", &[text("abcdef"), Value::from(-2)]), "ef");
+        assert_eq!(
+            call("substr", &[text("abcdef"), Value::from(0), Value::from(2)]),
+            "a"
+        );
+        assert_eq!(
+            call("substr", &[text("abcdef"), Value::from(4), Value::from(-2)]),
+            "bc"
+        );
+        assert_eq!(
+            call("replace", &[text("aXbX"), text("X"), text("-")]),
+            "a-b-"
+        );
+        assert_eq!(call("instr", &[text("héllo"), text("l")]), "3");
+        assert_eq!(
+            call("concat", &[text("a"), Value::null(), Value::from(1)]),
+            "a1"
+        );
+        assert_eq!(
+            call("concat_ws", &[text(", "), text("a"), text("b")]),
+            "a, b"
+        );
+        assert_eq!(
+            call("lpad", &[Value::from(7), Value::from(3), text("0")]),
+            "007"
+        );
+        assert_eq!(call("rpad", &[text("abcd"), Value::from(2)]), "ab");
+        assert_eq!(call("reverse", &[text("abc")]), "cba");
+        assert_eq!(
+            call("split_part", &[text("a,b,c"), text(","), Value::from(-1)]),
+            "c"
+        );
+        assert_eq!(
+            call("split_part", &[text("a,b,c"), text(","), Value::from(5)]),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_printf() {
+        let format = |f: &str, args: &[Value]| {
+            let mut all = vec![text(f)];
+            all.extend_from_slice(args);
+            call("printf", &all)
+        };
+        assert_eq!(
+            format(
+                "%5.2f|%-4d|%03d",
+                &[Value::from_f64(2.5), Value::from(7), Value::from(5)]
+            ),
+            " 2.50|7   |005"
+        );
+        assert_eq!(
+            format("%s has %d%%", &[text("it"), Value::from(50)]),
+            "it has 50%"
+        );
+        assert_eq!(
+            format(
+                "%.3s|%x|%e",
+                &[text("abcdef"), Value::from(255), Value::from(1500)]
+            ),
+            "abc|ff|1.500000e+03"
+        );
+        assert_eq!(format("%+d", &[Value::from(3)]), "+3");
+    }
+
+    #[test]
+    fn test_null_handling() {
+        assert_eq!(
+            call("coalesce", &[Value::null(), Value::from(2), Value::from(3)]),
+            "2"
+        );
+        assert_eq!(
+            call("nullif", &[Value::from(1), Value::from_f64(1.0)]),
+            "NULL"
+        );
+        assert_eq!(
+            call("iif", &[Value::from(0), text("yes"), text("no")]),
+            "no"
+        );
+        assert_eq!(
+            call(
+                "greatest",
+                &[Value::from(2), Value::from_f64(2.5), Value::from(1)]
+            ),
+            "2.5"
+        );
+        assert_eq!(call("least", &[Value::from(2), Value::null()]), "NULL");
+        assert_eq!(call("typeof", &[Value::from("1.5")]), "decimal");
+        assert_eq!(call("typeof", &[Value::from_f64(1.5)]), "real");
+        assert!(Registry::default().call("abs", &[]).is_err());
+        assert!(Registry::default().call("nope", &[]).is_err());
+    }
+}
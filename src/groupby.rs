@@ -1,19 +1,372 @@
-// use crate::Table;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 
-// impl Table {
-//     pub fn group_by(&self, select_expression: &str, group_by_expression: &str) -> Table {
-//         let table = Table::new("");
+use anyhow::anyhow;
 
-//         table
-//     }
-// }
+use crate::collation::{Collated, Collation};
+use crate::database::Database;
+use crate::decimal::Decimal;
+use crate::record::Record;
+use crate::sql::ast::{Expression, Statement};
+use crate::sql::parser;
+use crate::sql::tokens::TokenType;
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm::eval::{arithmetic, evaluate, expression_collation, number, Number};
+use crate::vm::{compiler, Vm};
 
-// // fn parse_select(select: &str){
+impl Table {
+    /// groups the records and computes aggregates
+    /// select_expression: 'region, product, sum(amount), grouping(region, product)'
+    /// group_by_expression: 'region, product' or 'rollup(region, product)', 'cube(..)', 'grouping sets(..)'
+    pub fn group_by(
+        &self,
+        select_expression: &str,
+        group_by_expression: &str,
+    ) -> anyhow::Result<Table> {
+        let sql = format!(
+            "select {} group by {}",
+            select_expression, group_by_expression
+        );
+        let Statement::Select(select) = parser::parse(&sql)? else {
+            return Err(anyhow!("Invalid group by expression"));
+        };
+        let database = Database::new();
+        Vm::new(&database, compiler::compile(&select, &database)?)
+            .with_input(self)
+            .run()
+    }
 
-// // }
+    /// computes one row for every group in every grouping set
+    /// result columns are the grouping expressions, followed by the aggregate columns,
+    /// all named after their expression
+    /// groups are formed using the collations of the grouping expressions, which the result columns keep
+    pub(crate) fn aggregate(
+        &self,
+        aggregate: &Aggregate,
+        database: &Database,
+    ) -> anyhow::Result<Table> {
+        let mut result = Table::new("");
+        for expression in aggregate.grouping.iter().chain(&aggregate.columns) {
+            result.add_column(expression.to_string(), false);
+        }
+        let collations = aggregate
+            .grouping
+            .iter()
+            .map(|expression| expression_collation(expression, database, self))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for (expression, collation) in aggregate.grouping.iter().zip(&collations) {
+            result.set_collation(&expression.to_string(), collation.clone())?;
+        }
 
-// // enum Aggregation {
-// //     Sum(String),
-// //     Max(String),
-// //     Min(String),
-// // }
+        let records: Vec<Record> = self.iter().collect();
+        let keys = records
+            .iter()
+            .map(|record| {
+                aggregate
+                    .grouping
+                    .iter()
+                    .map(|expression| evaluate(expression, database, self, record))
+                    .collect::<anyhow::Result<Vec<Value>>>()
+            })
+            .collect::<anyhow::Result<Vec<Vec<Value>>>>()?;
+
+        for set in &aggregate.sets {
+            // groups in order of first appearance
+            let mut groups: Vec<Vec<usize>> = vec![];
+            let mut group_index: BTreeMap<Vec<Collated>, usize> = BTreeMap::new();
+            for (record_index, key) in keys.iter().enumerate() {
+                let group_key: Vec<Collated> = set
+                    .iter()
+                    .map(|i| Collated {
+                        value: key[*i].clone(),
+                        collation: collations[*i].clone(),
+                    })
+                    .collect();
+                let index = *group_index.entry(group_key).or_insert_with(|| {
+                    groups.push(vec![]);
+                    groups.len() - 1
+                });
+                groups[index].push(record_index);
+            }
+            // the empty grouping set (the grand total) always yields a row, even without input
+            if groups.is_empty() && set.is_empty() {
+                groups.push(vec![]);
+            }
+
+            for group in groups {
+                let mut row = Record::default();
+                // the grand total of an empty input has no record to take the key from
+                let key = group.first().map(|i| &keys[*i]);
+                for i in 0..aggregate.grouping.len() {
+                    match key {
+                        Some(key) if set.contains(&i) => row.add_value(key[i].clone()),
+                        _ => row.add_value(Value::null()),
+                    }
+                }
+                let group_records: Vec<&Record> = group.iter().map(|i| &records[*i]).collect();
+                for column in &aggregate.columns {
+                    row.add_value(self.aggregate_column(
+                        column,
+                        aggregate,
+                        set,
+                        database,
+                        &group_records,
+                    )?);
+                }
+                result.append(row)?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn aggregate_column(
+        &self,
+        column: &Expression,
+        aggregate: &Aggregate,
+        set: &[usize],
+        database: &Database,
+        records: &[&Record],
+    ) -> anyhow::Result<Value> {
+        match column {
+            // bit is set for every argument that is not grouped in the current set
+            Expression::Function(name, args) if name == "grouping" => {
+                let mut bits = 0;
+                for arg in args {
+                    let index = aggregate
+                        .grouping
+                        .iter()
+                        .position(|g| g == arg)
+                        .ok_or_else(|| {
+                            anyhow!("Argument {} of grouping() is not in group by", arg)
+                        })?;
+                    bits = (bits << 1) | !set.contains(&index) as i64;
+                }
+                Ok(Value::from_i64(bits))
+            }
+            Expression::Function(name, args) => match Aggregation::from_name(name) {
+                Some(aggregation) => aggregation.compute(args, database, self, records),
+                // registered: the arguments of all records, NULLs included
+                None => {
+                    let rows = records
+                        .iter()
+                        .map(|record| {
+                            args.iter()
+                                .map(|arg| evaluate(arg, database, self, record))
+                                .collect::<anyhow::Result<Vec<Value>>>()
+                        })
+                        .collect::<anyhow::Result<Vec<Vec<Value>>>>()?;
+                    database.functions().aggregate(name, &rows)
+                }
+            },
+            // a column that is not grouped gets its value from the first record in the group
+            _ => match records.first() {
+                Some(record) => evaluate(column, database, self, record),
+                None => Ok(Value::null()),
+            },
+        }
+    }
+}
+
+/// the aggregation step of a grouped query
+#[derive(Debug, Clone)]
+pub(crate) struct Aggregate {
+    pub(crate) grouping: Vec<Expression>, // all distinct expressions in the group by clause
+    pub(crate) sets: Vec<Vec<usize>>,     // the grouping sets, as indexes in grouping
+    pub(crate) columns: Vec<Expression>, // aggregate functions, grouping() calls and ungrouped columns
+}
+
+/// built in or registered with Database::register_aggregate
+pub(crate) fn is_aggregate(name: &str, database: &Database) -> bool {
+    Aggregation::from_name(name).is_some() || database.functions().is_aggregate(name)
+}
+
+enum Aggregation {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Aggregation::Count),
+            "sum" => Some(Aggregation::Sum),
+            "avg" => Some(Aggregation::Avg),
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            _ => None,
+        }
+    }
+
+    fn compute(
+        &self,
+        args: &[Expression],
+        database: &Database,
+        table: &Table,
+        records: &[&Record],
+    ) -> anyhow::Result<Value> {
+        if let (Aggregation::Count, [Expression::Star]) = (self, args) {
+            return Ok(Value::from_i64(records.len() as i64));
+        }
+        let [arg] = args else {
+            return Err(anyhow!("Aggregate functions take exactly one argument"));
+        };
+        let (arg, distinct) = match arg {
+            Expression::Distinct(arg) => (arg.as_ref(), true),
+            arg => (arg, false),
+        };
+
+        let collation = expression_collation(arg, database, table)?;
+        // NULLs are ignored by all aggregates
+        // with distinct, values that are equal in the collation of the argument count once
+        let mut values = vec![];
+        let mut seen = BTreeSet::new();
+        for record in records {
+            let value = evaluate(arg, database, table, record)?;
+            if !value.is_null()
+                && (!distinct
+                    || seen.insert(Collated {
+                        value: value.clone(),
+                        collation: collation.clone(),
+                    }))
+            {
+                values.push(value);
+            }
+        }
+
+        Ok(match self {
+            Aggregation::Count => Value::from_i64(values.len() as i64),
+            Aggregation::Sum => sum(&values).unwrap_or_else(Value::null),
+            // exact for decimals
+            Aggregation::Avg => match sum(&values).as_ref().and_then(number) {
+                Some(total @ Number::Decimal(_)) => arithmetic(
+                    TokenType::Slash,
+                    total,
+                    Number::Integer(values.len() as i64),
+                ),
+                Some(total) => Value::from_f64(total.as_f64() / values.len() as f64),
+                None => Value::null(),
+            },
+            Aggregation::Min => extreme(values, Ordering::Less, &collation),
+            Aggregation::Max => extreme(values, Ordering::Greater, &collation),
+        })
+    }
+}
+
+/// integer sum as long as all values are integers and it does not overflow,
+/// exact decimal sum for decimals and integers, float sum otherwise
+fn sum(values: &[Value]) -> Option<Value> {
+    let numbers: Vec<Number> = values.iter().filter_map(number).collect();
+    if numbers.is_empty() {
+        return None;
+    }
+    let integer_sum = numbers.iter().try_fold(0i64, |acc, n| match n {
+        Number::Integer(i) => acc.checked_add(*i),
+        _ => None,
+    });
+    if let Some(total) = integer_sum {
+        return Some(Value::from_i64(total));
+    }
+    let decimal_sum = numbers
+        .iter()
+        .try_fold(Decimal::from(0), |acc, n| acc.checked_add(&n.as_decimal()?));
+    Some(match decimal_sum {
+        Some(total) => total.into(),
+        None => Value::from_f64(numbers.iter().map(|n| n.as_f64()).sum()),
+    })
+}
+
+fn extreme(values: Vec<Value>, wanted: Ordering, collation: &Collation) -> Value {
+    values
+        .into_iter()
+        .reduce(|acc, v| {
+            if collation.compare(&v, &acc) == wanted {
+                v
+            } else {
+                acc
+            }
+        })
+        .unwrap_or_else(Value::null)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sales() -> Table {
+        Table::from_csv(
+            "region,product,amount\nnorth,apples,10\nnorth,pears,5\nsouth,apples,7\n",
+            Some(","),
+        )
+    }
+
+    fn rows(table: &Table) -> Vec<String> {
+        table
+            .iter()
+            .map(|r| {
+                r.values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group_by() {
+        let result = sales().group_by("region, sum(amount)", "region").unwrap();
+        assert_eq!(rows(&result), vec!["north,15", "south,7"]);
+    }
+
+    #[test]
+    fn test_rollup() {
+        let result = sales()
+            .group_by(
+                "region, product, sum(amount), grouping(region, product)",
+                "rollup(region, product)",
+            )
+            .unwrap();
+        assert_eq!(
+            rows(&result),
+            vec![
+                "north,apples,10,0",
+                "north,pears,5,0",
+                "south,apples,7,0",
+                "north,NULL,15,1",
+                "south,NULL,7,1",
+                "NULL,NULL,22,3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cube() {
+        let result = sales()
+            .group_by("product, count(*)", "cube(product)")
+            .unwrap();
+        assert_eq!(rows(&result), vec!["apples,2", "pears,1", "NULL,3"]);
+    }
+
+    #[test]
+    fn test_grouping_sets() {
+        let result = sales()
+            .group_by(
+                "region, product, max(amount)",
+                "grouping sets (region, product)",
+            )
+            .unwrap();
+        assert_eq!(
+            rows(&result),
+            vec![
+                "north,NULL,10",
+                "south,NULL,7",
+                "NULL,apples,10",
+                "NULL,pears,5"
+            ]
+        );
+    }
+}
@@ -15,4 +15,21 @@ impl ThreadSafeIdGenerator {
     pub fn next(&self) -> usize {
         self.counter.fetch_add(1, Ordering::SeqCst)
     }
+
+    /// makes next return ids above id, if it does not already
+    pub fn skip_past(&self, id: usize) {
+        self.counter.fetch_max(id + 1, Ordering::SeqCst);
+    }
+
+    /// the id that next will return
+    pub fn peek(&self) -> usize {
+        self.counter.load(Ordering::SeqCst)
+    }
+}
+
+/// a generator that continues from the same id, independently
+impl Clone for ThreadSafeIdGenerator {
+    fn clone(&self) -> Self {
+        Self::new(self.counter.load(Ordering::SeqCst))
+    }
 }
@@ -0,0 +1,338 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+
+use crate::collation::Collation;
+use crate::record::Record;
+use crate::table::Table;
+use crate::value::Value;
+
+/// secondary index on one or more columns of a table
+/// maps the compound key of the column values to the rowid
+/// the rowid is also the last part of the key, so that duplicate values get their own entry
+/// keys are ordered by the collations of the columns
+#[derive(Debug, Clone)]
+pub struct View {
+    columns: Vec<usize>, // indexes of the indexed columns in the records
+    collations: Rc<[Collation]>,
+    records: BTreeMap<Key, Key>,
+}
+
+impl View {
+    pub(crate) fn new(columns: Vec<usize>, collations: Vec<Collation>) -> Self {
+        Self {
+            columns,
+            collations: collations.into(),
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// adds the record to the index
+    pub(crate) fn insert(&mut self, record: &Record) {
+        self.records
+            .insert(self.key(record), Key::integer(record.rowid as usize));
+    }
+
+    /// removes the record, with the values it had when it was added, from the index
+    pub(crate) fn remove(&mut self, record: &Record) {
+        self.records.remove(&self.key(record));
+    }
+
+    fn key(&self, record: &Record) -> Key {
+        let mut values: Vec<Value> = self
+            .columns
+            .iter()
+            .map(|i| record.get(*i).clone())
+            .collect();
+        values.push(Value::from_i64(record.rowid as i64));
+        Key::collated(values, Rc::clone(&self.collations))
+    }
+
+    /// the rowid of another record with the same values in the indexed columns
+    /// NULL is not equal to anything, so a record with NULL in one of them has no duplicates
+    pub(crate) fn duplicate(&self, record: &Record) -> Option<u64> {
+        let values: Vec<Value> = self
+            .columns
+            .iter()
+            .map(|i| record.get(*i).clone())
+            .collect();
+        if values.iter().any(Value::is_null) {
+            return None;
+        }
+        let lower = bound(&values, &Bound::Unbounded, false, &self.collations);
+        let upper = bound(&values, &Bound::Unbounded, true, &self.collations);
+        self.rowids(lower, upper)
+            .into_iter()
+            .find(|rowid| *rowid != record.rowid)
+    }
+
+    /// the indexed columns
+    pub(crate) fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+
+    /// rowids for the keys in the range, in key order
+    fn rowids(&self, lower: Bound<Key>, upper: Bound<Key>) -> Vec<u64> {
+        self.records
+            .range((lower, upper))
+            .filter_map(|(_, rowid)| rowid.values[0].as_i64().map(|rowid| rowid as u64))
+            .collect()
+    }
+}
+
+/// a lookup in an index: equality on the leading columns,
+/// and optionally a range on the column after those
+#[derive(Debug, Clone)]
+pub(crate) struct IndexScan {
+    pub(crate) index: String,
+    pub(crate) equal: Vec<Value>,
+    pub(crate) lower: Bound<Value>,
+    pub(crate) upper: Bound<Value>,
+    pub(crate) descending: bool,
+}
+
+impl Table {
+    /// creates an index on one or more columns
+    /// columns: comma separated list of column names
+    pub fn create_index(&mut self, name: impl Into<String>, columns: &str) -> anyhow::Result<()> {
+        let name = name.into();
+        if self.views.contains_key(&name) {
+            return Err(anyhow!("Index {} already exists", name));
+        }
+        let columns = columns
+            .split(',')
+            .map(|c| {
+                self.find_index(c.trim())
+                    .ok_or_else(|| anyhow!("No such column: {}", c.trim()))
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        self.create_index_on(name, columns);
+        Ok(())
+    }
+
+    pub(crate) fn create_index_on(&mut self, name: String, columns: Vec<usize>) {
+        let collations = columns.iter().map(|i| self.collation(*i).clone()).collect();
+        let mut view = View::new(columns, collations);
+        for record in self.iter() {
+            view.insert(&record);
+        }
+        self.views.insert(name, view);
+    }
+
+    /// returns a table with the records found by the index scan, in index order
+    /// the records get new rowids, in that order
+    pub(crate) fn scan_index(&self, scan: &IndexScan) -> anyhow::Result<Table> {
+        let view = self
+            .views
+            .get(&scan.index)
+            .ok_or_else(|| anyhow!("No such index: {}", scan.index))?;
+
+        let mut result = self.empty_copy();
+        let collation = view
+            .collations
+            .get(scan.equal.len())
+            .cloned()
+            .unwrap_or_else(Collation::binary);
+        if is_empty_range(&scan.lower, &scan.upper, &collation) {
+            return Ok(result);
+        }
+        let lower = bound(&scan.equal, &scan.lower, false, &view.collations);
+        let upper = bound(&scan.equal, &scan.upper, true, &view.collations);
+
+        let mut rowids = view.rowids(lower, upper);
+        if scan.descending {
+            rowids.reverse();
+        }
+        for rowid in rowids {
+            let record = self
+                .get(rowid)
+                .ok_or_else(|| anyhow!("Index {} refers to missing row {}", scan.index, rowid))?;
+            result.append(record)?;
+        }
+        Ok(result)
+    }
+}
+
+/// the key bound for a range on the column after the equal values
+/// without a range, the equal values bound the keys that start with them
+fn bound(
+    equal: &[Value],
+    range: &Bound<Value>,
+    upper: bool,
+    collations: &Rc<[Collation]>,
+) -> Bound<Key> {
+    let mut values = equal.to_vec();
+    // 'a > 5' starts after all keys that start with 5, 'a < 5' ends before them
+    let after = match range {
+        Bound::Included(value) => {
+            values.push(value.clone());
+            upper
+        }
+        Bound::Excluded(value) => {
+            values.push(value.clone());
+            !upper
+        }
+        Bound::Unbounded if equal.is_empty() => return Bound::Unbounded,
+        Bound::Unbounded => upper,
+    };
+    Bound::Included(Key {
+        values,
+        collations: Rc::clone(collations),
+        after_extensions: after,
+    })
+}
+
+/// eg 'a > 5 and a < 3'
+fn is_empty_range(lower: &Bound<Value>, upper: &Bound<Value>, collation: &Collation) -> bool {
+    match (lower, upper) {
+        (Bound::Included(l), Bound::Included(u)) => collation.compare(l, u) == Ordering::Greater,
+        (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u)) => {
+            collation.compare(l, u) != Ordering::Less
+        }
+        _ => false,
+    }
+}
+
+/// keys for indexes. Allow compound keys
+/// a key that is a prefix of another key sorts before it, or after it when after_extensions is set.
+/// That way a shorter key can bound a search on the leading columns of a compound index
+/// values without a collation are compared binary
+#[derive(Debug, Clone)]
+pub struct Key {
+    values: Vec<Value>,
+    collations: Rc<[Collation]>,
+    after_extensions: bool,
+}
+
+impl Key {
+    pub fn integer(integer: usize) -> Self {
+        Self::compound(vec![integer.into()])
+    }
+
+    pub fn compound(keys: Vec<Value>) -> Self {
+        Self::collated(keys, Rc::new([]))
+    }
+
+    pub(crate) fn collated(keys: Vec<Value>, collations: Rc<[Collation]>) -> Self {
+        Self {
+            values: keys,
+            collations,
+            after_extensions: false,
+        }
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (i, (l, r)) in self.values.iter().zip(&other.values).enumerate() {
+            let ord = match self.collations.get(i).or(other.collations.get(i)) {
+                Some(collation) => collation.compare(l, r),
+                None => l.cmp(r),
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        let extension_order = |key: &Key| {
+            if key.after_extensions {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }
+        };
+        match self.values.len().cmp(&other.values.len()) {
+            Ordering::Equal => Ordering::Equal,
+            Ordering::Less => extension_order(self),
+            Ordering::Greater => extension_order(other).reverse(),
+        }
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::from_csv("a,b\n1,x\n2,y\n1,z\n3,x\n2,x\n", Some(","));
+        table.create_index("idx_a_b", "a, b").unwrap();
+        table
+    }
+
+    fn column(table: &Table, index: usize) -> Vec<String> {
+        table.iter().map(|r| r.get(index).to_string()).collect()
+    }
+
+    #[test]
+    fn test_equal() {
+        let scan = IndexScan {
+            index: "idx_a_b".to_string(),
+            equal: vec![1.into()],
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+            descending: false,
+        };
+        let result = table().scan_index(&scan).unwrap();
+        assert_eq!(column(&result, 1), vec!["x", "z"]);
+    }
+
+    #[test]
+    fn test_range() {
+        let scan = IndexScan {
+            index: "idx_a_b".to_string(),
+            equal: vec![],
+            lower: Bound::Excluded(1.into()),
+            upper: Bound::Included(3.into()),
+            descending: true,
+        };
+        let result = table().scan_index(&scan).unwrap();
+        assert_eq!(column(&result, 0), vec!["3", "2", "2"]);
+        assert_eq!(column(&result, 1), vec!["x", "y", "x"]);
+    }
+
+    #[test]
+    fn test_empty_range() {
+        let scan = IndexScan {
+            index: "idx_a_b".to_string(),
+            equal: vec![],
+            lower: Bound::Excluded(3.into()),
+            upper: Bound::Excluded(3.into()),
+            descending: false,
+        };
+        assert_eq!(table().scan_index(&scan).unwrap().iter().count(), 0);
+    }
+
+    #[test]
+    fn test_maintained_on_insert() {
+        let mut table = table();
+        let mut record = Record::default();
+        record.add_value(0);
+        record.add_value("w");
+        table.append(record).unwrap();
+        let scan = IndexScan {
+            index: "idx_a_b".to_string(),
+            equal: vec![],
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+            descending: false,
+        };
+        let result = table.scan_index(&scan).unwrap();
+        assert_eq!(column(&result, 1), vec!["w", "x", "z", "x", "y", "x"]);
+    }
+}
@@ -0,0 +1,735 @@
+use std::fmt::{Display, Write};
+
+use anyhow::anyhow;
+
+use crate::functions::Registry;
+use crate::record::Record;
+use crate::table::Table;
+use crate::value::{Datatype, Value};
+
+/// a parsed json text, objects keep the order of their members
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(String), // as written, so that no precision is lost
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// deeper nesting is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 1000;
+
+impl Json {
+    pub(crate) fn parse(text: &str) -> anyhow::Result<Json> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            current: 0,
+        };
+        let json = parser.value(0);
+        parser.whitespace();
+        match json {
+            Some(json) if parser.current == parser.bytes.len() => Ok(json),
+            _ => Err(anyhow!("Malformed JSON")),
+        }
+    }
+
+    /// the json for a sql value: NULL is null, numbers are numbers, text is a string
+    /// there are no subtypes to tell json text from other text, so text that is
+    /// a json object or array is taken as json, so that the constructors nest
+    pub(crate) fn from_value(value: &Value) -> anyhow::Result<Json> {
+        Ok(match value.datatype()? {
+            Datatype::Null => Json::Null,
+            Datatype::Integer | Datatype::Decimal => Json::Number(value.to_string()),
+            Datatype::Float => match value.as_f64() {
+                Some(f) if f.is_finite() => Json::Number(value.to_string()),
+                _ => Json::Null,
+            },
+            Datatype::Blob => return Err(anyhow!("JSON cannot hold BLOB values")),
+            Datatype::Text => {
+                let text = value.to_string();
+                match text.trim_start().as_bytes().first() {
+                    Some(b'{' | b'[') => Json::parse(&text).unwrap_or(Json::String(text)),
+                    _ => Json::String(text),
+                }
+            }
+            _ => Json::String(value.to_string()),
+        })
+    }
+
+    /// the sql value: null is NULL, true and false are 1 and 0, strings are text
+    /// and arrays and objects are json text
+    pub(crate) fn to_value(&self) -> Value {
+        match self {
+            Json::Null => Value::null(),
+            Json::Bool(b) => Value::from_bool(*b),
+            Json::Number(n) => match n.parse::<i64>() {
+                Ok(i) => Value::from_i64(i),
+                Err(_) => Value::from_f64(n.parse().unwrap_or(f64::NAN)),
+            },
+            Json::String(s) => Value::from_text(s.as_str()),
+            _ => Value::from_text(self.to_string()),
+        }
+    }
+
+    /// the type as reported by json_type and json_each
+    fn type_name(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(true) => "true",
+            Json::Bool(false) => "false",
+            Json::Number(n) if n.parse::<i64>().is_ok() => "integer",
+            Json::Number(_) => "real",
+            Json::String(_) => "text",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    /// the element at the path, None when there is no such element
+    fn get(&self, path: &[Step]) -> Option<&Json> {
+        let Some((step, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        let element = match (self, step) {
+            (Json::Object(members), Step::Key(key)) => {
+                members.iter().find(|(name, _)| name == key).map(|m| &m.1)
+            }
+            (Json::Array(elements), Step::Index(index)) => elements.get(*index),
+            (Json::Array(elements), Step::FromEnd(n)) => {
+                elements.len().checked_sub(*n).and_then(|i| elements.get(i))
+            }
+            _ => None,
+        }?;
+        element.get(rest)
+    }
+}
+
+/// minified json text
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(elements) => {
+                f.write_char('[')?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (name, element)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", element)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut impl Write, s: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    current: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Option<Json> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.whitespace();
+        match self.peek()? {
+            b'{' => self.object(depth),
+            b'[' => self.array(depth),
+            b'"' => self.string().map(Json::String),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'n' => self.literal("null", Json::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Option<Json> {
+        self.current += 1;
+        let mut members = vec![];
+        self.whitespace();
+        if self.match_byte(b'}') {
+            return Some(Json::Object(members));
+        }
+        loop {
+            self.whitespace();
+            let name = self.string()?;
+            self.whitespace();
+            if !self.match_byte(b':') {
+                return None;
+            }
+            members.push((name, self.value(depth + 1)?));
+            self.whitespace();
+            if self.match_byte(b'}') {
+                return Some(Json::Object(members));
+            }
+            if !self.match_byte(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Option<Json> {
+        self.current += 1;
+        let mut elements = vec![];
+        self.whitespace();
+        if self.match_byte(b']') {
+            return Some(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value(depth + 1)?);
+            self.whitespace();
+            if self.match_byte(b']') {
+                return Some(Json::Array(elements));
+            }
+            if !self.match_byte(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if !self.match_byte(b'"') {
+            return None;
+        }
+        let mut bytes = vec![];
+        loop {
+            match self.advance()? {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let c = match self.advance()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return None,
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b if b < 0x20 => return None,
+                b => bytes.push(b),
+            }
+        }
+    }
+
+    /// \uXXXX, or a surrogate pair \uXXXX\uXXXX
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if !(self.match_byte(b'\\') && self.match_byte(b'u')) {
+            return None;
+        }
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.bytes.get(self.current..self.current + 4)?;
+        let digits = std::str::from_utf8(digits).ok()?;
+        let code = u32::from_str_radix(digits, 16).ok()?;
+        self.current += 4;
+        Some(code)
+    }
+
+    /// -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Option<Json> {
+        let start = self.current;
+        self.match_byte(b'-');
+        if !self.match_byte(b'0') && self.digits() == 0 {
+            return None;
+        }
+        if self.match_byte(b'.') && self.digits() == 0 {
+            return None;
+        }
+        if self.match_byte(b'e') || self.match_byte(b'E') {
+            if !self.match_byte(b'+') {
+                self.match_byte(b'-');
+            }
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.current]).ok()?;
+        Some(Json::Number(text.to_string()))
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.current;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.current += 1;
+        }
+        self.current - start
+    }
+
+    fn literal(&mut self, text: &str, json: Json) -> Option<Json> {
+        if self.bytes[self.current..].starts_with(text.as_bytes()) {
+            self.current += text.len();
+            Some(json)
+        } else {
+            None
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.current += 1;
+        }
+    }
+
+    fn match_byte(&mut self, expected: u8) -> bool {
+        if self.peek() == Some(expected) {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.current += 1;
+        Some(b)
+    }
+}
+
+/// a step in a path like $.a[2]."b c"[#-1]
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    FromEnd(usize), // [#-n], n elements before the end
+}
+
+fn parse_path(path: &str) -> anyhow::Result<Vec<Step>> {
+    let bad = || anyhow!("Bad JSON path: '{}'", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(bad)?;
+    let mut steps = vec![];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(".\"") {
+            let end = after.find('"').ok_or_else(bad)?;
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(bad());
+            }
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(bad)?;
+            let index = after[..end].trim();
+            steps.push(if let Some(n) = index.strip_prefix('#') {
+                let n = n
+                    .trim_start()
+                    .strip_prefix('-')
+                    .map_or(Ok(0), |n| n.trim().parse::<usize>().map_err(|_| bad()))?;
+                Step::FromEnd(n)
+            } else {
+                Step::Index(index.parse().map_err(|_| bad())?)
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(bad());
+        }
+    }
+    Ok(steps)
+}
+
+/// the path for the right operand of -> and ->>: a path, an array index or an object key
+fn operand_path(operand: &Value) -> anyhow::Result<Vec<Step>> {
+    if let Some(index) = operand.as_i64() {
+        return Ok(vec![match usize::try_from(index) {
+            Ok(index) => Step::Index(index),
+            Err(_) => Step::FromEnd(index.unsigned_abs() as usize),
+        }]);
+    }
+    let text = operand.to_string();
+    if text.starts_with('$') {
+        parse_path(&text)
+    } else {
+        Ok(vec![Step::Key(text)])
+    }
+}
+
+/// json -> path: the element as json text, json ->> path: the element as sql value
+/// NULL when there is no such element
+pub(crate) fn extract_operator(json: &Value, path: &Value, as_json: bool) -> anyhow::Result<Value> {
+    if json.is_null() || path.is_null() {
+        return Ok(Value::null());
+    }
+    let json = Json::parse(&json.to_string())?;
+    Ok(match json.get(&operand_path(path)?) {
+        None => Value::null(),
+        Some(element) if as_json => Value::from_text(element.to_string()),
+        Some(element) => element.to_value(),
+    })
+}
+
+/// adds json, json_valid, json_type, json_extract, json_array_length, json_array and json_object
+/// json arguments are text, paths start with $ and consist of .key, ."key", [n] and [#-n]
+pub(crate) fn register(registry: &mut Registry) {
+    registry.add("json", (1, Some(1)), true, |args| {
+        with_json(&args[0], |json| Ok(Value::from_text(json.to_string())))
+    });
+    registry.add("json_valid", (1, Some(1)), true, |args| {
+        Ok(if args[0].is_null() {
+            Value::null()
+        } else {
+            Value::from_bool(Json::parse(&args[0].to_string()).is_ok())
+        })
+    });
+    registry.add("json_type", (1, Some(2)), true, |args| {
+        with_element(args, |element| Value::from_text(element.type_name()))
+    });
+    // one path: the element as sql value, more paths: a json array with the elements
+    registry.add("json_extract", (2, None), true, |args| {
+        if args.len() == 2 {
+            return with_element(args, Json::to_value);
+        }
+        with_json(&args[0], |json| {
+            let elements = args[1..]
+                .iter()
+                .map(|path| Ok(json.get(&parse_path(&path.to_string())?).cloned()))
+                .collect::<anyhow::Result<Vec<Option<Json>>>>()?;
+            let elements = elements
+                .into_iter()
+                .map(|element| element.unwrap_or(Json::Null))
+                .collect();
+            Ok(Value::from_text(Json::Array(elements).to_string()))
+        })
+    });
+    // 0 for elements that are not arrays
+    registry.add("json_array_length", (1, Some(2)), true, |args| {
+        with_element(args, |element| match element {
+            Json::Array(elements) => Value::from_i64(elements.len() as i64),
+            _ => Value::from_i64(0),
+        })
+    });
+    registry.add("json_array", (0, None), true, |args| {
+        let elements = args
+            .iter()
+            .map(Json::from_value)
+            .collect::<anyhow::Result<Vec<Json>>>()?;
+        Ok(Value::from_text(Json::Array(elements).to_string()))
+    });
+    // json_object(name, value, ..)
+    registry.add("json_object", (0, None), true, |args| {
+        if args.len() % 2 != 0 {
+            return Err(anyhow!(
+                "json_object() requires an even number of arguments"
+            ));
+        }
+        let mut members = vec![];
+        for pair in args.chunks(2) {
+            if !pair[0].is_text() {
+                return Err(anyhow!("json_object() labels must be TEXT"));
+            }
+            members.push((pair[0].to_string(), Json::from_value(&pair[1])?));
+        }
+        Ok(Value::from_text(Json::Object(members).to_string()))
+    });
+}
+
+/// NULL for NULL, an error for malformed json
+fn with_json(
+    value: &Value,
+    f: impl FnOnce(&Json) -> anyhow::Result<Value>,
+) -> anyhow::Result<Value> {
+    if value.is_null() {
+        return Ok(Value::null());
+    }
+    f(&Json::parse(&value.to_string())?)
+}
+
+/// f(json, path), for the element at the optional path; NULL when there is no such element
+fn with_element(args: &[Value], f: impl FnOnce(&Json) -> Value) -> anyhow::Result<Value> {
+    if args.get(1).is_some_and(Value::is_null) {
+        return Ok(Value::null());
+    }
+    with_json(&args[0], |json| {
+        let path = match args.get(1) {
+            Some(path) => parse_path(&path.to_string())?,
+            None => vec![],
+        };
+        Ok(json.get(&path).map_or_else(Value::null, f))
+    })
+}
+
+/// the columns of json_each and json_tree
+pub(crate) const COLUMNS: [&str; 8] = [
+    "key", "value", "type", "atom", "id", "parent", "fullkey", "path",
+];
+
+/// the table-valued functions json_each(json [, path]) and json_tree(json [, path])
+/// json_each has a row for every child of the element, json_tree for the element and
+/// all its descendants. Returns None when there is no function with the name
+pub(crate) fn table_function(name: &str, args: &[Value]) -> Option<anyhow::Result<Table>> {
+    let recursive = match name {
+        "json_each" => false,
+        "json_tree" => true,
+        _ => return None,
+    };
+    Some(each(name, args, recursive))
+}
+
+fn each(name: &str, args: &[Value], recursive: bool) -> anyhow::Result<Table> {
+    let mut table = Table::new("");
+    for column in COLUMNS {
+        table.add_column(column, false);
+    }
+    if !(1..=2).contains(&args.len()) {
+        return Err(anyhow!("Wrong number of arguments to function {}()", name));
+    }
+    if args.iter().any(Value::is_null) {
+        return Ok(table);
+    }
+    let json = Json::parse(&args[0].to_string())?;
+    let root = match args.get(1) {
+        Some(path) => path.to_string(),
+        None => "$".to_string(),
+    };
+    let Some(element) = json.get(&parse_path(&root)?) else {
+        return Ok(table);
+    };
+    let mut walk = Walk {
+        table,
+        id: 0,
+        recursive,
+    };
+    let parent_path = parent_path(&root);
+    match element {
+        // json_each leaves out the container itself
+        Json::Array(_) | Json::Object(_) if !recursive => {
+            walk.children(element, &root, None)?;
+        }
+        _ => walk.row(element, Value::null(), &root, &parent_path, None)?,
+    }
+    Ok(walk.table)
+}
+
+struct Walk {
+    table: Table,
+    id: i64,
+    recursive: bool,
+}
+
+impl Walk {
+    fn row(
+        &mut self,
+        element: &Json,
+        key: Value,
+        fullkey: &str,
+        path: &str,
+        parent: Option<i64>,
+    ) -> anyhow::Result<()> {
+        let id = self.id;
+        self.id += 1;
+        let atom = match element {
+            Json::Array(_) | Json::Object(_) => Value::null(),
+            _ => element.to_value(),
+        };
+        let mut record = Record::default();
+        record.add_value(key);
+        record.add_value(element.to_value());
+        record.add_value(Value::from_text(element.type_name()));
+        record.add_value(atom);
+        record.add_value(Value::from_i64(id));
+        record.add_value(parent.map_or_else(Value::null, Value::from_i64));
+        record.add_value(Value::from_text(fullkey));
+        record.add_value(Value::from_text(path));
+        self.table.append(record)?;
+        if self.recursive {
+            self.children(element, fullkey, Some(id))?;
+        }
+        Ok(())
+    }
+
+    fn children(
+        &mut self,
+        element: &Json,
+        fullkey: &str,
+        parent: Option<i64>,
+    ) -> anyhow::Result<()> {
+        match element {
+            Json::Array(elements) => {
+                for (i, child) in elements.iter().enumerate() {
+                    let key = Value::from_i64(i as i64);
+                    self.row(child, key, &format!("{}[{}]", fullkey, i), fullkey, parent)?;
+                }
+            }
+            Json::Object(members) => {
+                for (name, child) in members {
+                    let key = Value::from_text(name.as_str());
+                    self.row(child, key, &key_path(fullkey, name), fullkey, parent)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// $.a for simple names, $."a b" otherwise
+fn key_path(path: &str, name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        format!("{}.{}", path, name)
+    } else {
+        format!("{}.\"{}\"", path, name)
+    }
+}
+
+/// $.a[1] => $.a
+fn parent_path(path: &str) -> String {
+    let end = path.rfind(['.', '[']).unwrap_or(path.len()).max(1);
+    path[..end].to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> String {
+        Registry::default().call(name, args).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#" {"a": [1, 2.5e3, -0.5], "b": {"c": "x\"é😀"}, "d": null, "e": true} "#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(
+            json.to_string(),
+            r#"{"a":[1,2.5e3,-0.5],"b":{"c":"x\"é😀"},"d":null,"e":true}"#
+        );
+        for malformed in ["", "{", "[1,]", "01", "{'a': 1}", "[1] 2", "\"\t\""] {
+            assert!(Json::parse(malformed).is_err(), "{}", malformed);
+        }
+        assert!(Json::parse(&"[".repeat(2000)).is_err());
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(
+            parse_path(r#"$.a[2]."b c"[#-1]"#).unwrap(),
+            vec![
+                Step::Key("a".to_string()),
+                Step::Index(2),
+                Step::Key("b c".to_string()),
+                Step::FromEnd(1)
+            ]
+        );
+        assert!(parse_path("a.b").is_err());
+        assert!(parse_path("$[x]").is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        let doc = Value::from_text(r#"{"a": {"b": [10, 20, 30]}, "s": "x"}"#);
+        let text = |t: &str| Value::from_text(t);
+        assert_eq!(call("json_extract", &[doc.clone(), text("$.a.b[1]")]), "20");
+        assert_eq!(
+            call("json_extract", &[doc.clone(), text("$.a.b[#-1]")]),
+            "30"
+        );
+        assert_eq!(call("json_extract", &[doc.clone(), text("$.s")]), "x");
+        assert_eq!(call("json_extract", &[doc.clone(), text("$.z")]), "NULL");
+        assert_eq!(
+            call("json_extract", &[doc.clone(), text("$.s"), text("$.z")]),
+            r#"["x",null]"#
+        );
+        assert_eq!(
+            call("json_array_length", &[doc.clone(), text("$.a.b")]),
+            "3"
+        );
+        assert_eq!(call("json_type", &[doc.clone(), text("$.a")]), "object");
+        assert_eq!(call("json_valid", &[text("{]")]), "0");
+        assert_eq!(
+            call(
+                "json_object",
+                &[text("n"), Value::from(1), text("l"), text("[1,2]")]
+            ),
+            r#"{"n":1,"l":[1,2]}"#
+        );
+        assert_eq!(
+            call(
+                "json_array",
+                &[Value::null(), text("a\"b"), Value::from_f64(1.5)]
+            ),
+            r#"[null,"a\"b",1.5]"#
+        );
+        let error = Registry::default()
+            .call("json_extract", &[text("{"), text("$")])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Malformed JSON");
+    }
+
+    #[test]
+    fn test_tree() {
+        let doc = Value::from_text(r#"{"a": [1, {"b c": 2}]}"#);
+        let tree = table_function("json_tree", &[doc]).unwrap().unwrap();
+        let rows: Vec<String> = tree
+            .iter()
+            .map(|r| format!("{} {} {} {}", r.get(6), r.get(2), r.get(4), r.get(5)))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "$ object 0 NULL",
+                "$.a array 1 0",
+                "$.a[0] integer 2 1",
+                "$.a[1] object 3 1",
+                "$.a[1].\"b c\" integer 4 3",
+            ]
+        );
+    }
+}
@@ -1,13 +1,26 @@
+pub mod affinity;
+pub mod collation;
+mod constraint;
+pub mod database;
+pub mod datetime;
+pub mod decimal;
+mod distinct;
+mod functions;
 pub mod groupby;
+mod id_sequence;
+mod index;
 pub mod join;
+mod json;
 pub mod order;
 mod page;
+pub mod pager;
 pub mod print;
 pub mod read;
+mod record;
+mod regexp;
 pub mod sql;
 pub mod table;
+pub mod union;
 pub mod value;
 mod varint;
 pub mod vm;
-mod id_sequence;
-mod record;
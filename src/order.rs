@@ -1,6 +1,10 @@
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
 
-use crate::table::{Key, Table};
+use crate::database::Database;
+use crate::sql::ast::OrderingTerm;
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm::eval::{evaluate, expression_collation};
 
 impl Table {
     // pub fn order_by(&self, expression: &str) -> Self {
@@ -16,4 +20,47 @@ impl Table {
     //     ordered.records = sorted_records;
     //     ordered
     // }
+
+    /// returns a copy of the table, sorted on the terms
+    /// NULLs come first in ascending order, records with equal keys keep their order
+    /// text is compared with the collation of the term
+    pub(crate) fn sort(
+        &self,
+        terms: &[OrderingTerm],
+        database: &Database,
+    ) -> anyhow::Result<Table> {
+        let collations = terms
+            .iter()
+            .map(|term| expression_collation(&term.expression, database, self))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut keyed = vec![];
+        for record in self.iter() {
+            let key = terms
+                .iter()
+                .map(|term| evaluate(&term.expression, database, self, &record))
+                .collect::<anyhow::Result<Vec<Value>>>()?;
+            keyed.push((key, record));
+        }
+        keyed.sort_by(|(left, _), (right, _)| {
+            for (i, term) in terms.iter().enumerate() {
+                let ordering = collations[i].compare(&left[i], &right[i]);
+                let ordering = if term.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+
+        // the records get new rowids, in sorted order
+        let mut sorted = self.empty_copy();
+        for (_, record) in keyed {
+            sorted.append(record)?;
+        }
+        Ok(sorted)
+    }
 }
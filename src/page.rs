@@ -2,10 +2,15 @@ use crate::record::Record;
 use crate::value::Value;
 use crate::varint;
 use byteorder::{BigEndian, ByteOrder};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-const PAGE_SIZE: usize = 4096;
+pub(crate) const PAGE_SIZE: usize = 4096;
+/// the largest encoded record that fits in an empty page, next to its cell pointer
+pub(crate) const MAX_RECORD_SIZE: usize = PAGE_SIZE - 3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(dead_code)] // work in progress
 pub enum PageType {
     Root,
     Interior,
@@ -13,17 +18,19 @@ pub enum PageType {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)] // work in progress
 pub struct Page {
     pagetype: PageType,
     id: usize,           // rowid
-    start: Value,        // first value in page
-    end: Value,          // last value in page
-    data: Vec<u8>,       // page data
+    start: Value,        // first rowid in page
+    end: Value,          // last rowid in page
+    data: Rc<Vec<u8>>,   // page data, shared with copies until one of them writes it
     index_pos: u16,      // current write position for indexes (to the page data)
     data_pos: u16, // current write position for data (written backwards from the end of the page)
     key: usize,    // ?
-    children: Vec<Page>, // child pages
+    pub(crate) children: Vec<Rc<RefCell<Page>>>, // child pages
     n_records: usize, // nr of records in the page
+    dirty: bool,      // written since it was last saved to a page file
 }
 
 impl Page {
@@ -33,43 +40,279 @@ impl Page {
             id,
             start: Value::null(),
             end: Value::null(),
-            data: vec![0; PAGE_SIZE],
+            data: Rc::new(vec![0; PAGE_SIZE]),
             index_pos: 0,
             data_pos: (PAGE_SIZE - 1) as u16,
             key: 0,
             children: vec![],
             n_records: 0,
+            dirty: true,
         }
     }
 
-    pub fn insert(&mut self, record: Record) {
-        let bytes: Vec<u8> = record.into();
+    /// inserts an encoded record
+    /// records are appended in rowid order
+    pub fn insert(&mut self, rowid: u64, bytes: Vec<u8>) -> anyhow::Result<()> {
+        if !self.has_room(bytes.len()) {
+            return Err(anyhow::anyhow!(
+                "Record {} of {} bytes does not fit in page {}",
+                rowid,
+                bytes.len(),
+                self.id
+            ));
+        }
+        if self.n_records == 0 {
+            self.start = Value::from_i64(rowid as i64);
+        }
+        self.end = Value::from_i64(rowid as i64);
         self.insert_data(bytes);
         self.insert_index(self.data_pos);
         self.n_records += 1;
+        Ok(())
+    }
+
+    /// true if an encoded record of len bytes (plus its index entry) still fits in the page
+    pub(crate) fn has_room(&self, len: usize) -> bool {
+        self.index_pos as usize + 2 + len <= self.data_pos as usize
     }
 
     fn insert_data(&mut self, bytes: Vec<u8>) {
         let end = self.data_pos as usize;
         self.data_pos -= bytes.len() as u16;
-        self.data.splice(self.data_pos as usize..end, bytes);
+        let start = self.data_pos as usize;
+        self.data_mut().splice(start..end, bytes);
     }
 
     fn insert_index(&mut self, value: u16) {
         let bytes = u16_to_bytes(value);
         let start = self.index_pos as usize;
         self.index_pos += bytes.len() as u16;
-        self.data.splice(start..self.index_pos as usize, bytes);
+        let end = self.index_pos as usize;
+        self.data_mut().splice(start..end, bytes);
+    }
+
+    /// the page data for writing, copied first when a copy of the page shares it
+    fn data_mut(&mut self) -> &mut Vec<u8> {
+        self.dirty = true;
+        Rc::make_mut(&mut self.data)
+    }
+
+    /// the rowid of the first record, None for an empty page
+    pub(crate) fn first_rowid(&self) -> Option<u64> {
+        self.start.as_i64().map(|start| start as u64)
+    }
+
+    /// the rowid of the last record, None for an empty page
+    pub(crate) fn last_rowid(&self) -> Option<u64> {
+        self.end.as_i64().map(|end| end as u64)
+    }
+
+    /// finds the record with rowid, using binary search on the cell pointers
+    pub(crate) fn get_by_rowid(&self, rowid: u64) -> Option<Record> {
+        self.get(self.find(rowid)?)
+    }
+
+    /// the position of the record with rowid in the cell pointer array
+    fn find(&self, rowid: u64) -> Option<usize> {
+        let (mut low, mut high) = (0, self.n_records);
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.rowid_at(mid).cmp(&rowid) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    /// removes the record by removing its cell pointer
+    /// the space of its data is reclaimed when the page is rewritten
+    pub(crate) fn delete(&mut self, rowid: u64) -> bool {
+        let Some(row_index) = self.find(rowid) else {
+            return false;
+        };
+        let start = row_index * 2;
+        let end = self.index_pos as usize;
+        self.data_mut().copy_within(start + 2..end, start);
+        self.index_pos -= 2;
+        self.n_records -= 1;
+        self.start = self.rowid_value(0);
+        self.end = self.rowid_value(self.n_records.wrapping_sub(1));
+        true
+    }
+
+    /// replaces the record, in place when it is not larger than the old one,
+    /// else in the free space, rewriting the page without gaps when that is too small
+    /// returns false when the record does not fit, the caller must split the page then
+    pub(crate) fn update(&mut self, rowid: u64, bytes: Vec<u8>) -> bool {
+        let Some(row_index) = self.find(rowid) else {
+            return false;
+        };
+        let physical_index = self.physical_index(row_index);
+        if bytes.len() <= self.cell_len(row_index) {
+            self.data_mut()[physical_index..physical_index + bytes.len()].copy_from_slice(&bytes);
+            return true;
+        }
+        // the record keeps its cell pointer
+        if self.index_pos as usize + bytes.len() <= self.data_pos as usize {
+            self.insert_data(bytes);
+            let data_pos = self.data_pos;
+            BigEndian::write_u16(&mut self.data_mut()[row_index * 2..row_index * 2 + 2], data_pos);
+            return true;
+        }
+        let mut cells = self.cells();
+        cells[row_index].1 = bytes;
+        self.rewrite(cells).is_ok()
+    }
+
+    /// the rowids and encoded records in the page, in rowid order
+    pub(crate) fn cells(&self) -> Vec<(u64, Vec<u8>)> {
+        (0..self.n_records)
+            .filter_map(|row_index| self.get(row_index))
+            .map(|record| (record.rowid, record.into()))
+            .collect()
+    }
+
+    /// replaces the contents of the page by the cells, without gaps between the records
+    /// the page is left unchanged when the cells do not fit
+    pub(crate) fn rewrite(&mut self, cells: Vec<(u64, Vec<u8>)>) -> anyhow::Result<()> {
+        if !fits(&cells) {
+            return Err(anyhow::anyhow!(
+                "{} records do not fit in page {}",
+                cells.len(),
+                self.id
+            ));
+        }
+        self.data = Rc::new(vec![0; PAGE_SIZE]);
+        self.dirty = true;
+        self.index_pos = 0;
+        self.data_pos = (PAGE_SIZE - 1) as u16;
+        self.n_records = 0;
+        self.start = Value::null();
+        self.end = Value::null();
+        for (rowid, bytes) in cells {
+            self.insert(rowid, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// a copy of the page and its child pages, that shares the page data with the original
+    /// until either of them writes to a page, which then gets a copy of its own
+    pub(crate) fn copy_on_write(&self) -> Page {
+        Page {
+            pagetype: self.pagetype.clone(),
+            id: self.id,
+            start: self.start.clone(),
+            end: self.end.clone(),
+            data: Rc::clone(&self.data),
+            index_pos: self.index_pos,
+            data_pos: self.data_pos,
+            key: self.key,
+            children: self
+                .children
+                .iter()
+                .map(|child| Rc::new(RefCell::new(child.borrow().copy_on_write())))
+                .collect(),
+            n_records: self.n_records,
+            dirty: self.dirty,
+        }
+    }
+
+    /// true when the pages have not been written since one was copied from the other
+    #[cfg(test)]
+    pub(crate) fn shares_data(&self, other: &Page) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+
+    /// the page as stored in a page file: the number of records and the data position,
+    /// followed by the page data
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = u16_to_bytes(self.n_records as u16);
+        bytes.append(&mut u16_to_bytes(self.data_pos));
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// reads a page written by to_bytes
+    pub(crate) fn from_bytes(pagetype: PageType, id: usize, bytes: &[u8]) -> anyhow::Result<Page> {
+        if bytes.len() < PAGE_SIZE + 4 {
+            return Err(anyhow::anyhow!("Page {} is truncated", id));
+        }
+        let n_records = BigEndian::read_u16(&bytes[0..2]) as usize;
+        let data_pos = BigEndian::read_u16(&bytes[2..4]);
+        if n_records * 2 > data_pos as usize || data_pos as usize >= PAGE_SIZE {
+            return Err(anyhow::anyhow!("Page {} is corrupt", id));
+        }
+        let mut page = Page::new(pagetype, id);
+        page.data = Rc::new(bytes[4..PAGE_SIZE + 4].to_vec());
+        page.n_records = n_records;
+        page.index_pos = (n_records * 2) as u16;
+        page.data_pos = data_pos;
+        page.dirty = false;
+        page.start = page.rowid_value(0);
+        page.end = page.rowid_value(n_records.saturating_sub(1));
+        Ok(page)
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    /// true when the page was written since it was read from or saved to a page file
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub(crate) fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    pub(crate) fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// the number of bytes used by the records and their cell pointers, gaps excluded
+    pub(crate) fn used(&self) -> usize {
+        (0..self.n_records)
+            .map(|row_index| self.cell_len(row_index) + 2)
+            .sum()
+    }
+
+    /// the length of the encoded record at row_index
+    fn cell_len(&self, row_index: usize) -> usize {
+        self.get(row_index)
+            .map(|record| Vec::<u8>::from(record).len())
+            .unwrap_or(0)
+    }
+
+    fn rowid_value(&self, row_index: usize) -> Value {
+        if row_index < self.n_records {
+            Value::from_i64(self.rowid_at(row_index) as i64)
+        } else {
+            Value::null()
+        }
+    }
+
+    /// decodes only the rowid of the record at row_index
+    fn rowid_at(&self, row_index: usize) -> u64 {
+        let physical_index = self.physical_index(row_index);
+        let (bytes_read, _) = varint::read(&self.data[physical_index..]);
+        varint::read(&self.data[physical_index + bytes_read..]).1
+    }
+
+    fn physical_index(&self, row_index: usize) -> usize {
+        BigEndian::read_u16(&self.data[row_index * 2..=row_index * 2 + 1]) as usize
     }
 
     pub fn get(&self, row_index: usize) -> Option<Record> {
         if row_index < self.n_records {
-            let physical_index = BigEndian::read_u16(&self.data[row_index * 2..=row_index * 2 + 1]);
+            let physical_index = self.physical_index(row_index) as u16;
             let (bytes_read, len) = varint::read(&self.data[physical_index as usize..]);
             Some(
                 (
                     len,
-                    &self.data[bytes_read + physical_index as usize..=bytes_read + physical_index as usize + len as usize],
+                    &self.data[bytes_read + physical_index as usize..bytes_read + physical_index as usize + len as usize],
                 )
                     .into(),
             )
@@ -79,6 +322,11 @@ impl Page {
     }
 }
 
+/// true when the cells fit in an empty page
+pub(crate) fn fits(cells: &[(u64, Vec<u8>)]) -> bool {
+    cells.iter().map(|(_, bytes)| bytes.len() + 2).sum::<usize>() < PAGE_SIZE
+}
+
 fn u16_to_bytes(value: u16) -> Vec<u8> {
     let mut buf = vec![0; 2];
     BigEndian::write_u16(&mut buf, value);
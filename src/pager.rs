@@ -0,0 +1,426 @@
+use crate::page::PAGE_SIZE;
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// the size of a page in the page file: the page data and its header
+pub const FILE_PAGE_SIZE: usize = PAGE_SIZE + 4;
+
+const WAL_MAGIC: &[u8; 8] = b"csvwal01";
+const WAL_HEADER_SIZE: usize = 12; // magic, salt
+const FRAME_HEADER_SIZE: usize = 16; // page number, commit, salt, checksum
+const FRAME_SIZE: usize = FRAME_HEADER_SIZE + FILE_PAGE_SIZE;
+
+/// reads and writes fixed size pages in a file, with a write-ahead log
+///
+/// a commit appends the changed pages as frames to the log `<file>-wal`,
+/// the last frame of a commit records the number of pages in the file after it.
+/// every frame has a checksum over itself and all frames before it,
+/// so a commit that was cut off by a crash is recognized and ignored.
+/// a checkpoint copies the pages in the log to the file and then empties the log.
+/// opening the file recovers: committed frames are checkpointed, the rest is discarded
+#[derive(Debug)]
+pub struct Pager {
+    file: File,
+    wal: File,
+    wal_path: PathBuf,
+    salt: u32,     // changes with every checkpoint, so old frames never match
+    checksum: u32, // of the last frame in the log
+    wal_len: u64,  // the end of the last commit in the log, the next one is written there
+    wal_pages: HashMap<u32, Vec<u8>>, // the newest committed version of the pages in the log
+    page_count: u32,
+}
+
+impl Pager {
+    /// opens or creates the page file, and recovers from the log when there is one
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut wal_path = path.as_os_str().to_owned();
+        wal_path.push("-wal");
+        let wal_path = PathBuf::from(wal_path);
+        let wal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&wal_path)?;
+        let page_count = (file.metadata()?.len() / FILE_PAGE_SIZE as u64) as u32;
+        let mut pager = Pager {
+            file,
+            wal,
+            wal_path,
+            salt: 0,
+            checksum: 0,
+            wal_len: 0,
+            wal_pages: HashMap::new(),
+            page_count,
+        };
+        pager.recover()?;
+        Ok(pager)
+    }
+
+    /// the number of pages in the file, committed changes included
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// the path of the write-ahead log
+    pub fn wal_path(&self) -> &Path {
+        &self.wal_path
+    }
+
+    /// the number of committed pages in the log, that a checkpoint copies to the file
+    pub fn log_pages(&self) -> usize {
+        self.wal_pages.len()
+    }
+
+    /// the committed contents of the page
+    pub fn read(&mut self, page_number: u32) -> anyhow::Result<Vec<u8>> {
+        if page_number >= self.page_count {
+            return Err(anyhow::anyhow!(
+                "Page {} does not exist, the file has {} pages",
+                page_number,
+                self.page_count
+            ));
+        }
+        if let Some(page) = self.wal_pages.get(&page_number) {
+            return Ok(page.clone());
+        }
+        let mut page = vec![0; FILE_PAGE_SIZE];
+        self.file
+            .seek(SeekFrom::Start(page_number as u64 * FILE_PAGE_SIZE as u64))?;
+        self.file.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    /// writes the pages to the log as one transaction, after which the file has page_count pages
+    /// the pages are durable when this returns, and a crash before that leaves none of them
+    pub fn commit(&mut self, pages: &[(u32, Vec<u8>)], page_count: u32) -> anyhow::Result<()> {
+        if pages.is_empty() {
+            return Err(anyhow::anyhow!("Nothing to commit"));
+        }
+        let mut frames = Vec::with_capacity(pages.len() * FRAME_SIZE);
+        let mut checksum = self.checksum;
+        for (index, (page_number, data)) in pages.iter().enumerate() {
+            if data.len() > FILE_PAGE_SIZE {
+                return Err(anyhow::anyhow!("Page {} is too large", page_number));
+            }
+            if *page_number >= page_count {
+                return Err(anyhow::anyhow!(
+                    "Page {} is beyond the {} pages of the file",
+                    page_number,
+                    page_count
+                ));
+            }
+            let commit = if index == pages.len() - 1 {
+                page_count
+            } else {
+                0
+            };
+            let mut frame = vec![0; FRAME_SIZE];
+            BigEndian::write_u32(&mut frame[0..4], *page_number);
+            BigEndian::write_u32(&mut frame[4..8], commit);
+            BigEndian::write_u32(&mut frame[8..12], self.salt);
+            frame[FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + data.len()].copy_from_slice(data);
+            checksum = frame_checksum(checksum, &frame);
+            BigEndian::write_u32(&mut frame[12..16], checksum);
+            frames.extend(frame);
+        }
+        // a write that fails halfway leaves frames that the next commit overwrites,
+        // the salt and checksum only change when the commit succeeds
+        self.wal.seek(SeekFrom::Start(self.wal_len))?;
+        let written = self
+            .wal
+            .write_all(&frames)
+            .and_then(|_| self.wal.sync_data());
+        if let Err(error) = written {
+            let _ = self.wal.set_len(self.wal_len);
+            return Err(error.into());
+        }
+
+        self.wal_len += frames.len() as u64;
+        self.checksum = checksum;
+        for (page_number, data) in pages {
+            let mut page = data.clone();
+            page.resize(FILE_PAGE_SIZE, 0);
+            self.wal_pages.insert(*page_number, page);
+        }
+        self.wal_pages
+            .retain(|page_number, _| *page_number < page_count);
+        self.page_count = page_count;
+        Ok(())
+    }
+
+    /// copies the committed pages from the log to the file, and empties the log
+    /// a crash halfway leaves the log as it was, so the next open does it again
+    pub fn checkpoint(&mut self) -> anyhow::Result<()> {
+        let mut page_numbers: Vec<u32> = self.wal_pages.keys().copied().collect();
+        page_numbers.sort();
+        for page_number in page_numbers {
+            self.file
+                .seek(SeekFrom::Start(page_number as u64 * FILE_PAGE_SIZE as u64))?;
+            self.file.write_all(&self.wal_pages[&page_number])?;
+        }
+        self.file
+            .set_len(self.page_count as u64 * FILE_PAGE_SIZE as u64)?;
+        self.file.sync_all()?;
+        self.wal_pages.clear();
+        self.reset_wal()
+    }
+
+    /// starts an empty log, with a new salt
+    fn reset_wal(&mut self) -> anyhow::Result<()> {
+        let salt = self.salt.wrapping_add(1);
+        let mut header = WAL_MAGIC.to_vec();
+        header.extend(salt.to_be_bytes());
+        self.wal.set_len(0)?;
+        self.wal.seek(SeekFrom::Start(0))?;
+        self.wal.write_all(&header)?;
+        self.wal.sync_all()?;
+        self.salt = salt;
+        self.checksum = salt;
+        self.wal_len = WAL_HEADER_SIZE as u64;
+        Ok(())
+    }
+
+    /// reads the committed frames in the log and checkpoints them
+    /// reading stops at the first frame that is incomplete or does not match its checksum,
+    /// the frames of a commit that did not get its commit frame are dropped
+    fn recover(&mut self) -> anyhow::Result<()> {
+        let mut log = vec![];
+        self.wal.seek(SeekFrom::Start(0))?;
+        self.wal.read_to_end(&mut log)?;
+        if log.len() >= WAL_HEADER_SIZE && &log[0..8] == WAL_MAGIC {
+            self.salt = BigEndian::read_u32(&log[8..12]);
+            let mut checksum = self.salt;
+            let mut pending = vec![];
+            for frame in log[WAL_HEADER_SIZE..].chunks_exact(FRAME_SIZE) {
+                let stored = BigEndian::read_u32(&frame[12..16]);
+                if BigEndian::read_u32(&frame[8..12]) != self.salt
+                    || frame_checksum(checksum, frame) != stored
+                {
+                    break;
+                }
+                checksum = stored;
+                let page_number = BigEndian::read_u32(&frame[0..4]);
+                pending.push((page_number, frame[FRAME_HEADER_SIZE..].to_vec()));
+                let commit = BigEndian::read_u32(&frame[4..8]);
+                if commit > 0 {
+                    self.wal_pages.extend(pending.drain(..));
+                    self.wal_pages
+                        .retain(|page_number, _| *page_number < commit);
+                    self.page_count = commit;
+                }
+            }
+        }
+        self.checkpoint()
+    }
+}
+
+/// FNV-1a over the frame header and data, continuing from the checksum of the previous frame
+fn frame_checksum(previous: u32, frame: &[u8]) -> u32 {
+    frame[0..12]
+        .iter()
+        .chain(&frame[FRAME_HEADER_SIZE..])
+        .fold(previous ^ 0x811c_9dc5, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+        })
+}
+
+/// a page file in the temporary directory, removed with its log when dropped
+#[cfg(test)]
+pub(crate) struct TempFile {
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(test)]
+impl TempFile {
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("csv-{}-{}", std::process::id(), name));
+        let file = TempFile { path };
+        file.remove();
+        file
+    }
+
+    fn remove(&self) {
+        let mut wal = self.path.as_os_str().to_owned();
+        wal.push("-wal");
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_file(PathBuf::from(wal));
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// the lengths at which a crash may cut off a log of log_len bytes: every length up to
+/// the first frame, those around the start of every frame and of its data, and every
+/// stride-th length, which falls on a different place in every frame
+#[cfg(test)]
+pub(crate) fn crash_offsets(log_len: usize, stride: usize) -> Vec<usize> {
+    let mut offsets: Vec<usize> = (0..=WAL_HEADER_SIZE + 1).collect();
+    offsets.extend((0..=log_len).step_by(stride));
+    for frame in (WAL_HEADER_SIZE..log_len).step_by(FRAME_SIZE) {
+        for boundary in [frame, frame + FRAME_HEADER_SIZE, frame + FRAME_SIZE] {
+            offsets.extend([boundary - 1, boundary, boundary + 1]);
+        }
+    }
+    offsets.retain(|offset| *offset <= log_len);
+    offsets.sort();
+    offsets.dedup();
+    offsets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn page(byte: u8) -> Vec<u8> {
+        vec![byte; FILE_PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_commit_and_checkpoint() {
+        let file = TempFile::new("pager-commit");
+        let path = file.path.clone();
+        let mut pager = Pager::open(&path).unwrap();
+        assert_eq!(pager.page_count(), 0);
+        pager.commit(&[(0, page(1)), (1, page(2))], 2).unwrap();
+        assert_eq!(pager.read(1).unwrap(), page(2));
+        // committed but not checkpointed: the file is still empty
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        pager.checkpoint().unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            2 * FILE_PAGE_SIZE as u64
+        );
+        assert_eq!(
+            fs::metadata(pager.wal_path()).unwrap().len(),
+            WAL_HEADER_SIZE as u64
+        );
+        pager.commit(&[(0, page(3))], 1).unwrap();
+        drop(pager);
+
+        let mut pager = Pager::open(&path).unwrap();
+        assert_eq!(pager.page_count(), 1);
+        assert_eq!(pager.read(0).unwrap(), page(3));
+        assert!(pager.read(1).is_err());
+    }
+
+    #[test]
+    fn test_crash_during_commit() {
+        let file = TempFile::new("pager-crash");
+        let path = file.path.clone();
+        let mut pager = Pager::open(&path).unwrap();
+        pager.commit(&[(0, page(1)), (1, page(1))], 2).unwrap();
+        pager.checkpoint().unwrap();
+        pager.commit(&[(1, page(2))], 2).unwrap();
+        pager
+            .commit(&[(0, page(3)), (1, page(3)), (2, page(3))], 3)
+            .unwrap();
+        let wal_path = pager.wal_path().to_path_buf();
+        drop(pager);
+        let database = fs::read(&path).unwrap();
+        let log = fs::read(&wal_path).unwrap();
+
+        // the write of the log stops anywhere, a frame is written completely or not at all
+        for cut in crash_offsets(log.len(), 31) {
+            fs::write(&path, &database).unwrap();
+            fs::write(&wal_path, &log[..cut]).unwrap();
+            let mut pager = Pager::open(&path).unwrap();
+            let expected = if cut == log.len() {
+                3
+            } else if cut >= WAL_HEADER_SIZE + FRAME_SIZE {
+                2
+            } else {
+                1
+            };
+            assert_eq!(pager.read(1).unwrap(), page(expected), "cut at {}", cut);
+            assert_eq!(pager.page_count(), if expected == 3 { 3 } else { 2 });
+        }
+    }
+
+    /// /dev/full fails every write
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_failed_commit() {
+        let file = TempFile::new("pager-failed");
+        let mut pager = Pager::open(&file.path).unwrap();
+        pager.commit(&[(0, page(1))], 1).unwrap();
+        // the write stops halfway through the first frame
+        let full = OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let wal = std::mem::replace(&mut pager.wal, full);
+        assert!(pager.commit(&[(0, page(2)), (1, page(2))], 2).is_err());
+        OpenOptions::new()
+            .append(true)
+            .open(pager.wal_path())
+            .unwrap()
+            .write_all(&[2; FRAME_SIZE / 2])
+            .unwrap();
+        pager.wal = wal;
+        assert_eq!(pager.read(0).unwrap(), page(1));
+        assert_eq!(pager.page_count(), 1);
+
+        // the next commit is not lost behind what the failed one left
+        pager.commit(&[(0, page(3)), (1, page(3))], 2).unwrap();
+        drop(pager);
+        let mut pager = Pager::open(&file.path).unwrap();
+        assert_eq!(pager.page_count(), 2);
+        assert_eq!(pager.read(0).unwrap(), page(3));
+        assert_eq!(pager.read(1).unwrap(), page(3));
+    }
+
+    #[test]
+    fn test_crash_during_checkpoint() {
+        let file = TempFile::new("pager-checkpoint");
+        let path = file.path.clone();
+        let mut pager = Pager::open(&path).unwrap();
+        pager.commit(&[(0, page(1)), (1, page(1))], 2).unwrap();
+        pager.checkpoint().unwrap();
+        pager
+            .commit(&[(0, page(2)), (1, page(2)), (2, page(2))], 3)
+            .unwrap();
+        let wal_path = pager.wal_path().to_path_buf();
+        drop(pager);
+        let old = fs::read(&path).unwrap();
+        let log = fs::read(&wal_path).unwrap();
+        let mut new = vec![];
+        for byte in [2, 2, 2] {
+            new.extend(page(byte));
+        }
+
+        // a checkpoint writes the pages in order, and stops anywhere:
+        // around the start of every page and of its data, and every 31st byte
+        let starts = (0..=new.len()).step_by(FILE_PAGE_SIZE);
+        let cuts = starts
+            .flat_map(|start| [start.saturating_sub(1), start, start + 1, start + 4])
+            .chain((0..=new.len()).step_by(31));
+        for cut in cuts.filter(|cut| *cut <= new.len()) {
+            let mut database = new[..cut].to_vec();
+            if cut < old.len() {
+                database.extend_from_slice(&old[cut..]);
+            }
+            fs::write(&path, &database).unwrap();
+            fs::write(&wal_path, &log).unwrap();
+            let mut pager = Pager::open(&path).unwrap();
+            assert_eq!(pager.page_count(), 3);
+            for page_number in 0..3 {
+                assert_eq!(pager.read(page_number).unwrap(), page(2), "cut at {}", cut);
+            }
+        }
+    }
+}
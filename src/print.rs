@@ -12,9 +12,9 @@ impl Table {
                 .split(",")
                 .map(|c| c.trim())
                 .collect::<Vec<&str>>();
-            cols.iter()
-                .filter(|c| !self.has_column(**c))
-                .any(|invalid| panic!("{} is not a column in this table", invalid));
+            if let Some(invalid) = cols.iter().find(|c| !self.has_column(**c)) {
+                panic!("{} is not a column in this table", invalid);
+            }
             self.pretty_print_select(cols);
         }
     }
@@ -95,7 +95,7 @@ impl Table {
         for record in self.iter().skip(offset).take(nrecords) {
             for col in self.select_columns(columns) {
                 let e = widths.get_mut(&col).unwrap();
-                let index = self.get_index(&col);
+                let index = self.get_index(col);
                 *e = (*e).max(record.get(index).string_len());
             }
         }
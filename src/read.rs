@@ -1,44 +1,265 @@
+use crate::affinity::Affinity;
+use crate::database::Database;
+use crate::datetime::{parse_iso, parse_with_format};
+use crate::sql::ast::Constraint;
+use crate::sql::parser::parse_expression;
 use crate::table::Table;
 
 use crate::record::Record;
+use crate::value::Value;
 use std::fs;
 
 const EOL: &str = "\n";
 
+/// settings for reading csv
+/// CsvOptions::new().separator(";").date_format("%d/%m/%Y").booleans(&["j"], &["n"])
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    separator: Option<String>,           // guessed from the data when not set
+    date_formats: Vec<String>,           // tried after ISO 8601
+    truthy: Vec<String>,                 // lowercase
+    falsy: Vec<String>,                  // lowercase
+    column_types: Vec<(String, String)>, // declared types by column name
+    constraints: Vec<Constraint>,        // checked for every row
+    checks: Vec<String>,                 // check expressions, parsed when reading
+}
+
+/// a row that breaks a constraint, does not fit in a page or has more values than the header,
+/// it is not loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub line: usize, // the header is line 1
+    pub message: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            separator: None,
+            date_formats: vec![],
+            truthy: vec!["true".into(), "yes".into()],
+            falsy: vec!["false".into(), "no".into()],
+            column_types: vec![],
+            constraints: vec![],
+            checks: vec![],
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// declares the type of a column, like 'NUMERIC' or 'VARCHAR(10)'
+    /// values are converted to its affinity (see Affinity::from_type_name),
+    /// text columns keep the text as it is in the file ('007' is not read as 7)
+    pub fn column_type(mut self, column: &str, type_name: &str) -> Self {
+        self.column_types
+            .push((column.to_string(), type_name.to_string()));
+        self
+    }
+
+    /// the values in the columns are unique and not NULL
+    pub fn primary_key(mut self, columns: &[&str]) -> Self {
+        self.constraints.push(Constraint::PrimaryKey(
+            columns.iter().map(|c| c.to_string()).collect(),
+        ));
+        self
+    }
+
+    /// no two rows have the same values in the columns, NULLs excepted
+    pub fn unique(mut self, columns: &[&str]) -> Self {
+        self.constraints.push(Constraint::Unique(
+            columns.iter().map(|c| c.to_string()).collect(),
+        ));
+        self
+    }
+
+    pub fn not_null(mut self, column: &str) -> Self {
+        self.constraints
+            .push(Constraint::NotNull(column.to_string()));
+        self
+    }
+
+    /// the values in the columns refer to the parent columns of a row in the parent table,
+    /// checked by Database::check_foreign_keys once both tables are in the database
+    /// without parent columns, the primary key of the parent
+    pub fn foreign_key(mut self, columns: &[&str], parent: &str, parent_columns: &[&str]) -> Self {
+        self.constraints.push(Constraint::ForeignKey {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            parent: parent.to_string(),
+            parent_columns: parent_columns.iter().map(|c| c.to_string()).collect(),
+        });
+        self
+    }
+
+    /// an expression like 'amount >= 0', that must not be false for a row
+    pub fn check(mut self, expression: &str) -> Self {
+        self.checks.push(expression.to_string());
+        self
+    }
+
+    /// replaces the texts that are read as booleans (case insensitive)
+    /// default: true and yes, false and no. Pass empty slices to read them as text
+    pub fn booleans(mut self, truthy: &[&str], falsy: &[&str]) -> Self {
+        self.truthy = truthy.iter().map(|t| t.to_lowercase()).collect();
+        self.falsy = falsy.iter().map(|f| f.to_lowercase()).collect();
+        self
+    }
+
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = Some(separator.to_string());
+        self
+    }
+
+    /// adds a format for recognizing dates and times, like '%m/%d/%Y %H:%M'
+    /// see datetime::parse_with_format
+    pub fn date_format(mut self, format: &str) -> Self {
+        self.date_formats.push(format.to_string());
+        self
+    }
+}
+
 impl Table {
     pub fn from_csv_file(name: &str, separator: Option<&str>) -> anyhow::Result<Table> {
+        Table::from_csv_file_with_options(name, &options(separator))
+    }
+
+    pub fn from_csv_file_with_options(name: &str, options: &CsvOptions) -> anyhow::Result<Table> {
         let csv = fs::read_to_string(name)?;
 
-        Ok(Table::from_csv(csv, separator))
+        Table::from_csv_with_options(csv, options)
     }
 
     pub fn from_csv(csv: impl Into<String>, separator: Option<&str>) -> Self {
+        Table::from_csv_with_options(csv, &options(separator))
+            .expect("You did not give me a separator and I could not guess it from the data")
+    }
+
+    /// numbers, booleans, and dates and times in ISO 8601 or one of the date formats
+    /// are recognized, other values are text
+    /// missing values are NULL; rows that break a declared constraint, do not fit in a page
+    /// or have more values than the header are left out,
+    /// see from_csv_checked for a report of them
+    /// errors like from_csv_checked
+    pub fn from_csv_with_options(
+        csv: impl Into<String>,
+        options: &CsvOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Table::from_csv_checked(csv, options)?.0)
+    }
+
+    /// reads the csv like from_csv_with_options, and returns the rows that are left out
+    /// errors for a type or constraint on a column that does not exist, a check that does
+    /// not parse, or no separator when it cannot be guessed
+    pub fn from_csv_checked(
+        csv: impl Into<String>,
+        options: &CsvOptions,
+    ) -> anyhow::Result<(Self, Vec<Violation>)> {
         let csv = csv.into();
-        let separator = separator.unwrap_or(
-            guess_separator(&csv)
-                .expect("You did not give me a separator and I could not guess it from the data"),
-        );
+        let separator = match options.separator.as_deref() {
+            Some(separator) => separator,
+            None => guess_separator(&csv).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "You did not give me a separator and I could not guess it from the data"
+                )
+            })?,
+        };
+        let database = Database::new();
         let mut table = Table::new("");
+        let mut violations = vec![];
         for (index, row) in csv.split(EOL).enumerate() {
             if index == 0 {
                 for col in row.split(separator) {
                     table.add_column(col, true);
                 }
-            } else if row.len() > 0 {
+                for (column, type_name) in &options.column_types {
+                    table
+                        .set_affinity(column, Affinity::from_type_name(type_name))
+                        .map_err(|_| {
+                            anyhow::anyhow!(
+                                "declared column type for a column that is not in the header: {}",
+                                column
+                            )
+                        })?;
+                }
+                for constraint in &options.constraints {
+                    table.add_constraint(constraint, &database)?;
+                }
+                for check in &options.checks {
+                    table
+                        .add_constraint(&Constraint::Check(parse_expression(check)?), &database)?;
+                }
+            } else if !row.is_empty() {
                 // skip empty lines
                 let mut record = Record::default();
-                for value in row.split(separator) {
+                for (index, value) in row.split(separator).enumerate() {
                     //TODO quoted values
-                    record.add_value(value);
+                    if index < table.cols.len() && table.affinity(index) == Affinity::Text {
+                        record.add_value(Value::from_text(value));
+                    } else {
+                        record.add_value(parse_value(value, options));
+                    }
+                }
+                // missing values are NULL, a row with more values than the header is left out
+                let inserted = if record.values.len() > table.cols.len() {
+                    Err(anyhow::anyhow!(
+                        "Row has {} values, the header has {} columns",
+                        record.values.len(),
+                        table.cols.len()
+                    ))
+                } else {
+                    record.values.resize(table.cols.len(), Value::null());
+                    if table.constraints.is_empty() {
+                        table.append(record)
+                    } else {
+                        table.insert_checked(record, &database)
+                    }
+                };
+                if let Err(error) = inserted {
+                    violations.push(Violation {
+                        line: index + 1,
+                        message: error.to_string(),
+                    });
                 }
-                table.insert(record);
             }
         }
-        table
+        Ok((table, violations))
+    }
+}
+
+fn options(separator: Option<&str>) -> CsvOptions {
+    match separator {
+        Some(separator) => CsvOptions::new().separator(separator),
+        None => CsvOptions::new(),
+    }
+}
+
+fn parse_value(text: &str, options: &CsvOptions) -> Value {
+    let value = Value::from(text);
+    if !value.is_text() {
+        return value;
     }
+    let text = text.trim();
+    let lowercase = text.to_lowercase();
+    if options.truthy.contains(&lowercase) {
+        return Value::from_bool(true);
+    }
+    if options.falsy.contains(&lowercase) {
+        return Value::from_bool(false);
+    }
+    parse_iso(text)
+        .or_else(|| {
+            options
+                .date_formats
+                .iter()
+                .find_map(|format| parse_with_format(text, format))
+        })
+        .map_or(value, Value::from)
 }
 
-fn guess_separator(csv: &String) -> Option<&'static str> {
+fn guess_separator(csv: &str) -> Option<&'static str> {
     let mut tabs = 0;
     let mut semis = 0;
     let mut commas = 0;
@@ -52,7 +273,7 @@ fn guess_separator(csv: &String) -> Option<&'static str> {
             _ => {}
         }
     }
-    let values = vec![(tabs, 0), (semis, 1), (commas, 2), (pipes, 3)];
+    let values = [(tabs, 0), (semis, 1), (commas, 2), (pipes, 3)];
     values.iter().max().map(|m| match m.1 {
         0 => "\t",
         1 => ";",
@@ -67,6 +288,141 @@ mod test {
     use super::*;
     #[test]
     fn test() {
-        guess_separator(&"a,b,c|d".to_string());
+        guess_separator("a,b,c|d");
+    }
+
+    #[test]
+    fn test_dates() {
+        let options = CsvOptions::new().separator(",").date_format("%m/%d/%Y");
+        let table = Table::from_csv_with_options(
+            "day,at\n1/12/2024,2024-01-12T10:00:00Z\n2/1/2023,noon\n",
+            &options,
+        )
+        .unwrap();
+        let sorted = table
+            .sort(
+                &[crate::sql::ast::OrderingTerm {
+                    expression: crate::sql::ast::Expression::Column("day".to_string()),
+                    descending: false,
+                }],
+                &crate::database::Database::new(),
+            )
+            .unwrap();
+        let days: Vec<String> = sorted.iter().map(|r| r.get(0).to_string()).collect();
+        assert_eq!(days, vec!["2023-02-01", "2024-01-12"]);
+        let at: Vec<String> = sorted.iter().map(|r| r.get(1).to_string()).collect();
+        assert_eq!(at, vec!["noon", "2024-01-12 10:00:00"]);
+    }
+
+    #[test]
+    fn test_column_types() {
+        let options = CsvOptions::new()
+            .separator(",")
+            .column_type("zip", "TEXT")
+            .column_type("amount", "NUMERIC")
+            .column_type("ratio", "REAL");
+        let table =
+            Table::from_csv_with_options("zip,amount,ratio\n007,1e3,2\n", &options).unwrap();
+        let record = table.iter().next().unwrap();
+        assert_eq!(record.get(0), &Value::from_text("007"));
+        assert_eq!(record.get(1), &Value::from(1000));
+        assert_eq!(record.get(1).as_i64(), Some(1000));
+        assert_eq!(
+            record.get(2).datatype().unwrap(),
+            crate::value::Datatype::Float
+        );
+        // a type for a column that is not in the header is an error
+        let options = CsvOptions::new().column_type("zip_code", "TEXT");
+        let error = Table::from_csv_with_options("zip,amount\n007,1\n", &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "declared column type for a column that is not in the header: zip_code"
+        );
+        let options = CsvOptions::new().unique(&["zip_code"]);
+        assert!(Table::from_csv_with_options("zip,amount\n007,1\n", &options).is_err());
+    }
+
+    #[test]
+    fn test_booleans() {
+        let csv = "flag\nYes\nfalse\nj\nmaybe\n";
+        let table = Table::from_csv(csv, Some(","));
+        let flags: Vec<Option<bool>> = table.iter().map(|r| r.get(0).as_bool()).collect();
+        assert_eq!(
+            flags,
+            vec![Some(true), Some(false), Some(false), Some(false)]
+        );
+        assert!(table.iter().nth(2).unwrap().get(0).is_text());
+
+        let options = CsvOptions::new().separator(",").booleans(&["j"], &["n"]);
+        let table = Table::from_csv_with_options(csv, &options).unwrap();
+        let flags: Vec<Option<bool>> = table.iter().map(|r| r.get(0).as_bool()).collect();
+        assert_eq!(
+            flags,
+            vec![Some(false), Some(false), Some(true), Some(false)]
+        );
+        assert!(table.iter().next().unwrap().get(0).is_text());
+    }
+
+    #[test]
+    fn test_constraints() {
+        let options = CsvOptions::new()
+            .separator(",")
+            .primary_key(&["id"])
+            .unique(&["email"])
+            .not_null("email")
+            .check("amount >= 0");
+        let csv = "id,email,amount\n1,a@x,10\n1,b@x,5\n\n2,a@x,3\n3\n4,d@x,-2\n5,e@x\n";
+        let (table, violations) = Table::from_csv_checked(csv, &options).unwrap();
+        let lines: Vec<(usize, &str)> = violations
+            .iter()
+            .map(|v| (v.line, v.message.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (3, "UNIQUE constraint failed: id"),
+                (5, "UNIQUE constraint failed: email"),
+                (6, "NOT NULL constraint failed: email"),
+                (7, "CHECK constraint failed: amount >= 0"),
+            ]
+        );
+        // missing values are NULL, a NULL amount passes the check
+        let ids: Vec<String> = table.iter().map(|r| r.get(0).to_string()).collect();
+        assert_eq!(ids, vec!["1", "5"]);
+        assert_eq!(
+            Table::from_csv_with_options(csv, &options)
+                .unwrap()
+                .iter()
+                .count(),
+            2
+        );
+        assert!(Table::from_csv_checked(csv, &CsvOptions::new().unique(&["x"])).is_err());
+        assert!(Table::from_csv_checked(csv, &CsvOptions::new().check("amount >")).is_err());
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let csv = "a,b,c\n1,2\n1,2,3,4\n5,6,7\n";
+        let (table, violations) = Table::from_csv_checked(csv, &CsvOptions::new()).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].line, 3);
+        let mut database = crate::database::Database::new();
+        database.add_table("t", table);
+        let result = database.execute("select a, c from t").unwrap();
+        let c: Vec<String> = result.iter().map(|r| r.get(1).to_string()).collect();
+        assert_eq!(c, vec!["NULL", "7"]);
+        assert!(result.iter().next().unwrap().get(5).is_null());
+    }
+
+    #[test]
+    fn test_field_larger_than_page() {
+        let csv = format!("id,text\n1,a\n2,{}\n3,c\n", "x".repeat(5000));
+        let (table, violations) = Table::from_csv_checked(csv, &CsvOptions::new()).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].line, 3);
+        let ids: Vec<String> = table.iter().map(|r| r.get(0).to_string()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
     }
 }
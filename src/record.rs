@@ -1,9 +1,14 @@
+use crate::datetime;
+use crate::decimal;
 use crate::value::Value;
 use crate::varint;
-use byteorder::{BigEndian, ByteOrder};
 use std::ops::Add;
+use std::sync::LazyLock;
 
-#[derive(Debug, Clone)]
+/// the value past the end of a record
+static NULL: LazyLock<Value> = LazyLock::new(Value::null);
+
+#[derive(Debug, Clone, Default)]
 pub struct Record {
     pub rowid: u64,
     pub(crate) values: Vec<Value>,
@@ -17,8 +22,8 @@ impl Record {
     }
 
     /// returns the length of the internal byte representation
-    pub fn bytes_len(&self) -> u16 {
-        let record_length: u16 = self.values.iter().map(Value::bytes_len).sum();
+    pub fn bytes_len(&self) -> usize {
+        let record_length: usize = self.values.iter().map(Value::bytes_len).sum();
         record_length + 1
     }
 
@@ -27,9 +32,9 @@ impl Record {
         self.values.push(value.into());
     }
 
-    /// gets the value at the column index of the record
+    /// gets the value at the column index of the record, NULL past its end
     pub fn get(&self, index: usize) -> &Value {
-        self.values.get(index).unwrap() //TODO
+        self.values.get(index).unwrap_or(&NULL)
     }
 }
 
@@ -49,12 +54,15 @@ impl From<Record> for Vec<u8> {
     /// returns the byte reprsentation of the record
     /// which will be stored physically in the page (and some day on disk)
     fn from(mut record: Record) -> Vec<u8> {
-        let record_length = record.bytes_len(); // len of all the values
-        let mut length_bytes = varint::write(u64::from(record_length)); // the length of the above in bytes representation
         let mut rowid_bytes = varint::write(record.rowid); // the bytes representation of the rowid
+        let length_of_encoded_column_types: usize =
+            record.values.iter().map(|v| v.datatype_bytes.len()).sum();
+        let mut types_length_bytes = varint::write((length_of_encoded_column_types + 1) as u64);
+        // the length of everything after the length itself: rowid, types length and the values
+        let record_length = rowid_bytes.len() + types_length_bytes.len() + record.bytes_len() - 1;
+        let mut length_bytes = varint::write(record_length as u64); // the length of the above in bytes representation
 
-        let mut buffer =
-            Vec::with_capacity(length_bytes.len() + rowid_bytes.len() + record_length as usize);
+        let mut buffer = Vec::with_capacity(length_bytes.len() + record_length);
         buffer.append(&mut length_bytes);
         buffer.append(&mut rowid_bytes);
 
@@ -63,11 +71,7 @@ impl From<Record> for Vec<u8> {
         // -> after the record header, first all types (text, int, float etc) for the record are written
         // after that come the values themselves
         // so decoders first read this value to know how many types there are (how many bytes to read to decode the type bytes)
-        let length_of_encoded_column_types: usize =
-            record.values.iter().map(|v| v.datatype_bytes.len()).sum();
-        buffer.append(&mut varint::write(
-            (length_of_encoded_column_types + 1) as u64,
-        ));
+        buffer.append(&mut types_length_bytes);
 
         //write all types
         for v in &mut record.values {
@@ -86,9 +90,9 @@ impl From<Record> for Vec<u8> {
 /// tuple (len, byte buffer)
 /// len is the length that was read from the bytes before calling this
 // needs improving, for clarity get rid of the tuple
-impl Into<Record> for (u64, &[u8]) {
-    fn into(self) -> Record {
-        let (_, data) = self;
+impl From<(u64, &[u8])> for Record {
+    fn from(value: (u64, &[u8])) -> Record {
+        let (_, data) = value;
         let mut offset = 0;
         let (inc, rowid) = varint::read(data);
         offset += inc;
@@ -115,7 +119,7 @@ impl Into<Record> for (u64, &[u8]) {
                     offset += len;
                 }
                 12.. if dt % 2 == 0 => {
-                    let len = ((dt >> 1) - 12) as usize;
+                    let len = ((dt - 12) >> 1) as usize;
                     values.push(Value::new(dt, data[offset..offset + len].to_vec()));
                     offset += len;
                 }
@@ -132,6 +136,16 @@ impl Into<Record> for (u64, &[u8]) {
                 0 => {
                     values.push(Value::null());
                 }
+                decimal::DECIMAL => {
+                    let len = 2 + data[offset + 1] as usize;
+                    values.push(Value::new(dt, data[offset..offset + len].to_vec()));
+                    offset += len;
+                }
+                datetime::TEMPORAL => {
+                    let len = datetime::encoded_len(data[offset]);
+                    values.push(Value::new(dt, data[offset..offset + len].to_vec()));
+                    offset += len;
+                }
                 _ => panic!("unknown datatype"),
             }
         }
@@ -147,12 +161,3 @@ fn read_int_len(datatype: u64) -> usize {
         _ => datatype as usize,
     }
 }
-
-impl Default for Record {
-    fn default() -> Self {
-        Self {
-            rowid: 0,
-            values: vec![],
-        }
-    }
-}
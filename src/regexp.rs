@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+use regex::Regex;
+
+use crate::functions::Registry;
+use crate::json::Json;
+use crate::value::Value;
+use crate::vm::eval::number;
+
+/// compiled patterns by pattern text, so that a pattern is compiled once per statement
+/// instead of for every record
+#[derive(Debug, Default)]
+pub(crate) struct RegexCache {
+    patterns: RefCell<HashMap<String, Regex>>,
+}
+
+impl RegexCache {
+    fn get(&self, pattern: &str) -> anyhow::Result<Regex> {
+        if let Some(regex) = self.patterns.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern)
+            .map_err(|e| anyhow!("Invalid regular expression '{}': {}", pattern, e))?;
+        self.patterns
+            .borrow_mut()
+            .insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    pub(crate) fn clear(&self) {
+        self.patterns.borrow_mut().clear();
+    }
+}
+
+/// adds regexp_like, regexp_extract, regexp_replace and regexp_split, which use the cache
+/// patterns use the syntax of the regex crate, flags are i (case insensitive),
+/// c (case sensitive), m (multi line), s (. matches newline) and x (ignore whitespace)
+pub(crate) fn register(registry: &mut Registry, cache: &Rc<RegexCache>) {
+    let cache_for = || Rc::clone(cache);
+
+    let cache = cache_for();
+    registry.add("regexp_like", (2, Some(3)), true, move |args| {
+        if any_null(args) {
+            return Ok(Value::null());
+        }
+        let regex = compile(&cache, &args[1], args.get(2))?;
+        Ok(Value::from_bool(regex.is_match(&args[0].to_string())))
+    });
+
+    // regexp_extract(x, pattern [, group]): the group (number or name) of the first match,
+    // NULL when there is no match
+    let cache = cache_for();
+    registry.add("regexp_extract", (2, Some(3)), true, move |args| {
+        if any_null(args) {
+            return Ok(Value::null());
+        }
+        let regex = compile(&cache, &args[1], None)?;
+        let text = args[0].to_string();
+        let Some(captures) = regex.captures(&text) else {
+            return Ok(Value::null());
+        };
+        let group = match args.get(2) {
+            None => captures.get(0),
+            Some(group) if group.is_text() => captures.name(&group.to_string()),
+            Some(group) => {
+                let index = number(group).map_or(0, |n| n.as_f64() as usize);
+                if index >= regex.captures_len() {
+                    return Err(anyhow!("regexp_extract: there is no group {}", index));
+                }
+                captures.get(index)
+            }
+        };
+        Ok(group.map_or_else(Value::null, |m| Value::from_text(m.as_str())))
+    });
+
+    // regexp_replace(x, pattern, replacement [, flags]) replaces all matches
+    // the replacement refers to groups with $1, ${name} or \1
+    let cache = cache_for();
+    registry.add("regexp_replace", (3, Some(4)), true, move |args| {
+        if any_null(args) {
+            return Ok(Value::null());
+        }
+        let regex = compile(&cache, &args[1], args.get(3))?;
+        let replacement = backreferences(&args[2].to_string());
+        let text = args[0].to_string();
+        Ok(Value::from_text(
+            regex.replace_all(&text, replacement.as_str()).into_owned(),
+        ))
+    });
+
+    // regexp_split(x, pattern): all parts, as a json array of strings
+    // regexp_split(x, pattern, n): the nth part (starting at 1, negative from the end),
+    // like split_part. Empty when there is no such part
+    let cache = cache_for();
+    registry.add("regexp_split", (2, Some(3)), true, move |args| {
+        if any_null(args) {
+            return Ok(Value::null());
+        }
+        let regex = compile(&cache, &args[1], None)?;
+        let text = args[0].to_string();
+        let parts: Vec<&str> = regex.split(&text).collect();
+        let Some(n) = args.get(2) else {
+            let parts = parts.iter().map(|part| Json::String(part.to_string()));
+            return Ok(Value::from_text(Json::Array(parts.collect()).to_string()));
+        };
+        let n = number(n).map_or(0, |n| n.as_f64() as i64);
+        let index = match n {
+            0 => return Err(anyhow!("Field position must not be zero")),
+            1.. => n - 1,
+            _ => parts.len() as i64 + n,
+        };
+        let part = usize::try_from(index)
+            .ok()
+            .and_then(|index| parts.get(index))
+            .copied()
+            .unwrap_or_default();
+        Ok(Value::from_text(part))
+    });
+}
+
+fn any_null(args: &[Value]) -> bool {
+    args.iter().any(Value::is_null)
+}
+
+/// the pattern with the flags as inline flags: (?i)pattern
+fn compile(cache: &RegexCache, pattern: &Value, flags: Option<&Value>) -> anyhow::Result<Regex> {
+    let pattern = pattern.to_string();
+    let Some(flags) = flags else {
+        return cache.get(&pattern);
+    };
+    let mut inline = String::new();
+    for flag in flags.to_string().chars() {
+        match flag {
+            'i' | 'm' | 's' | 'x' => inline.push(flag),
+            'c' => inline.retain(|f| f != 'i'),
+            _ => return Err(anyhow!("Invalid regular expression flag '{}'", flag)),
+        }
+    }
+    if inline.is_empty() {
+        cache.get(&pattern)
+    } else {
+        cache.get(&format!("(?{}){}", inline, pattern))
+    }
+}
+
+/// \1 => ${1}
+fn backreferences(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(d)) if d.is_ascii_digit() => {
+                result.push_str(&format!("${{{}}}", d));
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(name: &str, args: &[&str]) -> String {
+        let args: Vec<Value> = args.iter().map(|a| Value::from(*a)).collect();
+        Registry::default().call(name, &args).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(call("regexp_like", &["Abc", "^a"]), "0");
+        assert_eq!(call("regexp_like", &["Abc", "^a", "i"]), "1");
+        assert_eq!(
+            call(
+                "regexp_extract",
+                &["order 66 of 99", r"(\d+) of (\d+)", "2"]
+            ),
+            "99"
+        );
+        assert_eq!(
+            call("regexp_extract", &["2024-01", r"(?P<year>\d{4})", "year"]),
+            "2024"
+        );
+        assert_eq!(call("regexp_extract", &["abc", r"\d"]), "NULL");
+        assert_eq!(
+            call("regexp_replace", &["a1b22c", r"(\d+)", "<\\1>"]),
+            "a<1>b<22>c"
+        );
+        assert_eq!(call("regexp_split", &["a, b;c", r"[,;]\s*", "-1"]), "c");
+        assert_eq!(
+            call("regexp_split", &["a, b;c", r"[,;]\s*"]),
+            r#"["a","b","c"]"#
+        );
+        let error = Registry::default()
+            .call("regexp_like", &[Value::from("a"), Value::from("(")])
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Invalid regular expression"));
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = RegexCache::default();
+        compile(&cache, &Value::from("a+"), None).unwrap();
+        compile(&cache, &Value::from("a+"), Some(&Value::from("i"))).unwrap();
+        compile(&cache, &Value::from("a+"), None).unwrap();
+        assert_eq!(cache.patterns.borrow().len(), 2);
+        cache.clear();
+        assert!(cache.patterns.borrow().is_empty());
+    }
+}
@@ -0,0 +1,795 @@
+use std::fmt::Display;
+
+use crate::value::{Datatype, Value};
+
+use super::tokens::TokenType;
+
+/// a parsed sql statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select(Box<Select>), // boxed, selects are large
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<String>,
+    },
+    // insert into table [(column, ..)] values (..), .. | select ..
+    Insert {
+        table: String,
+        columns: Vec<String>, // empty for all columns
+        source: InsertSource,
+    },
+    // update table set column = expression, .. [where expression]
+    Update {
+        table: String,
+        assignments: Vec<(String, Expression)>,
+        where_clause: Option<Expression>,
+    },
+    // delete from table [where expression]
+    Delete {
+        table: String,
+        where_clause: Option<Expression>,
+    },
+    // create table [if not exists] name (column definition, .. [, table constraint, ..]) | as select ..
+    CreateTable {
+        name: String,
+        if_not_exists: bool,
+        columns: Vec<ColumnDefinition>,
+        constraints: Vec<Constraint>,
+        select: Option<Box<Select>>,
+    },
+    // drop table [if exists] name
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    AlterTable {
+        name: String,
+        action: AlterAction,
+    },
+    Begin,
+    Commit,
+    Rollback(Option<String>), // rollback [to savepoint name]
+    Savepoint(String),
+    Release(String),
+    // pragma name [= value | (value)]
+    Pragma {
+        name: String,
+        argument: Option<String>,
+    },
+}
+
+/// name [type name] [collate collation] {column constraint}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub type_name: Option<String>,
+    pub collation: Option<String>,
+    pub constraints: Vec<Constraint>, // on this column
+}
+
+/// a table constraint, column constraints name their column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    NotNull(String),         // column not null
+    PrimaryKey(Vec<String>), // primary key (column, ..)
+    Unique(Vec<String>),     // unique (column, ..)
+    Check(Expression),       // check (expression)
+    // foreign key (column, ..) references parent [(column, ..)]
+    // without parent columns, the primary key of the parent
+    ForeignKey {
+        columns: Vec<String>,
+        parent: String,
+        parent_columns: Vec<String>,
+    },
+}
+
+/// what alter table does
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterAction {
+    AddColumn(ColumnDefinition),  // add [column] definition
+    DropColumn(String),           // drop [column] name
+    RenameColumn(String, String), // rename [column] name to new name
+    RenameTo(String),             // rename to new name
+}
+
+/// the rows to insert
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expression>>),
+    Select(Box<Select>),
+}
+
+/// [with ..] select [columns] from [table] where [expression] group by [..] having [expression] order by [..]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub with: Option<With>,
+    pub distinct: Distinct,
+    pub columns: Vec<ResultColumn>,
+    pub from: Option<TableReference>,
+    pub where_clause: Option<Expression>,
+    pub group_by: Vec<GroupingElement>,
+    pub having: Option<Expression>,
+    pub compound: Vec<(SetOperator, Box<Select>)>, // union, intersect or except, applied from left to right
+    pub order_by: Vec<OrderingTerm>,               // for a compound select: of the combined result
+    pub limit: Option<Expression>,                 // limit n or fetch first n rows only
+    pub offset: Option<Expression>,
+}
+
+/// select [all] | distinct | distinct on (expression, ..)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Distinct {
+    #[default]
+    All,
+    Rows,
+    On(Vec<Expression>), // the first row for every value of the expressions, in order by order
+}
+
+/// combines the rows of two selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperator {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+impl Display for SetOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetOperator::Union => write!(f, "union"),
+            SetOperator::UnionAll => write!(f, "union all"),
+            SetOperator::Intersect => write!(f, "intersect"),
+            SetOperator::Except => write!(f, "except"),
+        }
+    }
+}
+
+/// with [recursive] name [(column, ..)] as (select ..), ..
+#[derive(Debug, Clone, PartialEq)]
+pub struct With {
+    pub recursive: bool,
+    pub tables: Vec<CommonTableExpression>,
+}
+
+/// a select that can be used as a table, by name, in the statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTableExpression {
+    pub name: String,
+    pub columns: Vec<String>, // the column names, instead of those of the select
+    pub select: Select,
+    pub step: Option<(Box<Select>, bool)>, // union [all] select, which can refer to name when recursive
+}
+
+impl Select {
+    /// true when the select, or one of its subqueries, reads from the table
+    pub fn references(&self, table: &str) -> bool {
+        let mut found = matches!(&self.from, Some(TableReference::Table(name, _)) if name == table);
+        for expression in self.expressions() {
+            expression.walk(&mut |e| match e {
+                Expression::Subquery(select)
+                | Expression::Exists(select)
+                | Expression::InSelect(_, select, _) => found |= select.references(table),
+                _ => {}
+            });
+        }
+        found
+            || self.compound.iter().any(|(_, part)| part.references(table))
+            || self.with.iter().flat_map(|with| &with.tables).any(|cte| {
+                cte.select.references(table)
+                    || cte
+                        .step
+                        .as_ref()
+                        .is_some_and(|(step, _)| step.references(table))
+            })
+    }
+
+    /// all expressions in the select, not those in its subqueries or compound parts
+    pub fn expressions(&self) -> Vec<&Expression> {
+        let mut expressions = vec![];
+        if let Distinct::On(list) = &self.distinct {
+            expressions.extend(list);
+        }
+        for column in &self.columns {
+            if let ResultColumn::Expression(expression, _) = column {
+                expressions.push(expression);
+            }
+        }
+        if let Some(TableReference::Function(_, args, _)) = &self.from {
+            expressions.extend(args);
+        }
+        expressions.extend(&self.where_clause);
+        for element in &self.group_by {
+            match element {
+                GroupingElement::Expression(expression) => expressions.push(expression),
+                GroupingElement::Rollup(list) | GroupingElement::Cube(list) => {
+                    expressions.extend(list)
+                }
+                GroupingElement::GroupingSets(sets) => expressions.extend(sets.iter().flatten()),
+            }
+        }
+        expressions.extend(&self.having);
+        expressions.extend(self.order_by.iter().map(|term| &term.expression));
+        expressions
+    }
+
+    /// returns a copy of the select with f applied to all its expressions, see Expression::transform
+    /// compound parts are copied as they are
+    pub fn transform(&self, f: &impl Fn(&Expression) -> Option<Expression>) -> Select {
+        let list = |list: &[Expression]| list.iter().map(|e| e.transform(f)).collect();
+        Select {
+            with: self.with.clone(),
+            distinct: match &self.distinct {
+                Distinct::On(expressions) => Distinct::On(list(expressions)),
+                distinct => distinct.clone(),
+            },
+            columns: self
+                .columns
+                .iter()
+                .map(|column| match column {
+                    ResultColumn::Star => ResultColumn::Star,
+                    ResultColumn::Expression(expression, alias) => {
+                        ResultColumn::Expression(expression.transform(f), alias.clone())
+                    }
+                })
+                .collect(),
+            from: match &self.from {
+                Some(TableReference::Function(name, args, alias)) => Some(
+                    TableReference::Function(name.clone(), list(args), alias.clone()),
+                ),
+                from => from.clone(),
+            },
+            where_clause: self.where_clause.as_ref().map(|e| e.transform(f)),
+            group_by: self
+                .group_by
+                .iter()
+                .map(|element| match element {
+                    GroupingElement::Expression(e) => GroupingElement::Expression(e.transform(f)),
+                    GroupingElement::Rollup(l) => GroupingElement::Rollup(list(l)),
+                    GroupingElement::Cube(l) => GroupingElement::Cube(list(l)),
+                    GroupingElement::GroupingSets(sets) => {
+                        GroupingElement::GroupingSets(sets.iter().map(|set| list(set)).collect())
+                    }
+                })
+                .collect(),
+            having: self.having.as_ref().map(|e| e.transform(f)),
+            compound: self.compound.clone(),
+            order_by: self
+                .order_by
+                .iter()
+                .map(|term| OrderingTerm {
+                    expression: term.expression.transform(f),
+                    descending: term.descending,
+                })
+                .collect(),
+            limit: self.limit.as_ref().map(|e| e.transform(f)),
+            offset: self.offset.as_ref().map(|e| e.transform(f)),
+        }
+    }
+}
+
+impl Display for Select {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::Expression(expression, Some(alias)) => {
+                    format!("{} as {}", expression, alias)
+                }
+                column => column.name(),
+            })
+            .collect::<Vec<String>>();
+        if let Some(with) = &self.with {
+            write!(f, "{} ", with)?;
+        }
+        write!(f, "select ")?;
+        match &self.distinct {
+            Distinct::All => {}
+            Distinct::Rows => write!(f, "distinct ")?,
+            Distinct::On(expressions) => write!(f, "distinct on ({}) ", join(expressions))?,
+        }
+        write!(f, "{}", columns.join(", "))?;
+        if let Some(from) = &self.from {
+            write!(f, " from {}", from)?;
+        }
+        if let Some(where_clause) = &self.where_clause {
+            write!(f, " where {}", where_clause)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " group by {}", join(&self.group_by))?;
+        }
+        if let Some(having) = &self.having {
+            write!(f, " having {}", having)?;
+        }
+        for (operator, part) in &self.compound {
+            write!(f, " {} {}", operator, part)?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " order by {}", join(&self.order_by))?;
+        }
+        if let Some(limit) = &self.limit {
+            write!(f, " limit {}", limit)?;
+        }
+        if let Some(offset) = &self.offset {
+            write!(f, " offset {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for With {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "with ")?;
+        if self.recursive {
+            write!(f, "recursive ")?;
+        }
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", table.name)?;
+            if !table.columns.is_empty() {
+                write!(f, "({})", table.columns.join(", "))?;
+            }
+            write!(f, " as ({}", table.select)?;
+            match &table.step {
+                Some((step, true)) => write!(f, " union all {})", step)?,
+                Some((step, false)) => write!(f, " union {})", step)?,
+                None => write!(f, ")")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// what a select reads from: a table, or a table-valued function like json_each(..),
+/// with an optional alias
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableReference {
+    Table(String, Option<String>),
+    Function(String, Vec<Expression>, Option<String>),
+}
+
+impl TableReference {
+    /// the name by which columns can be qualified: the alias, or the table name
+    pub fn qualifier(&self) -> Option<&str> {
+        match self {
+            TableReference::Table(_, Some(alias)) | TableReference::Function(_, _, Some(alias)) => {
+                Some(alias)
+            }
+            TableReference::Table(name, None) => Some(name),
+            TableReference::Function(..) => None,
+        }
+    }
+}
+
+impl Display for TableReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableReference::Table(name, _)
+                if name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                write!(f, "{}", name)
+            }
+            TableReference::Table(name, _) => write!(f, "'{}'", name),
+            TableReference::Function(name, args, _) => write!(f, "{}({})", name, join(args)),
+        }?;
+        match self {
+            TableReference::Table(_, Some(alias)) | TableReference::Function(_, _, Some(alias)) => {
+                write!(f, " as {}", alias)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// an item in the select list
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultColumn {
+    Star,
+    Expression(Expression, Option<String>), // expression with optional alias
+}
+
+impl ResultColumn {
+    /// the column name in the result table
+    pub fn name(&self) -> String {
+        match self {
+            ResultColumn::Star => "*".to_string(),
+            ResultColumn::Expression(_, Some(alias)) => alias.clone(),
+            ResultColumn::Expression(expression, None) => expression.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expression: Expression,
+    pub descending: bool,
+}
+
+impl Display for OrderingTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.descending {
+            write!(f, "{} desc", self.expression)
+        } else {
+            write!(f, "{}", self.expression)
+        }
+    }
+}
+
+/// an element in the group by clause
+/// 'group by a, rollup(b, c)' has two elements
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupingElement {
+    Expression(Expression),
+    Rollup(Vec<Expression>),
+    Cube(Vec<Expression>),
+    GroupingSets(Vec<Vec<Expression>>),
+}
+
+impl GroupingElement {
+    /// returns the grouping sets this element stands for
+    /// rollup(a, b) => (a, b), (a), ()
+    /// cube(a, b) => (a, b), (a), (b), ()
+    pub fn grouping_sets(&self) -> Vec<Vec<Expression>> {
+        match self {
+            GroupingElement::Expression(expression) => vec![vec![expression.clone()]],
+            GroupingElement::Rollup(expressions) => (0..=expressions.len())
+                .rev()
+                .map(|len| expressions[..len].to_vec())
+                .collect(),
+            GroupingElement::Cube(expressions) => {
+                let n = expressions.len();
+                (0..1usize << n)
+                    .rev()
+                    .map(|mask| {
+                        expressions
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| mask & (1 << (n - i - 1)) != 0)
+                            .map(|(_, e)| e.clone())
+                            .collect()
+                    })
+                    .collect()
+            }
+            GroupingElement::GroupingSets(sets) => sets.clone(),
+        }
+    }
+}
+
+impl Display for GroupingElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupingElement::Expression(expression) => write!(f, "{}", expression),
+            GroupingElement::Rollup(list) => write!(f, "rollup({})", join(list)),
+            GroupingElement::Cube(list) => write!(f, "cube({})", join(list)),
+            GroupingElement::GroupingSets(sets) => {
+                let sets = sets
+                    .iter()
+                    .map(|set| format!("({})", join(set)))
+                    .collect::<Vec<String>>();
+                write!(f, "grouping sets ({})", sets.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Column(String),
+    Unary(TokenType, Box<Expression>),
+    Binary(Box<Expression>, TokenType, Box<Expression>),
+    IsNull(Box<Expression>, bool),        // expression is [not] null
+    IsTruth(Box<Expression>, bool, bool), // expression is [not] true|false
+    Function(String, Vec<Expression>),
+    Collate(Box<Expression>, String),    // expression collate name
+    Cast(Box<Expression>, String, bool), // [try_]cast(expression as type name)
+    // case [operand] when .. then .. [else ..] end
+    Case(
+        Option<Box<Expression>>,
+        Vec<(Expression, Expression)>,
+        Option<Box<Expression>>,
+    ),
+    InList(Box<Expression>, Vec<Expression>, bool), // expression [not] in (expression, ..)
+    InSelect(Box<Expression>, Box<Select>, bool),   // expression [not] in (select ..)
+    Subquery(Box<Select>),                          // (select ..), the first value of the first row
+    Exists(Box<Select>),
+    Distinct(Box<Expression>), // as in count(distinct expression)
+    Star,                      // as in count(*)
+}
+
+impl Expression {
+    /// calls f for this expression and all its subexpressions
+    /// subqueries have their own scope, f is called for them but not for their expressions
+    pub fn walk(&self, f: &mut impl FnMut(&Expression)) {
+        f(self);
+        match self {
+            Expression::Unary(_, operand)
+            | Expression::IsNull(operand, _)
+            | Expression::IsTruth(operand, ..)
+            | Expression::Collate(operand, _)
+            | Expression::Cast(operand, ..)
+            | Expression::InSelect(operand, ..)
+            | Expression::Distinct(operand) => operand.walk(f),
+            Expression::Binary(left, _, right) => {
+                left.walk(f);
+                right.walk(f);
+            }
+            Expression::Function(_, args) => args.iter().for_each(|a| a.walk(f)),
+            Expression::Case(operand, branches, otherwise) => {
+                for operand in operand.iter().chain(otherwise) {
+                    operand.walk(f);
+                }
+                for (when, then) in branches {
+                    when.walk(f);
+                    then.walk(f);
+                }
+            }
+            Expression::InList(operand, list, _) => {
+                operand.walk(f);
+                list.iter().for_each(|e| e.walk(f));
+            }
+            Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::Subquery(_)
+            | Expression::Exists(_)
+            | Expression::Star => {}
+        }
+    }
+
+    /// returns a copy of the expression, where every subexpression for which f returns Some
+    /// is replaced by the returned expression
+    pub fn transform(&self, f: &impl Fn(&Expression) -> Option<Expression>) -> Expression {
+        if let Some(replacement) = f(self) {
+            return replacement;
+        }
+        match self {
+            Expression::Unary(op, operand) => {
+                Expression::Unary(*op, Box::new(operand.transform(f)))
+            }
+            Expression::IsNull(operand, negated) => {
+                Expression::IsNull(Box::new(operand.transform(f)), *negated)
+            }
+            Expression::IsTruth(operand, wanted, negated) => {
+                Expression::IsTruth(Box::new(operand.transform(f)), *wanted, *negated)
+            }
+            Expression::Binary(left, op, right) => Expression::Binary(
+                Box::new(left.transform(f)),
+                *op,
+                Box::new(right.transform(f)),
+            ),
+            Expression::Function(name, args) => {
+                Expression::Function(name.clone(), args.iter().map(|a| a.transform(f)).collect())
+            }
+            Expression::Collate(operand, collation) => {
+                Expression::Collate(Box::new(operand.transform(f)), collation.clone())
+            }
+            Expression::Cast(operand, type_name, fallible) => {
+                Expression::Cast(Box::new(operand.transform(f)), type_name.clone(), *fallible)
+            }
+            Expression::Case(operand, branches, otherwise) => Expression::Case(
+                operand.as_ref().map(|e| Box::new(e.transform(f))),
+                branches
+                    .iter()
+                    .map(|(when, then)| (when.transform(f), then.transform(f)))
+                    .collect(),
+                otherwise.as_ref().map(|e| Box::new(e.transform(f))),
+            ),
+            Expression::InList(operand, list, negated) => Expression::InList(
+                Box::new(operand.transform(f)),
+                list.iter().map(|e| e.transform(f)).collect(),
+                *negated,
+            ),
+            Expression::InSelect(operand, select, negated) => {
+                Expression::InSelect(Box::new(operand.transform(f)), select.clone(), *negated)
+            }
+            Expression::Distinct(operand) => Expression::Distinct(Box::new(operand.transform(f))),
+            Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::Subquery(_)
+            | Expression::Exists(_)
+            | Expression::Star => self.clone(),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Literal(value) => match value.datatype() {
+                Ok(Datatype::Text) => write!(f, "'{}'", value),
+                Ok(Datatype::Date) => write!(f, "date '{}'", value),
+                Ok(Datatype::Time) => write!(f, "time '{}'", value),
+                Ok(Datatype::Timestamp) => write!(f, "timestamp '{}'", value),
+                Ok(Datatype::Interval) => write!(f, "interval '{}'", value),
+                _ => write!(f, "{}", value),
+            },
+            Expression::Column(name) => write!(f, "{}", name),
+            Expression::Unary(TokenType::Not, operand) => write!(f, "not {}", operand),
+            Expression::Unary(_, operand) => match operand.as_ref() {
+                Expression::Binary(..) => write!(f, "-({})", operand),
+                _ => write!(f, "-{}", operand),
+            },
+            Expression::Binary(left, op, right) => {
+                // parenthesize operands that bind less tightly than op, so that the text is unambiguous
+                let precedence = precedence(*op);
+                if binding(left) < precedence {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", operator(*op))?;
+                if binding(right) <= precedence {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+            Expression::IsNull(operand, false) => write!(f, "{} is null", operand),
+            Expression::IsNull(operand, true) => write!(f, "{} is not null", operand),
+            Expression::IsTruth(operand, wanted, negated) => write!(
+                f,
+                "{} is {}{}",
+                operand,
+                if *negated { "not " } else { "" },
+                wanted
+            ),
+            Expression::Function(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{}({})", name, args)
+            }
+            Expression::Collate(operand, collation) => match operand.as_ref() {
+                Expression::Literal(_)
+                | Expression::Column(_)
+                | Expression::Function(..)
+                | Expression::Cast(..) => {
+                    write!(f, "{} collate {}", operand, collation)
+                }
+                _ => write!(f, "({}) collate {}", operand, collation),
+            },
+            Expression::Cast(operand, type_name, true) => {
+                write!(f, "try_cast({} as {})", operand, type_name)
+            }
+            Expression::Cast(operand, type_name, false) => {
+                write!(f, "cast({} as {})", operand, type_name)
+            }
+            Expression::Case(operand, branches, otherwise) => {
+                write!(f, "case")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (when, then) in branches {
+                    write!(f, " when {} then {}", when, then)?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, " else {}", otherwise)?;
+                }
+                write!(f, " end")
+            }
+            Expression::InList(operand, list, negated) => {
+                let list = list
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{} {}in ({})", operand, not(*negated), list)
+            }
+            Expression::InSelect(operand, select, negated) => {
+                write!(f, "{} {}in ({})", operand, not(*negated), select)
+            }
+            Expression::Subquery(select) => write!(f, "({})", select),
+            Expression::Exists(select) => write!(f, "exists ({})", select),
+            Expression::Distinct(operand) => write!(f, "distinct {}", operand),
+            Expression::Star => write!(f, "*"),
+        }
+    }
+}
+
+fn not(negated: bool) -> &'static str {
+    if negated {
+        "not "
+    } else {
+        ""
+    }
+}
+
+fn binding(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Binary(_, op, _) => precedence(*op),
+        Expression::Unary(TokenType::Not, _) => 3,
+        Expression::IsNull(..)
+        | Expression::IsTruth(..)
+        | Expression::InList(..)
+        | Expression::InSelect(..) => 4,
+        _ => u8::MAX,
+    }
+}
+
+fn precedence(tokentype: TokenType) -> u8 {
+    match tokentype {
+        TokenType::Or => 1,
+        TokenType::And => 2,
+        TokenType::Plus | TokenType::Minus => 5,
+        TokenType::Star | TokenType::Slash => 6,
+        TokenType::Arrow | TokenType::DoubleArrow => 7,
+        _ => 4, // comparisons
+    }
+}
+
+fn operator(tokentype: TokenType) -> &'static str {
+    match tokentype {
+        TokenType::Plus => "+",
+        TokenType::Minus => "-",
+        TokenType::Star => "*",
+        TokenType::Slash => "/",
+        TokenType::Equals => "=",
+        TokenType::BangEquals => "!=",
+        TokenType::Unequal => "<>",
+        TokenType::Less => "<",
+        TokenType::LessEqual => "<=",
+        TokenType::Greater => ">",
+        TokenType::GreaterEqual => ">=",
+        TokenType::And => "and",
+        TokenType::Or => "or",
+        TokenType::Regexp => "regexp",
+        TokenType::Arrow => "->",
+        TokenType::DoubleArrow => "->>",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn col(name: &str) -> Expression {
+        Expression::Column(name.to_string())
+    }
+
+    #[test]
+    fn test_display_parenthesizes() {
+        let sum = Expression::Binary(Box::new(col("a")), TokenType::Plus, Box::new(col("b")));
+        let product =
+            Expression::Binary(Box::new(sum.clone()), TokenType::Star, Box::new(col("c")));
+        assert_eq!(product.to_string(), "(a + b) * c");
+        let difference = Expression::Binary(Box::new(col("c")), TokenType::Minus, Box::new(sum));
+        assert_eq!(difference.to_string(), "c - (a + b)");
+    }
+
+    #[test]
+    fn test_rollup_sets() {
+        let rollup = GroupingElement::Rollup(vec![col("a"), col("b")]);
+        assert_eq!(
+            rollup.grouping_sets(),
+            vec![vec![col("a"), col("b")], vec![col("a")], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_cube_sets() {
+        let cube = GroupingElement::Cube(vec![col("a"), col("b")]);
+        assert_eq!(
+            cube.grouping_sets(),
+            vec![
+                vec![col("a"), col("b")],
+                vec![col("a")],
+                vec![col("b")],
+                vec![]
+            ]
+        );
+    }
+}
@@ -1,2 +1,4 @@
+pub mod ast;
+pub mod parser;
 pub mod scanner;
 pub mod tokens;
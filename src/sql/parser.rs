@@ -0,0 +1,1412 @@
+use anyhow::anyhow;
+
+use crate::datetime::{self, Temporal};
+use crate::value::Value;
+
+use super::ast::{
+    AlterAction, ColumnDefinition, CommonTableExpression, Constraint, Distinct, Expression,
+    GroupingElement, InsertSource, OrderingTerm, ResultColumn, Select, SetOperator, Statement,
+    TableReference, With,
+};
+use super::scanner;
+use super::tokens::{Token, TokenType};
+
+/// parses a single sql statement
+pub fn parse(sql: &str) -> anyhow::Result<Statement> {
+    let mut parser = Parser::new(scanner::parse(sql)?);
+    let statement = parser.statement()?;
+    parser.match_token(TokenType::Semicolon);
+    if !parser.is_at_end() {
+        return Err(anyhow!(
+            "Unexpected '{}' after end of statement",
+            parser.peek().lexeme
+        ));
+    }
+    Ok(statement)
+}
+
+/// parses a standalone expression, eg for use in the Table api
+pub fn parse_expression(expression: &str) -> anyhow::Result<Expression> {
+    let mut parser = Parser::new(scanner::parse(expression)?);
+    let expression = parser.expression()?;
+    if !parser.is_at_end() {
+        return Err(anyhow!(
+            "Unexpected '{}' after expression",
+            parser.peek().lexeme
+        ));
+    }
+    Ok(expression)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, current: 0 }
+    }
+
+    fn statement(&mut self) -> anyhow::Result<Statement> {
+        if self.check(TokenType::Select) || self.check(TokenType::With) {
+            Ok(Statement::Select(Box::new(self.query()?)))
+        } else if self.match_token(TokenType::Insert) {
+            self.insert()
+        } else if self.match_token(TokenType::Update) {
+            self.update()
+        } else if self.match_token(TokenType::Delete) {
+            self.consume(TokenType::From, "Expected 'from' after 'delete'")?;
+            let table = self.table_name()?;
+            Ok(Statement::Delete {
+                table,
+                where_clause: self.where_clause()?,
+            })
+        } else if self.match_token(TokenType::Create) {
+            if self.match_token(TokenType::Table) {
+                self.create_table()
+            } else {
+                self.consume(
+                    TokenType::Index,
+                    "Expected 'table' or 'index' after 'create'",
+                )?;
+                self.create_index()
+            }
+        } else if self.match_token(TokenType::Drop) {
+            self.consume(TokenType::Table, "Expected 'table' after 'drop'")?;
+            let if_exists = self.match_word("if");
+            if if_exists {
+                self.consume(TokenType::Exists, "Expected 'exists' after 'if'")?;
+            }
+            Ok(Statement::DropTable {
+                name: self.table_name()?,
+                if_exists,
+            })
+        } else if self.match_token(TokenType::Begin) {
+            self.match_word("transaction");
+            Ok(Statement::Begin)
+        } else if self.match_token(TokenType::Commit) || self.match_token(TokenType::End) {
+            self.match_word("transaction");
+            Ok(Statement::Commit)
+        } else if self.match_token(TokenType::Rollback) {
+            self.match_word("transaction");
+            if self.match_word("to") {
+                self.match_token(TokenType::Savepoint);
+                let name = self.consume(TokenType::Identifier, "Expected savepoint name")?;
+                Ok(Statement::Rollback(Some(name)))
+            } else {
+                Ok(Statement::Rollback(None))
+            }
+        } else if self.match_token(TokenType::Savepoint) {
+            let name = self.consume(TokenType::Identifier, "Expected savepoint name")?;
+            Ok(Statement::Savepoint(name))
+        } else if self.match_token(TokenType::Release) {
+            self.match_token(TokenType::Savepoint);
+            let name = self.consume(TokenType::Identifier, "Expected savepoint name")?;
+            Ok(Statement::Release(name))
+        } else if self.match_token(TokenType::Alter) {
+            self.consume(TokenType::Table, "Expected 'table' after 'alter'")?;
+            self.alter_table()
+        } else if self.match_word("pragma") {
+            let name = self.consume(TokenType::Identifier, "Expected pragma name")?;
+            let argument = if self.match_token(TokenType::Equals) {
+                Some(self.pragma_argument())
+            } else if self.match_token(TokenType::LeftParen) {
+                let argument = self.pragma_argument();
+                self.consume(TokenType::RightParen, "Expected ')' after pragma argument")?;
+                Some(argument)
+            } else {
+                None
+            };
+            Ok(Statement::Pragma { name, argument })
+        } else {
+            Err(anyhow!(
+                "Expected a statement, found '{}'",
+                self.peek().lexeme
+            ))
+        }
+    }
+
+    /// [with ..] select ..
+    fn query(&mut self) -> anyhow::Result<Select> {
+        let with = if self.match_token(TokenType::With) {
+            Some(self.with()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Select, "Expected 'select'")?;
+        let select = self.select()?;
+        Ok(Select { with, ..select })
+    }
+
+    /// [recursive] name [(column, ..)] as '(' select [union [all] select] ')', .. after with
+    fn with(&mut self) -> anyhow::Result<With> {
+        let recursive = self.match_token(TokenType::Recursive);
+        let mut tables = vec![];
+        loop {
+            let name = self.consume(TokenType::Identifier, "Expected table name after 'with'")?;
+            let mut columns = vec![];
+            if self.match_token(TokenType::LeftParen) {
+                loop {
+                    columns.push(self.consume(TokenType::Identifier, "Expected column name")?);
+                    if !self.match_token(TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expected ')' after column names")?;
+            }
+            self.consume(TokenType::As, "Expected 'as' after table name")?;
+            self.consume(TokenType::LeftParen, "Expected '(' after 'as'")?;
+            let mut select = self.query()?;
+            self.consume(TokenType::RightParen, "Expected ')' after select")?;
+            // recursive: the last union refers to the table itself
+            let mut step = None;
+            if let Some((operator @ (SetOperator::Union | SetOperator::UnionAll), last)) =
+                select.compound.last()
+            {
+                if recursive && last.references(&name) {
+                    step = Some((last.clone(), *operator == SetOperator::UnionAll));
+                    select.compound.pop();
+                }
+            }
+            tables.push(CommonTableExpression {
+                name,
+                columns,
+                select,
+                step,
+            });
+            if !self.match_token(TokenType::Comma) {
+                return Ok(With { recursive, tables });
+            }
+        }
+    }
+
+    /// select .. {union [all] | intersect | except select ..} [order by ..], after 'select'
+    fn select(&mut self) -> anyhow::Result<Select> {
+        let mut select = self.select_core()?;
+        loop {
+            let operator = if self.match_token(TokenType::Union) {
+                if self.match_token(TokenType::All) {
+                    SetOperator::UnionAll
+                } else {
+                    SetOperator::Union
+                }
+            } else if self.match_token(TokenType::Intersect) {
+                SetOperator::Intersect
+            } else if self.match_token(TokenType::Except) {
+                SetOperator::Except
+            } else {
+                break;
+            };
+            self.consume(
+                TokenType::Select,
+                &format!("Expected 'select' after '{}'", operator),
+            )?;
+            select
+                .compound
+                .push((operator, Box::new(self.select_core()?)));
+        }
+
+        if self.match_token(TokenType::Order) {
+            self.consume(TokenType::By, "Expected 'by' after 'order'")?;
+            loop {
+                let expression = self.expression()?;
+                let descending = if self.match_token(TokenType::Desc) {
+                    true
+                } else {
+                    self.match_token(TokenType::Asc);
+                    false
+                };
+                select.order_by.push(OrderingTerm {
+                    expression,
+                    descending,
+                });
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        if self.match_token(TokenType::Limit) {
+            select.limit = Some(self.expression()?);
+            if self.match_token(TokenType::Offset) {
+                select.offset = Some(self.expression()?);
+            }
+        } else {
+            if self.match_token(TokenType::Offset) {
+                select.offset = Some(self.expression()?);
+                let _ = self.match_word("row") || self.match_word("rows");
+            }
+            if self.match_token(TokenType::Fetch) {
+                select.limit = Some(self.fetch()?);
+            }
+        }
+        Ok(select)
+    }
+
+    /// fetch first|next [n] row|rows only, after 'fetch'
+    fn fetch(&mut self) -> anyhow::Result<Expression> {
+        if !self.match_word("first") && !self.match_word("next") {
+            return Err(anyhow!("Expected 'first' or 'next' after 'fetch'"));
+        }
+        let count = if self.check_word("row") || self.check_word("rows") {
+            Expression::Literal(Value::from_i64(1))
+        } else {
+            self.expression()?
+        };
+        if !self.match_word("row") && !self.match_word("rows") {
+            return Err(anyhow!("Expected 'rows'"));
+        }
+        if !self.match_word("only") {
+            return Err(anyhow!("Expected 'only' after 'rows'"));
+        }
+        Ok(count)
+    }
+
+    /// select [columns] from .. where .. group by .. having .., after 'select'
+    fn select_core(&mut self) -> anyhow::Result<Select> {
+        let distinct = if self.match_token(TokenType::Distinct) {
+            if self.match_token(TokenType::On) {
+                Distinct::On(self.parenthesized_list()?)
+            } else {
+                Distinct::Rows
+            }
+        } else {
+            self.match_token(TokenType::All);
+            Distinct::All
+        };
+        let columns = self.result_columns()?;
+
+        let from = if self.match_token(TokenType::From) {
+            Some(self.table_reference()?)
+        } else {
+            None
+        };
+
+        let where_clause = self.where_clause()?;
+
+        let mut group_by = vec![];
+        if self.match_token(TokenType::Group) {
+            self.consume(TokenType::By, "Expected 'by' after 'group'")?;
+            loop {
+                group_by.push(self.grouping_element()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let having = if self.match_token(TokenType::Having) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        Ok(Select {
+            with: None,
+            distinct,
+            columns,
+            from,
+            where_clause,
+            group_by,
+            having,
+            compound: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        })
+    }
+
+    /// create table [if not exists] name (column definition, ..) | as select .., after 'table'
+    fn create_table(&mut self) -> anyhow::Result<Statement> {
+        let if_not_exists = self.match_word("if");
+        if if_not_exists {
+            self.consume(TokenType::Not, "Expected 'not' after 'if'")?;
+            self.consume(TokenType::Exists, "Expected 'exists' after 'not'")?;
+        }
+        let name = self.table_name()?;
+        let mut columns = vec![];
+        let mut constraints = vec![];
+        let mut select = None;
+        if self.match_token(TokenType::As) {
+            select = Some(Box::new(self.query()?));
+        } else {
+            self.consume(
+                TokenType::LeftParen,
+                "Expected '(' or 'as' after table name",
+            )?;
+            loop {
+                if ["constraint", "primary", "unique", "check", "foreign"]
+                    .iter()
+                    .any(|word| self.check_word(word))
+                {
+                    constraints.push(self.table_constraint()?);
+                } else {
+                    columns.push(self.column_definition()?);
+                }
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after columns")?;
+        }
+        Ok(Statement::CreateTable {
+            name,
+            if_not_exists,
+            columns,
+            constraints,
+            select,
+        })
+    }
+
+    /// name [type name] {collate collation | column constraint}
+    /// column constraint: [constraint name] not null | null | primary key [asc | desc] | unique
+    /// | check (expression) | references parent [(column)]
+    fn column_definition(&mut self) -> anyhow::Result<ColumnDefinition> {
+        let name = self.consume(TokenType::Identifier, "Expected column name")?;
+        let type_name = if self.check(TokenType::Identifier) && !self.check_constraint_word() {
+            Some(self.type_name()?)
+        } else {
+            None
+        };
+        let mut collation = None;
+        let mut constraints = vec![];
+        loop {
+            if self.match_token(TokenType::Collate) {
+                collation = Some(self.consume(TokenType::Identifier, "Expected collation name")?);
+                continue;
+            }
+            let named = self.match_word("constraint");
+            if named {
+                self.consume(TokenType::Identifier, "Expected constraint name")?;
+            }
+            if self.match_token(TokenType::Not) {
+                self.consume(TokenType::Nil, "Expected 'null' after 'not'")?;
+                constraints.push(Constraint::NotNull(name.clone()));
+            } else if self.match_token(TokenType::Nil) {
+                // the default
+            } else if self.match_word("primary") {
+                self.primary_key()?;
+                self.match_any(&[TokenType::Asc, TokenType::Desc]);
+                constraints.push(Constraint::PrimaryKey(vec![name.clone()]));
+            } else if self.match_word("unique") {
+                constraints.push(Constraint::Unique(vec![name.clone()]));
+            } else if self.match_word("check") {
+                constraints.push(Constraint::Check(self.check_expression()?));
+            } else if self.match_word("references") {
+                constraints.push(self.references(vec![name.clone()])?);
+            } else if named {
+                return Err(anyhow!(
+                    "Expected constraint after constraint name, found '{}'",
+                    self.peek().lexeme
+                ));
+            } else {
+                break;
+            }
+        }
+        Ok(ColumnDefinition {
+            name,
+            type_name,
+            collation,
+            constraints,
+        })
+    }
+
+    /// [constraint name] primary key (column, ..) | unique (column, ..) | check (expression)
+    /// | foreign key (column, ..) references parent [(column, ..)]
+    fn table_constraint(&mut self) -> anyhow::Result<Constraint> {
+        if self.match_word("constraint") {
+            self.consume(TokenType::Identifier, "Expected constraint name")?;
+        }
+        if self.match_word("primary") {
+            self.primary_key()?;
+            Ok(Constraint::PrimaryKey(self.column_list()?))
+        } else if self.match_word("unique") {
+            Ok(Constraint::Unique(self.column_list()?))
+        } else if self.match_word("check") {
+            Ok(Constraint::Check(self.check_expression()?))
+        } else if self.match_word("foreign") {
+            if !self.match_word("key") {
+                return Err(anyhow!("Expected 'key' after 'foreign'"));
+            }
+            let columns = self.column_list()?;
+            if !self.match_word("references") {
+                return Err(anyhow!("Expected 'references' after foreign key columns"));
+            }
+            self.references(columns)
+        } else {
+            Err(anyhow!(
+                "Expected 'primary key', 'unique', 'check' or 'foreign key', found '{}'",
+                self.peek().lexeme
+            ))
+        }
+    }
+
+    /// a name, number or string, strings without their quotes
+    fn pragma_argument(&mut self) -> String {
+        if self.match_token(TokenType::Str) {
+            self.previous().literal.to_string()
+        } else {
+            self.advance().lexeme.clone()
+        }
+    }
+
+    /// parent [(column, ..)], after 'references'
+    fn references(&mut self, columns: Vec<String>) -> anyhow::Result<Constraint> {
+        let parent = self.table_name()?;
+        let parent_columns = if self.check(TokenType::LeftParen) {
+            self.column_list()?
+        } else {
+            vec![]
+        };
+        Ok(Constraint::ForeignKey {
+            columns,
+            parent,
+            parent_columns,
+        })
+    }
+
+    /// 'key' after 'primary'
+    fn primary_key(&mut self) -> anyhow::Result<()> {
+        if self.match_word("key") {
+            Ok(())
+        } else {
+            Err(anyhow!("Expected 'key' after 'primary'"))
+        }
+    }
+
+    /// (expression) after 'check'
+    fn check_expression(&mut self) -> anyhow::Result<Expression> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'check'")?;
+        let expression = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after check expression")?;
+        Ok(expression)
+    }
+
+    /// (column, ..)
+    fn column_list(&mut self) -> anyhow::Result<Vec<String>> {
+        self.consume(TokenType::LeftParen, "Expected '(' before columns")?;
+        let mut columns = vec![];
+        loop {
+            columns.push(self.consume(TokenType::Identifier, "Expected column name")?);
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after columns")?;
+        Ok(columns)
+    }
+
+    /// words that end a type name, because a constraint starts there
+    fn check_constraint_word(&self) -> bool {
+        [
+            "constraint",
+            "primary",
+            "unique",
+            "check",
+            "references",
+            "default",
+        ]
+        .iter()
+        .any(|word| self.check_word(word))
+    }
+
+    /// alter table name add [column] definition | drop [column] name
+    /// | rename [column] name to new name | rename to new name, after 'table'
+    fn alter_table(&mut self) -> anyhow::Result<Statement> {
+        let name = self.table_name()?;
+        let action = if self.match_word("add") {
+            self.match_word("column");
+            AlterAction::AddColumn(self.column_definition()?)
+        } else if self.match_token(TokenType::Drop) {
+            self.match_word("column");
+            AlterAction::DropColumn(self.consume(TokenType::Identifier, "Expected column name")?)
+        } else if self.match_word("rename") {
+            if self.match_word("to") {
+                AlterAction::RenameTo(self.table_name()?)
+            } else {
+                self.match_word("column");
+                let column = self.consume(TokenType::Identifier, "Expected column name")?;
+                if !self.match_word("to") {
+                    return Err(anyhow!("Expected 'to' after column name"));
+                }
+                let new_name = self.consume(TokenType::Identifier, "Expected new column name")?;
+                AlterAction::RenameColumn(column, new_name)
+            }
+        } else {
+            return Err(anyhow!(
+                "Expected 'add', 'drop' or 'rename' after table name"
+            ));
+        };
+        Ok(Statement::AlterTable { name, action })
+    }
+
+    /// create index name on table (column, ..)
+    fn create_index(&mut self) -> anyhow::Result<Statement> {
+        let name = self.consume(TokenType::Identifier, "Expected index name")?;
+        self.consume(TokenType::On, "Expected 'on' after index name")?;
+        let table = self.table_name()?;
+        self.consume(TokenType::LeftParen, "Expected '(' after table name")?;
+        let mut columns = vec![];
+        loop {
+            columns.push(self.consume(TokenType::Identifier, "Expected column name")?);
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after columns")?;
+        Ok(Statement::CreateIndex {
+            name,
+            table,
+            columns,
+        })
+    }
+
+    /// insert into table [(column, ..)] values (expression, ..), .. | select .., after 'insert'
+    fn insert(&mut self) -> anyhow::Result<Statement> {
+        self.consume(TokenType::Into, "Expected 'into' after 'insert'")?;
+        let table = self.table_name()?;
+        let mut columns = vec![];
+        if self.match_token(TokenType::LeftParen) {
+            loop {
+                columns.push(self.consume(TokenType::Identifier, "Expected column name")?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after columns")?;
+        }
+        let source = if self.match_token(TokenType::Values) {
+            let mut rows = vec![self.parenthesized_list()?];
+            while self.match_token(TokenType::Comma) {
+                rows.push(self.parenthesized_list()?);
+            }
+            InsertSource::Values(rows)
+        } else if self.check(TokenType::Select) || self.check(TokenType::With) {
+            InsertSource::Select(Box::new(self.query()?))
+        } else {
+            return Err(anyhow!("Expected 'values' or 'select' after table"));
+        };
+        Ok(Statement::Insert {
+            table,
+            columns,
+            source,
+        })
+    }
+
+    /// update table set column = expression, .. [where expression], after 'update'
+    fn update(&mut self) -> anyhow::Result<Statement> {
+        let table = self.table_name()?;
+        self.consume(TokenType::Set, "Expected 'set' after table")?;
+        let mut assignments = vec![];
+        loop {
+            let column = self.consume(TokenType::Identifier, "Expected column name")?;
+            self.consume(TokenType::Equals, "Expected '=' after column name")?;
+            assignments.push((column, self.expression()?));
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        Ok(Statement::Update {
+            table,
+            assignments,
+            where_clause: self.where_clause()?,
+        })
+    }
+
+    fn where_clause(&mut self) -> anyhow::Result<Option<Expression>> {
+        Ok(if self.match_token(TokenType::Where) {
+            Some(self.expression()?)
+        } else {
+            None
+        })
+    }
+
+    fn result_columns(&mut self) -> anyhow::Result<Vec<ResultColumn>> {
+        let mut columns = vec![];
+        loop {
+            if self.match_token(TokenType::Star) {
+                columns.push(ResultColumn::Star);
+            } else {
+                let expression = self.expression()?;
+                let alias = self.alias()?;
+                columns.push(ResultColumn::Expression(expression, alias));
+            }
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        Ok(columns)
+    }
+
+    /// a table name, or a table-valued function: name '(' [expression {, expression}] ')',
+    /// with an optional alias
+    fn table_reference(&mut self) -> anyhow::Result<TableReference> {
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::LeftParen) {
+            let name = self.advance().lexeme.to_lowercase();
+            let args = self.parenthesized_list()?;
+            Ok(TableReference::Function(name, args, self.alias()?))
+        } else {
+            let name = self.table_name()?;
+            Ok(TableReference::Table(name, self.alias()?))
+        }
+    }
+
+    /// [as] alias
+    fn alias(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(if self.match_token(TokenType::As) {
+            Some(self.consume(TokenType::Identifier, "Expected alias after 'as'")?)
+        } else if self.check(TokenType::Identifier) {
+            Some(self.advance().lexeme.clone())
+        } else {
+            None
+        })
+    }
+
+    /// a table name is an identifier, or a quoted path to a csv file
+    fn table_name(&mut self) -> anyhow::Result<String> {
+        if self.match_token(TokenType::Str) {
+            Ok(self.previous().literal.to_string())
+        } else {
+            self.consume(TokenType::Identifier, "Expected table name")
+        }
+    }
+
+    fn grouping_element(&mut self) -> anyhow::Result<GroupingElement> {
+        if self.match_token(TokenType::Rollup) {
+            Ok(GroupingElement::Rollup(self.parenthesized_list()?))
+        } else if self.match_token(TokenType::Cube) {
+            Ok(GroupingElement::Cube(self.parenthesized_list()?))
+        } else if self.check(TokenType::Grouping) && self.check_next(TokenType::Sets) {
+            self.advance();
+            self.advance();
+            self.consume(TokenType::LeftParen, "Expected '(' after 'grouping sets'")?;
+            let mut sets = vec![];
+            loop {
+                if self.check(TokenType::LeftParen) {
+                    sets.push(self.parenthesized_list()?);
+                } else {
+                    sets.push(vec![self.expression()?]);
+                }
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after grouping sets")?;
+            Ok(GroupingElement::GroupingSets(sets))
+        } else {
+            Ok(GroupingElement::Expression(self.expression()?))
+        }
+    }
+
+    /// '(' [expression {, expression}] ')'
+    fn parenthesized_list(&mut self) -> anyhow::Result<Vec<Expression>> {
+        self.consume(TokenType::LeftParen, "Expected '('")?;
+        let mut expressions = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                expressions.push(self.expression()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')'")?;
+        Ok(expressions)
+    }
+
+    fn expression(&mut self) -> anyhow::Result<Expression> {
+        self.or()
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.and()?;
+        while self.match_token(TokenType::Or) {
+            let right = self.and()?;
+            expression = Expression::Binary(Box::new(expression), TokenType::Or, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.not()?;
+        while self.match_token(TokenType::And) {
+            let right = self.not()?;
+            expression = Expression::Binary(Box::new(expression), TokenType::And, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expression> {
+        if self.match_token(TokenType::Not) {
+            let operand = self.not()?;
+            Ok(Expression::Unary(TokenType::Not, Box::new(operand)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.term()?;
+        loop {
+            if self.match_any(&[
+                TokenType::Equals,
+                TokenType::BangEquals,
+                TokenType::Unequal,
+                TokenType::Less,
+                TokenType::LessEqual,
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Regexp,
+            ]) {
+                let op = self.previous().tokentype;
+                let right = self.term()?;
+                expression = Expression::Binary(Box::new(expression), op, Box::new(right));
+            } else if self.check(TokenType::Not) && self.check_next(TokenType::Regexp) {
+                // x not regexp p => not (x regexp p)
+                self.advance();
+                self.advance();
+                let right = self.term()?;
+                let regexp =
+                    Expression::Binary(Box::new(expression), TokenType::Regexp, Box::new(right));
+                expression = Expression::Unary(TokenType::Not, Box::new(regexp));
+            } else if self.match_token(TokenType::In) {
+                expression = self.in_list(expression, false)?;
+            } else if self.check(TokenType::Not) && self.check_next(TokenType::In) {
+                self.advance();
+                self.advance();
+                expression = self.in_list(expression, true)?;
+            } else if self.match_token(TokenType::Is) {
+                let negated = self.match_token(TokenType::Not);
+                expression = if self.match_token(TokenType::True) {
+                    Expression::IsTruth(Box::new(expression), true, negated)
+                } else if self.match_token(TokenType::False) {
+                    Expression::IsTruth(Box::new(expression), false, negated)
+                } else {
+                    self.consume(
+                        TokenType::Nil,
+                        "Expected 'null', 'true' or 'false' after 'is'",
+                    )?;
+                    Expression::IsNull(Box::new(expression), negated)
+                };
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    /// '(' select ')' or '(' expression {, expression} ')', after [not] in
+    fn in_list(&mut self, operand: Expression, negated: bool) -> anyhow::Result<Expression> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'in'")?;
+        if self.check(TokenType::Select) || self.check(TokenType::With) {
+            let select = self.query()?;
+            self.consume(TokenType::RightParen, "Expected ')' after subquery")?;
+            return Ok(Expression::InSelect(
+                Box::new(operand),
+                Box::new(select),
+                negated,
+            ));
+        }
+        let mut list = vec![];
+        loop {
+            list.push(self.expression()?);
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after list")?;
+        Ok(Expression::InList(Box::new(operand), list, negated))
+    }
+
+    fn term(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.factor()?;
+        while self.match_any(&[TokenType::Plus, TokenType::Minus]) {
+            let op = self.previous().tokentype;
+            let right = self.factor()?;
+            expression = Expression::Binary(Box::new(expression), op, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn factor(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.unary()?;
+        while self.match_any(&[TokenType::Star, TokenType::Slash]) {
+            let op = self.previous().tokentype;
+            let right = self.unary()?;
+            expression = Expression::Binary(Box::new(expression), op, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expression> {
+        if self.match_token(TokenType::Minus) {
+            let operand = self.unary()?;
+            Ok(Expression::Unary(TokenType::Minus, Box::new(operand)))
+        } else if self.match_token(TokenType::Plus) {
+            self.unary()
+        } else {
+            self.arrow()
+        }
+    }
+
+    /// json -> path and json ->> path
+    fn arrow(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.collate()?;
+        while self.match_any(&[TokenType::Arrow, TokenType::DoubleArrow]) {
+            let op = self.previous().tokentype;
+            let right = self.collate()?;
+            expression = Expression::Binary(Box::new(expression), op, Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn collate(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.primary()?;
+        while self.match_token(TokenType::Collate) {
+            let collation = self.consume(TokenType::Identifier, "Expected collation name")?;
+            expression = Expression::Collate(Box::new(expression), collation.to_lowercase());
+        }
+        Ok(expression)
+    }
+
+    fn primary(&mut self) -> anyhow::Result<Expression> {
+        if self.match_any(&[TokenType::Num, TokenType::Str]) {
+            return Ok(Expression::Literal(self.previous().literal.clone()));
+        }
+        if self.match_token(TokenType::Nil) {
+            return Ok(Expression::Literal(Value::null()));
+        }
+        if self.match_token(TokenType::True) {
+            return Ok(Expression::Literal(Value::from_bool(true)));
+        }
+        if self.match_token(TokenType::False) {
+            return Ok(Expression::Literal(Value::from_bool(false)));
+        }
+        if self.match_any(&[TokenType::Cast, TokenType::TryCast]) {
+            return self.cast();
+        }
+        if self.match_token(TokenType::Case) {
+            return self.case();
+        }
+        if self.match_token(TokenType::Exists) {
+            self.consume(TokenType::LeftParen, "Expected '(' after 'exists'")?;
+            return Ok(Expression::Exists(Box::new(self.subquery()?)));
+        }
+        if self.match_token(TokenType::LeftParen) {
+            if self.check(TokenType::Select) || self.check(TokenType::With) {
+                return Ok(Expression::Subquery(Box::new(self.subquery()?)));
+            }
+            let expression = self.expression()?;
+            self.consume(TokenType::RightParen, "Expected ')' after expression")?;
+            return Ok(expression);
+        }
+        if self.check_next(TokenType::LeftParen)
+            && self.match_any(&[
+                TokenType::Identifier,
+                TokenType::Sum,
+                TokenType::Min,
+                TokenType::Max,
+                TokenType::Grouping,
+            ])
+        {
+            return self.call();
+        }
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::Str) {
+            return self.typed_literal();
+        }
+        // table.column
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::Dot) {
+            let table = self.advance().lexeme.clone();
+            self.advance();
+            let column = self.consume(TokenType::Identifier, "Expected column name after '.'")?;
+            return Ok(Expression::Column(format!("{}.{}", table, column)));
+        }
+        if self.match_token(TokenType::Identifier) {
+            return Ok(Expression::Column(self.previous().lexeme.clone()));
+        }
+        Err(anyhow!(
+            "Expected expression, found '{}'",
+            self.peek().lexeme
+        ))
+    }
+
+    /// [with ..] select .. ')', after '('
+    fn subquery(&mut self) -> anyhow::Result<Select> {
+        let select = self.query()?;
+        self.consume(TokenType::RightParen, "Expected ')' after subquery")?;
+        Ok(select)
+    }
+
+    /// case [operand] when expression then expression {when ..} [else expression] end
+    /// case has been consumed
+    fn case(&mut self) -> anyhow::Result<Expression> {
+        let operand = if self.check(TokenType::When) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        let mut branches = vec![];
+        while self.match_token(TokenType::When) {
+            let when = self.expression()?;
+            self.consume(TokenType::Then, "Expected 'then' after 'when' condition")?;
+            branches.push((when, self.expression()?));
+        }
+        if branches.is_empty() {
+            return Err(anyhow!("Expected 'when' in case"));
+        }
+        let otherwise = if self.match_token(TokenType::Else) {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        self.consume(TokenType::End, "Expected 'end' after case")?;
+        Ok(Expression::Case(operand, branches, otherwise))
+    }
+
+    /// date '2024-01-12', time '13:45', timestamp '2024-01-12 13:45', interval '1 day'
+    fn typed_literal(&mut self) -> anyhow::Result<Expression> {
+        let name = self.advance().lexeme.to_lowercase();
+        let text = self.advance().literal.to_string();
+        let value = match name.as_str() {
+            "interval" => datetime::parse_interval(&text).map(Value::from),
+            "date" | "time" | "timestamp" => datetime::parse_iso(&text)
+                .map(|temporal| match (name.as_str(), temporal) {
+                    ("date", Temporal::Timestamp(micros)) => Temporal::Date(micros).truncated(),
+                    ("timestamp", Temporal::Date(micros)) => Temporal::Timestamp(micros),
+                    (_, temporal) => temporal,
+                })
+                .map(Value::from),
+            _ => return Err(anyhow!("Expected expression, found '{}'", name)),
+        };
+        value
+            .map(Expression::Literal)
+            .ok_or_else(|| anyhow!("Invalid {} literal '{}'", name, text))
+    }
+
+    /// cast(expression as type name), cast or try_cast has been consumed
+    fn cast(&mut self) -> anyhow::Result<Expression> {
+        let fallible = self.previous().tokentype == TokenType::TryCast;
+        self.consume(TokenType::LeftParen, "Expected '(' after cast")?;
+        let expression = self.expression()?;
+        self.consume(TokenType::As, "Expected 'as' in cast")?;
+        let type_name = self.type_name()?;
+        self.consume(TokenType::RightParen, "Expected ')' after type name")?;
+        Ok(Expression::Cast(Box::new(expression), type_name, fallible))
+    }
+
+    /// identifier {identifier} ['(' number [, number] ')'], in lowercase
+    fn type_name(&mut self) -> anyhow::Result<String> {
+        let mut type_name = self
+            .consume(TokenType::Identifier, "Expected type name")?
+            .to_lowercase();
+        while self.check(TokenType::Identifier) && !self.check_constraint_word() {
+            type_name.push(' ');
+            type_name.push_str(&self.advance().lexeme.to_lowercase());
+        }
+        if self.match_token(TokenType::LeftParen) {
+            let mut sizes = vec![self.consume(TokenType::Num, "Expected number")?];
+            while self.match_token(TokenType::Comma) {
+                sizes.push(self.consume(TokenType::Num, "Expected number")?);
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after type size")?;
+            type_name.push_str(&format!("({})", sizes.join(", ")));
+        }
+        Ok(type_name)
+    }
+
+    /// function call, the function name has been consumed
+    fn call(&mut self) -> anyhow::Result<Expression> {
+        let name = self.previous().lexeme.to_lowercase();
+        self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
+        let mut args = vec![];
+        if self.match_token(TokenType::Star) {
+            args.push(Expression::Star);
+        } else if self.match_token(TokenType::Distinct) {
+            args.push(Expression::Distinct(Box::new(self.expression()?)));
+        } else if !self.check(TokenType::RightParen) {
+            loop {
+                args.push(self.expression()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+        Ok(Expression::Function(name, args))
+    }
+
+    fn consume(&mut self, tokentype: TokenType, message: &str) -> anyhow::Result<String> {
+        if self.check(tokentype) {
+            Ok(self.advance().lexeme.clone())
+        } else {
+            Err(anyhow!("{}, found '{}'", message, self.peek().lexeme))
+        }
+    }
+
+    fn match_any(&mut self, tokentypes: &[TokenType]) -> bool {
+        tokentypes.iter().any(|t| self.match_token(*t))
+    }
+
+    fn match_token(&mut self, tokentype: TokenType) -> bool {
+        if self.check(tokentype) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, tokentype: TokenType) -> bool {
+        self.peek().tokentype == tokentype
+    }
+
+    /// matches an identifier that is a keyword only here, like 'rows' in 'fetch first 1 rows only'
+    fn match_word(&mut self, word: &str) -> bool {
+        if self.check_word(word) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check_word(&self, word: &str) -> bool {
+        self.check(TokenType::Identifier) && self.peek().lexeme.eq_ignore_ascii_case(word)
+    }
+
+    fn check_next(&self, tokentype: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|t| t.tokentype == tokentype)
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().tokentype == TokenType::Eof
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let Ok(Statement::Select(select)) =
+            parse("select name, sum(value) as total from t where value > 1 group by name;")
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(select.columns.len(), 2);
+        assert_eq!(select.columns[1].name(), "total");
+        assert_eq!(
+            select.from,
+            Some(TableReference::Table("t".to_string(), None))
+        );
+        assert_eq!(select.where_clause.unwrap().to_string(), "value > 1");
+        assert_eq!(
+            select.group_by,
+            vec![GroupingElement::Expression(Expression::Column(
+                "name".to_string()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_grouping_sets() {
+        let Ok(Statement::Select(select)) =
+            parse("select a, b, count(*) from t group by grouping sets ((a, b), a, ())")
+        else {
+            panic!("expected select");
+        };
+        let GroupingElement::GroupingSets(sets) = &select.group_by[0] else {
+            panic!("expected grouping sets");
+        };
+        assert_eq!(sets.len(), 3);
+        assert_eq!(sets[1].len(), 1);
+        assert!(sets[2].is_empty());
+    }
+
+    #[test]
+    fn test_create_index() {
+        assert_eq!(
+            parse("create index idx on t (a, b)").unwrap(),
+            Statement::CreateIndex {
+                name: "idx".to_string(),
+                table: "t".to_string(),
+                columns: vec!["a".to_string(), "b".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert() {
+        let Ok(Statement::Insert {
+            table,
+            columns,
+            source: InsertSource::Values(rows),
+        }) = parse("insert into t (a, b) values (1, 'x'), (2, null)")
+        else {
+            panic!("expected insert values");
+        };
+        assert_eq!(table, "t");
+        assert_eq!(columns, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(
+            parse("insert into t select * from u"),
+            Ok(Statement::Insert {
+                source: InsertSource::Select(_),
+                ..
+            })
+        ));
+        assert!(parse("insert into t (a) (1)").is_err());
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let Ok(Statement::Update {
+            table,
+            assignments,
+            where_clause: Some(_),
+        }) = parse("update t set a = a + 1, b = 'x' where a > 1")
+        else {
+            panic!("expected update");
+        };
+        assert_eq!(table, "t");
+        assert_eq!(assignments.len(), 2);
+        assert_eq!(assignments[0].0, "a");
+        assert_eq!(
+            parse("delete from t").unwrap(),
+            Statement::Delete {
+                table: "t".to_string(),
+                where_clause: None
+            }
+        );
+        assert!(parse("update t a = 1").is_err());
+    }
+
+    #[test]
+    fn test_create_and_alter_table() {
+        let Ok(Statement::CreateTable {
+            name,
+            if_not_exists: true,
+            columns,
+            select: None,
+            ..
+        }) = parse("create table if not exists t (a integer, b varchar(10) collate nocase, c)")
+        else {
+            panic!("expected create table");
+        };
+        assert_eq!(name, "t");
+        assert_eq!(columns[1].type_name.as_deref(), Some("varchar(10)"));
+        assert_eq!(columns[1].collation.as_deref(), Some("nocase"));
+        assert_eq!(columns[2].type_name, None);
+        assert!(matches!(
+            parse("create table t as select 1"),
+            Ok(Statement::CreateTable {
+                select: Some(_),
+                ..
+            })
+        ));
+        assert_eq!(
+            parse("alter table t rename column a to b").unwrap(),
+            Statement::AlterTable {
+                name: "t".to_string(),
+                action: AlterAction::RenameColumn("a".to_string(), "b".to_string())
+            }
+        );
+        assert!(matches!(
+            parse("alter table t add c text"),
+            Ok(Statement::AlterTable {
+                action: AlterAction::AddColumn(_),
+                ..
+            })
+        ));
+        assert!(parse("drop table if exists t").is_ok());
+        assert!(parse("alter table t rename a b").is_err());
+    }
+
+    #[test]
+    fn test_constraints() {
+        let Ok(Statement::CreateTable {
+            columns,
+            constraints,
+            ..
+        }) = parse(
+            "create table t (id integer primary key, email text not null unique, \
+             amount numeric constraint positive check (amount >= 0), \
+             unique (email, amount), constraint pk primary key (id))",
+        )
+        else {
+            panic!("expected create table");
+        };
+        assert_eq!(columns[0].type_name.as_deref(), Some("integer"));
+        assert_eq!(
+            columns[0].constraints,
+            vec![Constraint::PrimaryKey(vec!["id".to_string()])]
+        );
+        assert_eq!(
+            columns[1].constraints,
+            vec![
+                Constraint::NotNull("email".to_string()),
+                Constraint::Unique(vec!["email".to_string()])
+            ]
+        );
+        assert!(matches!(
+            &columns[2].constraints[..],
+            [Constraint::Check(_)]
+        ));
+        assert_eq!(
+            constraints,
+            vec![
+                Constraint::Unique(vec!["email".to_string(), "amount".to_string()]),
+                Constraint::PrimaryKey(vec!["id".to_string()])
+            ]
+        );
+        let Ok(Statement::CreateTable {
+            columns,
+            constraints,
+            ..
+        }) = parse(
+            "create table o (c integer references customers (id), a, b, \
+             foreign key (a, b) references p)",
+        )
+        else {
+            panic!("expected create table");
+        };
+        assert_eq!(
+            columns[0].constraints,
+            vec![Constraint::ForeignKey {
+                columns: vec!["c".to_string()],
+                parent: "customers".to_string(),
+                parent_columns: vec!["id".to_string()]
+            }]
+        );
+        assert_eq!(
+            constraints,
+            vec![Constraint::ForeignKey {
+                columns: vec!["a".to_string(), "b".to_string()],
+                parent: "p".to_string(),
+                parent_columns: vec![]
+            }]
+        );
+        assert_eq!(
+            parse("pragma foreign_key_check('orders.csv')").unwrap(),
+            Statement::Pragma {
+                name: "foreign_key_check".to_string(),
+                argument: Some("orders.csv".to_string())
+            }
+        );
+        assert!(parse("create table t (a constraint c)").is_err());
+        assert!(parse("create table t (a, primary (a))").is_err());
+    }
+
+    #[test]
+    fn test_transactions() {
+        assert_eq!(parse("begin transaction").unwrap(), Statement::Begin);
+        assert_eq!(parse("end").unwrap(), Statement::Commit);
+        assert_eq!(
+            parse("rollback to savepoint a").unwrap(),
+            Statement::Rollback(Some("a".to_string()))
+        );
+        assert_eq!(
+            parse("release a").unwrap(),
+            Statement::Release("a".to_string())
+        );
+        assert!(parse("savepoint").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let expression = parse_expression("1 + 2 * 3 = 7 and not a is null").unwrap();
+        let Expression::Binary(left, TokenType::And, right) = expression else {
+            panic!("expected and");
+        };
+        assert_eq!(left.to_string(), "1 + 2 * 3 = 7");
+        assert_eq!(right.to_string(), "not a is null");
+    }
+
+    #[test]
+    fn test_collate() {
+        let expression = parse_expression("-a collate NOCASE = 'x'").unwrap();
+        let Expression::Binary(left, TokenType::Equals, _) = expression else {
+            panic!("expected =");
+        };
+        let Expression::Unary(TokenType::Minus, operand) = *left else {
+            panic!("expected -");
+        };
+        assert_eq!(operand.to_string(), "a collate nocase");
+    }
+
+    #[test]
+    fn test_json() {
+        let expression = parse_expression("d -> 'a' ->> '$.b' = 1").unwrap();
+        assert_eq!(expression.to_string(), "d -> 'a' ->> '$.b' = 1");
+        let Ok(Statement::Select(select)) = parse("select key from json_each(d, '$.a')") else {
+            panic!("expected select");
+        };
+        assert!(
+            matches!(select.from, Some(TableReference::Function(name, args, None))
+            if name == "json_each" && args.len() == 2)
+        );
+    }
+
+    #[test]
+    fn test_case_and_subqueries() {
+        for sql in [
+            "case x when 1 then 'a' else 'b' end",
+            "case when a > 1 then 2 end + 1",
+            "a not in (select b from t as u where u.c = 1)",
+            "a in (1, 2) and not exists (select * from json_each(d))",
+        ] {
+            assert_eq!(parse_expression(sql).unwrap().to_string(), sql);
+        }
+        assert!(parse_expression("case end").is_err());
+        assert!(parse_expression("(select 1").is_err());
+    }
+
+    #[test]
+    fn test_with() {
+        let sql = "with recursive t(n) as (select 1 union all select n + 1 from t where n < 3), \
+                   u as (select n from t) select n from u";
+        let Ok(Statement::Select(select)) = parse(sql) else {
+            panic!("expected select");
+        };
+        let with = select.with.as_ref().unwrap();
+        assert!(with.recursive);
+        assert_eq!(with.tables[0].columns, vec!["n".to_string()]);
+        assert!(matches!(with.tables[0].step, Some((_, true))));
+        assert_eq!(
+            select.to_string(),
+            sql.split_whitespace().collect::<Vec<_>>().join(" ")
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let Ok(Statement::Select(select)) =
+            parse("select distinct on (a) a from t offset 2 rows fetch first 5 rows only")
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            select.to_string(),
+            "select distinct on (a) a from t limit 5 offset 2"
+        );
+        assert!(parse("select a from t fetch first 5 only").is_err());
+    }
+}
@@ -13,7 +13,6 @@ pub fn parse(sql: &str) -> anyhow::Result<Vec<Token>> {
 }
 
 struct Scanner {
-    source: String,
     source_chars: Vec<char>,
     tokens: Vec<Token>,
     start: usize,
@@ -24,8 +23,7 @@ struct Scanner {
 impl Scanner {
     fn new(sql: &str) -> Self {
         let mut new = Self {
-            source: sql.to_string(),
-            source_chars: sql.to_string().chars().collect(),
+            source_chars: sql.chars().collect(),
             tokens: vec![],
             start: 0,
             current: 0,
@@ -42,6 +40,8 @@ impl Scanner {
             self.start = self.current;
             self.scan_token()?;
         }
+        self.tokens
+            .push(Token::new(TokenType::Eof, "", Value::null()));
         Ok(())
     }
 
@@ -57,6 +57,13 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                } else if self.match_token('>') {
+                    let token = if self.match_token('>') {
+                        TokenType::DoubleArrow
+                    } else {
+                        TokenType::Arrow
+                    };
+                    self.add_token(token)
                 } else {
                     self.add_token(TokenType::Minus);
                 }
@@ -64,9 +71,21 @@ impl Scanner {
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::Semicolon),
             '*' => self.add_token(TokenType::Star),
+            '/' => self.add_token(TokenType::Slash),
+            '=' => self.add_token(TokenType::Equals),
+            '!' => {
+                let token = if self.match_token('=') {
+                    TokenType::BangEquals
+                } else {
+                    TokenType::Bang
+                };
+                self.add_token(token)
+            }
             '<' => {
                 let token = if self.match_token('=') {
                     TokenType::LessEqual
+                } else if self.match_token('>') {
+                    TokenType::Unequal
                 } else {
                     TokenType::Less
                 };
@@ -82,6 +101,7 @@ impl Scanner {
             }
             ' ' | '\t' | '\r' | '\n' => {}
             '\'' => self.string()?,
+            '"' => self.quoted_identifier()?,
             _ => {
                 if is_digit(c) {
                     self.number();
@@ -99,7 +119,7 @@ impl Scanner {
         while is_alphanumeric(self.peek()) {
             self.advance();
         }
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.lexeme();
         let tokentype = self.keywords.get(&text.to_lowercase());
 
         self.add_token(if let Some(tokentype) = tokentype {
@@ -113,11 +133,20 @@ impl Scanner {
         while is_digit(self.peek()) {
             self.advance();
         }
-        if (self.peek() == '.' || self.peek() == ',') && is_digit(self.peek_next()) {
+        if self.peek() == '.' && is_digit(self.peek_next()) {
             self.advance();
+            while is_digit(self.peek()) {
+                self.advance();
+            }
         }
 
-        self.add_literal(TokenType::Num, self.source[self.start..self.current].into());
+        let number = self.lexeme();
+        let literal = if let Ok(integer) = number.parse::<i64>() {
+            Value::from_i64(integer)
+        } else {
+            number.as_str().into()
+        };
+        self.add_literal(TokenType::Num, literal);
     }
 
     fn string(&mut self) -> anyhow::Result<()> {
@@ -131,8 +160,30 @@ impl Scanner {
 
         self.advance();
 
-        let string = self.source[self.start + 1..self.current - 1].to_string();
-        self.add_literal(TokenType::Str, string.into());
+        let string: String = self.source_chars[self.start + 1..self.current - 1]
+            .iter()
+            .collect();
+        self.add_literal(TokenType::Str, Value::from_text(string));
+        Ok(())
+    }
+
+    /// "some column" => identifier: some column
+    fn quoted_identifier(&mut self) -> anyhow::Result<()> {
+        while self.peek() != '"' && !self.is_at_end() {
+            self.advance();
+        }
+
+        if self.is_at_end() {
+            return Err(anyhow!("Unterminated quoted identifier"));
+        }
+
+        self.advance();
+
+        let name: String = self.source_chars[self.start + 1..self.current - 1]
+            .iter()
+            .collect();
+        self.tokens
+            .push(Token::new(TokenType::Identifier, name, Value::null()));
         Ok(())
     }
 
@@ -145,7 +196,7 @@ impl Scanner {
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 > self.source_chars.len() {
+        if self.current + 1 >= self.source_chars.len() {
             '\0'
         } else {
             self.source_chars[self.current + 1]
@@ -153,15 +204,19 @@ impl Scanner {
     }
 
     fn add_token(&mut self, tokentype: TokenType) {
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.lexeme();
         self.tokens.push(Token::new(tokentype, text, Value::null()));
     }
 
     fn add_literal(&mut self, tokentype: TokenType, literal: Value) {
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.lexeme();
         self.tokens.push(Token::new(tokentype, text, literal));
     }
 
+    fn lexeme(&self) -> String {
+        self.source_chars[self.start..self.current].iter().collect()
+    }
+
     fn advance(&mut self) -> char {
         self.current += 1;
         self.source_chars[self.current - 1]
@@ -182,7 +237,7 @@ impl Scanner {
 }
 
 fn is_digit(c: char) -> bool {
-    c.is_digit(10)
+    c.is_ascii_digit()
 }
 
 fn is_alpha(c: char) -> bool {
@@ -4,9 +4,9 @@ use crate::value::Value;
 
 #[derive(Debug)]
 pub struct Token {
-    tokentype: TokenType,
-    lexeme: String,
-    literal: Value,
+    pub(crate) tokentype: TokenType,
+    pub(crate) lexeme: String,
+    pub(crate) literal: Value,
 }
 
 impl Token {
@@ -42,9 +42,51 @@ pub(crate) fn add_keywords(keywords: &mut HashMap<String, TokenType>) {
     keywords.insert("delete".to_string(), TokenType::Delete);
     keywords.insert("commit".to_string(), TokenType::Commit);
     keywords.insert("describe".to_string(), TokenType::Describe);
+    keywords.insert("null".to_string(), TokenType::Nil);
+    keywords.insert("not".to_string(), TokenType::Not);
+    keywords.insert("is".to_string(), TokenType::Is);
+    keywords.insert("as".to_string(), TokenType::As);
+    keywords.insert("asc".to_string(), TokenType::Asc);
+    keywords.insert("desc".to_string(), TokenType::Desc);
+    keywords.insert("rollup".to_string(), TokenType::Rollup);
+    keywords.insert("cube".to_string(), TokenType::Cube);
+    keywords.insert("grouping".to_string(), TokenType::Grouping);
+    keywords.insert("sets".to_string(), TokenType::Sets);
+    keywords.insert("create".to_string(), TokenType::Create);
+    keywords.insert("index".to_string(), TokenType::Index);
+    keywords.insert("on".to_string(), TokenType::On);
+    keywords.insert("collate".to_string(), TokenType::Collate);
+    keywords.insert("cast".to_string(), TokenType::Cast);
+    keywords.insert("regexp".to_string(), TokenType::Regexp);
+    keywords.insert("try_cast".to_string(), TokenType::TryCast);
+    keywords.insert("case".to_string(), TokenType::Case);
+    keywords.insert("when".to_string(), TokenType::When);
+    keywords.insert("then".to_string(), TokenType::Then);
+    keywords.insert("end".to_string(), TokenType::End);
+    keywords.insert("in".to_string(), TokenType::In);
+    keywords.insert("exists".to_string(), TokenType::Exists);
+    keywords.insert("with".to_string(), TokenType::With);
+    keywords.insert("recursive".to_string(), TokenType::Recursive);
+    keywords.insert("all".to_string(), TokenType::All);
+    keywords.insert("intersect".to_string(), TokenType::Intersect);
+    keywords.insert("except".to_string(), TokenType::Except);
+    keywords.insert("distinct".to_string(), TokenType::Distinct);
+    keywords.insert("limit".to_string(), TokenType::Limit);
+    keywords.insert("offset".to_string(), TokenType::Offset);
+    keywords.insert("fetch".to_string(), TokenType::Fetch);
+    keywords.insert("into".to_string(), TokenType::Into);
+    keywords.insert("values".to_string(), TokenType::Values);
+    keywords.insert("set".to_string(), TokenType::Set);
+    keywords.insert("table".to_string(), TokenType::Table);
+    keywords.insert("drop".to_string(), TokenType::Drop);
+    keywords.insert("alter".to_string(), TokenType::Alter);
+    keywords.insert("begin".to_string(), TokenType::Begin);
+    keywords.insert("rollback".to_string(), TokenType::Rollback);
+    keywords.insert("savepoint".to_string(), TokenType::Savepoint);
+    keywords.insert("release".to_string(), TokenType::Release);
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
     LeftParen,
     RightParen,
@@ -53,6 +95,7 @@ pub enum TokenType {
     Minus,
     Plus,
     Star,
+    Slash,
     Semicolon,
     Colon,
     Bang, // !
@@ -88,4 +131,48 @@ pub enum TokenType {
     Delete,
     Commit,
     Describe,
+    Not,
+    Is,
+    As,
+    Asc,
+    Desc,
+    Rollup,
+    Cube,
+    Grouping,
+    Sets,
+    Create,
+    Index,
+    On,
+    Collate,
+    Cast,
+    TryCast,
+    Regexp,
+    Case,
+    When,
+    Then,
+    End,
+    In,
+    Exists,
+    With,
+    Recursive,
+    All,
+    Intersect,
+    Except,
+    Distinct,
+    Limit,
+    Offset,
+    Fetch,
+    Into,
+    Values,
+    Set,
+    Table,
+    Drop,
+    Alter,
+    Begin,
+    Rollback,
+    Savepoint,
+    Release,
+    Arrow,       // ->
+    DoubleArrow, // ->>
+    Eof,
 }
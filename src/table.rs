@@ -1,19 +1,16 @@
+use crate::affinity::Affinity;
+use crate::collation::Collation;
+use crate::constraint::Rule;
 use crate::id_sequence::ThreadSafeIdGenerator;
-use crate::page::{Page, PageType};
+pub use crate::index::{Key, View};
+use crate::page::{Page, PageType, MAX_RECORD_SIZE, PAGE_SIZE};
+use crate::pager::{Pager, FILE_PAGE_SIZE};
 use crate::record::Record;
 use crate::value::Value;
+use crate::varint;
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-use std::{
-    cmp::Ordering,
-    collections::{BTreeMap, HashMap},
-};
-
-// work in progress
-#[derive(Debug)]
-pub struct View {
-    records: BTreeMap<Key, Key>,
-}
 
 /// table struct
 #[derive(Debug)]
@@ -21,11 +18,16 @@ pub struct Table {
     name: String,
     cols_by_name: HashMap<String, usize>, // map names to the internal column indexes, for fetching record values
     pub(crate) cols: Vec<String>,         // column names
+    collations: Vec<Collation>,           // collation per column
+    affinities: Vec<Affinity>,            // affinity per column
     pub(crate) root: Rc<RefCell<Page>>,   // table root page
-    pub views: HashMap<String, View>, // cache all internally used views // not sure about this design
-    page_ids: ThreadSafeIdGenerator,  // generate page ids
-    row_ids: ThreadSafeIdGenerator,   // generate row ids
-    current_page: Rc<RefCell<Page>>,  // ref to current page for (bulk) loading
+    pub views: HashMap<String, View>,     // indexes by name // not sure about this design
+    page_ids: ThreadSafeIdGenerator,      // generate page ids
+    pub(crate) row_ids: ThreadSafeIdGenerator, // generate row ids
+    current_page: Rc<RefCell<Page>>,      // ref to current page for (bulk) loading
+    pub(crate) constraints: Vec<Rule>,    // enforced by insert_checked and update_checked
+    saved: bool,                          // read from or saved to a page file
+    freed: Vec<usize>,                    // ids of the pages removed since then
 }
 
 impl Table {
@@ -36,29 +38,219 @@ impl Table {
             name: name.into(),
             cols_by_name: HashMap::new(),
             cols: vec![],
+            collations: vec![],
+            affinities: vec![],
             root: Rc::clone(&root),
             views: HashMap::new(),
             page_ids: ThreadSafeIdGenerator::new(1),
-            row_ids: ThreadSafeIdGenerator::new(0),
+            row_ids: ThreadSafeIdGenerator::new(1),
             current_page: root,
+            constraints: vec![],
+            saved: false,
+            freed: vec![],
         }
     }
 
     /// Creates a new table with the same name and columns as self,
     /// but without data
-    // Note to self: be careful, might be dangerous to use once tables can be altered.
-    // That is not yet implemented. May need full copies
+    // the copy has the columns of the moment, later alterations of self do not apply to it
     pub fn empty_copy(&self) -> Self {
         let mut result = Table::new(self.name.clone());
         result.cols_by_name = self.cols_by_name.clone();
         result.cols = self.cols.clone();
+        result.collations = self.collations.clone();
+        result.affinities = self.affinities.clone();
         result
     }
 
-    /// insert a new record
+    /// insert a record under its own rowid, new rowids continue after it
+    /// use: copying records from another table, in rowid order
+    /// when the current page is full, a new one is added
+    /// values are converted to the affinity of their column
+    /// a record that does not fit in a page, or with a rowid that is not above
+    /// the last one in the table, is an error: pages are kept in rowid order
+    pub fn insert(&mut self, mut record: Record) -> anyhow::Result<()> {
+        self.apply_affinities(&mut record);
+        let rowid = record.rowid;
+        if let Some(last) = self.last_rowid().filter(|last| rowid <= *last) {
+            return Err(anyhow::anyhow!(
+                "Cannot insert rowid {} into {} after rowid {}",
+                rowid,
+                self.name,
+                last
+            ));
+        }
+        let bytes = encode(&record)?;
+        self.row_ids.skip_past(rowid as usize);
+        for view in self.views.values_mut() {
+            view.insert(&record);
+        }
+        if !self.current_page.borrow().has_room(bytes.len()) {
+            let page = Rc::new(RefCell::new(Page::new(
+                PageType::Leaf,
+                self.page_ids.next(),
+            )));
+            self.root.borrow_mut().children.push(Rc::clone(&page));
+            self.current_page = page;
+        }
+        self.current_page.borrow_mut().insert(rowid, bytes)
+    }
+
+    /// the rowid of the last record in the table, None when it is empty
+    fn last_rowid(&self) -> Option<u64> {
+        let root = self.root.borrow();
+        root.children
+            .iter()
+            .rev()
+            .find_map(|page| page.borrow().last_rowid())
+            .or_else(|| root.last_rowid())
+    }
+
+    /// insert a new record, assigning it the next rowid, and returns that rowid
     /// use: individual insert query, bulk loading
-    pub fn insert(&mut self, record: Record) {
-        self.current_page.borrow_mut().insert(record);
+    pub fn append(&mut self, mut record: Record) -> anyhow::Result<u64> {
+        record.rowid = self.row_ids.next() as u64;
+        let rowid = record.rowid;
+        self.insert(record)?;
+        Ok(rowid)
+    }
+
+    /// replaces the record that has the rowid of record, false when there is none
+    /// values are converted to the affinity of their column
+    /// a page that gets too full is split, one that gets almost empty is merged with the next
+    /// a record that does not fit in a page is an error
+    pub fn update(&mut self, mut record: Record) -> anyhow::Result<bool> {
+        let rowid = record.rowid;
+        let (Some(old), Some(page)) = (self.get(rowid), self.page_of(rowid)) else {
+            return Ok(false);
+        };
+        self.apply_affinities(&mut record);
+        let bytes = encode(&record)?;
+        for view in self.views.values_mut() {
+            view.remove(&old);
+            view.insert(&record);
+        }
+        if page.borrow_mut().update(rowid, bytes.clone()) {
+            self.rebalance(&page);
+        } else {
+            self.split(&page, rowid, bytes)?;
+        }
+        Ok(true)
+    }
+
+    /// removes the record with the rowid, false when there is none
+    pub fn delete(&mut self, rowid: u64) -> bool {
+        let (Some(old), Some(page)) = (self.get(rowid), self.page_of(rowid)) else {
+            return false;
+        };
+        for view in self.views.values_mut() {
+            view.remove(&old);
+        }
+        page.borrow_mut().delete(rowid);
+        self.rebalance(&page);
+        true
+    }
+
+    pub(crate) fn apply_affinities(&self, record: &mut Record) {
+        for (value, affinity) in record.values.iter_mut().zip(&self.affinities) {
+            if *affinity != Affinity::Blob {
+                *value = affinity.apply(std::mem::replace(value, Value::null()));
+            }
+        }
+    }
+
+    /// the page that holds, or would hold, the record with the rowid
+    fn page_of(&self, rowid: u64) -> Option<Rc<RefCell<Page>>> {
+        let root = self.root.borrow();
+        if root.last_rowid().is_some_and(|last| rowid <= last) {
+            return Some(Rc::clone(&self.root));
+        }
+        // child pages are in rowid order
+        let index = root
+            .children
+            .partition_point(|page| page.borrow().last_rowid().is_some_and(|last| last < rowid));
+        root.children.get(index).map(Rc::clone)
+    }
+
+    /// splits the page in two halves, by size, with the record replaced by bytes
+    /// a half that does not fit in a page is split again
+    /// the other parts go to new pages that follow the page
+    fn split(
+        &mut self,
+        page: &Rc<RefCell<Page>>,
+        rowid: u64,
+        bytes: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let mut cells = page.borrow().cells();
+        if let Some(cell) = cells.iter_mut().find(|(id, _)| *id == rowid) {
+            cell.1 = bytes;
+        }
+        let total: usize = cells.iter().map(|(_, bytes)| bytes.len() + 2).sum();
+        let mut parts: Vec<Vec<(u64, Vec<u8>)>> = vec![vec![]];
+        let mut size = 0;
+        for cell in cells {
+            let len = cell.1.len() + 2;
+            if size > 0 && (size >= total / 2 || size + len >= PAGE_SIZE) {
+                parts.push(vec![]);
+                size = 0;
+            }
+            size += len;
+            parts.last_mut().unwrap().push(cell);
+        }
+        let mut parts = parts.into_iter();
+        page.borrow_mut()
+            .rewrite(parts.next().unwrap_or_default())?;
+
+        let mut previous = Rc::clone(page);
+        for part in parts {
+            let new_page = Rc::new(RefCell::new(Page::new(
+                PageType::Leaf,
+                self.page_ids.next(),
+            )));
+            new_page.borrow_mut().rewrite(part)?;
+            let mut root = self.root.borrow_mut();
+            let position = root
+                .children
+                .iter()
+                .position(|child| Rc::ptr_eq(child, &previous))
+                .map_or(0, |index| index + 1);
+            root.children.insert(position, Rc::clone(&new_page));
+            if Rc::ptr_eq(&self.current_page, &previous) {
+                self.current_page = Rc::clone(&new_page);
+            }
+            previous = new_page;
+        }
+        Ok(())
+    }
+
+    /// removes an empty child page, or merges one that is less than a quarter full
+    /// into the next page when they fit together
+    /// the root and the current page for inserts stay
+    fn rebalance(&mut self, page: &Rc<RefCell<Page>>) {
+        if Rc::ptr_eq(page, &self.current_page) || Rc::ptr_eq(page, &self.root) {
+            return;
+        }
+        let mut root = self.root.borrow_mut();
+        let Some(index) = root.children.iter().position(|c| Rc::ptr_eq(c, page)) else {
+            return;
+        };
+        if page.borrow().n_records() == 0 {
+            root.children.remove(index);
+            self.freed.push(page.borrow().id());
+            return;
+        }
+        if page.borrow().used() >= PAGE_SIZE / 4 {
+            return;
+        }
+        if let Some(next) = root.children.get(index + 1) {
+            let mut cells = page.borrow().cells();
+            cells.extend(next.borrow().cells());
+            // the next page is left as it is when they do not fit together
+            if next.borrow_mut().rewrite(cells).is_ok() {
+                root.children.remove(index);
+                self.freed.push(page.borrow().id());
+            }
+        }
     }
 
     /// true if the column name is contained in the table
@@ -90,6 +282,131 @@ impl Table {
 
         self.cols_by_name.insert(name.clone(), col_index);
         self.cols.push(name);
+        self.collations.push(Collation::binary());
+        self.affinities.push(Affinity::Blob);
+    }
+
+    /// adds a column to a table that may already have records, they get NULL for it
+    pub fn append_column(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.has_column(name) {
+            return Err(anyhow::anyhow!("Duplicate column name: {}", name));
+        }
+        let mut records: Vec<Record> = self.iter().collect();
+        for record in &mut records {
+            record.add_value(Value::null());
+            // all or nothing: no record is changed when one of them grows too large
+            encode(record)?;
+        }
+        self.add_column(name, false);
+        for record in records {
+            self.update(record)?;
+        }
+        Ok(())
+    }
+
+    /// removes the column and its values
+    /// indexes on the column are dropped, the other indexes are rebuilt
+    pub fn drop_column(&mut self, name: &str) -> anyhow::Result<()> {
+        let index = self
+            .find_index(name)
+            .ok_or_else(|| anyhow::anyhow!("No such column: {}", name))?;
+        if self.cols.len() == 1 {
+            return Err(anyhow::anyhow!("Cannot drop {}, the only column", name));
+        }
+        self.drop_rules(index)?;
+        let views: Vec<(String, Vec<usize>)> = self
+            .views
+            .drain()
+            .filter(|(_, view)| !view.columns().contains(&index))
+            .map(|(name, view)| {
+                let columns = view
+                    .columns()
+                    .iter()
+                    .map(|column| if *column > index { column - 1 } else { *column })
+                    .collect();
+                (name, columns)
+            })
+            .collect();
+        self.cols.remove(index);
+        self.collations.remove(index);
+        self.affinities.remove(index);
+        self.cols_by_name = self
+            .cols
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.clone(), index))
+            .collect();
+        let records: Vec<Record> = self.iter().collect();
+        for mut record in records {
+            record.values.remove(index);
+            self.update(record)?;
+        }
+        for (name, columns) in views {
+            self.create_index_on(name, columns);
+        }
+        Ok(())
+    }
+
+    pub fn rename_column(&mut self, name: &str, new_name: &str) -> anyhow::Result<()> {
+        if self.has_column(new_name) {
+            return Err(anyhow::anyhow!("Duplicate column name: {}", new_name));
+        }
+        let index = self
+            .cols_by_name
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No such column: {}", name))?;
+        self.cols[index] = new_name.to_string();
+        self.cols_by_name.insert(new_name.to_string(), index);
+        self.rename_rules(name, new_name);
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rename(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    /// the affinity of the column at index
+    pub fn affinity(&self, index: usize) -> Affinity {
+        self.affinities[index]
+    }
+
+    /// sets the affinity of the column, for the values that are inserted from now on
+    /// declare it before loading data, see CsvOptions::column_type
+    pub fn set_affinity(&mut self, column: &str, affinity: Affinity) -> anyhow::Result<()> {
+        let index = self
+            .find_index(column)
+            .ok_or_else(|| anyhow::anyhow!("No such column: {}", column))?;
+        self.affinities[index] = affinity;
+        Ok(())
+    }
+
+    /// the collation of the column at index
+    pub fn collation(&self, index: usize) -> &Collation {
+        &self.collations[index]
+    }
+
+    /// sets the collation for comparing the text values in the column
+    /// indexes on the column are rebuilt
+    pub fn set_collation(&mut self, column: &str, collation: Collation) -> anyhow::Result<()> {
+        let index = self
+            .find_index(column)
+            .ok_or_else(|| anyhow::anyhow!("No such column: {}", column))?;
+        self.collations[index] = collation;
+        let names: Vec<String> = self
+            .views
+            .iter()
+            .filter(|(_, view)| view.columns().contains(&index))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            let columns = self.views.remove(&name).unwrap().columns().to_vec();
+            self.create_index_on(name, columns);
+        }
+        Ok(())
     }
 
     /// from a comma separated list of strings, return the column indexes in the record
@@ -105,10 +422,21 @@ impl Table {
         *self.cols_by_name.get(col_name).unwrap() // TODO handle invalid names better
     }
 
+    /// returns the column index, or None for an invalid name
+    pub fn find_index(&self, col_name: &str) -> Option<usize> {
+        self.cols_by_name.get(col_name).copied()
+    }
+
+    /// returns the record with the rowid
+    pub fn get(&self, rowid: u64) -> Option<Record> {
+        self.page_of(rowid)?.borrow().get_by_rowid(rowid)
+    }
+
     // work in progress
     pub fn iter(&self) -> TableIter {
         TableIter {
             root_page: Rc::clone(&self.root),
+            page_index: 0,
             index: 0,
         }
     }
@@ -123,13 +451,156 @@ impl Table {
     }
 
     /// iterate the column names
-    pub fn iter_colums(&self) -> ColIter {
+    pub fn iter_colums(&self) -> ColIter<'_> {
         ColIter {
             cols: &self.cols,
             index: 0,
         }
     }
 
+    /// writes the changes to the page file in one commit, nothing when there are none
+    /// page 0 describes the table, a page with id n is stored as page n + 1
+    /// after the first save, only the pages that were written or removed since the last one
+    /// are committed, so the page file must be the one the table was opened from or saved to
+    /// indexes and constraints are not stored
+    pub fn save(&mut self, pager: &mut Pager) -> anyhow::Result<()> {
+        let mut pages = vec![];
+        let schema = self.schema_page()?;
+        let mut padded = schema.clone();
+        padded.resize(FILE_PAGE_SIZE, 0);
+        if pager.page_count() == 0 || pager.read(0)? != padded {
+            pages.push((0, schema));
+        }
+        let live: Vec<Rc<RefCell<Page>>> = std::iter::once(Rc::clone(&self.root))
+            .chain(self.root.borrow().children.iter().cloned())
+            .collect();
+        let page_numbers: HashSet<u32> = live
+            .iter()
+            .map(|page| page.borrow().id() as u32 + 1)
+            .collect();
+        let last = page_numbers.iter().max().copied().unwrap_or(1);
+        for page in &live {
+            let page = page.borrow();
+            if !self.saved || page.is_dirty() {
+                pages.push((page.id() as u32 + 1, page.to_bytes()));
+            }
+        }
+        // removed pages are stored empty; the first save also empties
+        // the pages that a table saved to the file before left behind
+        let page_count = if self.saved {
+            pager.page_count().max(last + 1)
+        } else {
+            last + 1
+        };
+        let freed: Vec<u32> = if self.saved {
+            self.freed.iter().map(|id| *id as u32 + 1).collect()
+        } else {
+            (2..page_count).collect()
+        };
+        for page_number in freed {
+            if page_number < page_count && !page_numbers.contains(&page_number) {
+                let empty = Page::new(PageType::Leaf, page_number as usize - 1);
+                pages.push((page_number, empty.to_bytes()));
+            }
+        }
+        if pages.is_empty() {
+            return Ok(());
+        }
+        pages.sort_by_key(|(page_number, _)| *page_number);
+        pager.commit(&pages, page_count)?;
+
+        for page in live {
+            page.borrow_mut().mark_saved();
+        }
+        self.saved = true;
+        self.freed.clear();
+        Ok(())
+    }
+
+    /// reads the table that save wrote to the page file
+    /// only builtin collations can be restored
+    pub fn open(pager: &mut Pager) -> anyhow::Result<Table> {
+        if pager.page_count() < 2 {
+            return Err(anyhow::anyhow!("The page file does not contain a table"));
+        }
+        let schema = pager.read(0)?;
+        let (bytes_read, len) = varint::read(&schema);
+        if bytes_read + len as usize > schema.len() {
+            return Err(anyhow::anyhow!("The table description is corrupt"));
+        }
+        let schema = Record::from((len, &schema[bytes_read..bytes_read + len as usize]));
+        let text = |index: usize| {
+            schema
+                .values
+                .get(index)
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("The table description is corrupt"))
+        };
+
+        let mut table = Table::new(text(0)?);
+        for index in (2..schema.values.len()).step_by(3) {
+            let name = text(index)?;
+            table.add_column(&name, false);
+            table.set_affinity(&name, Affinity::from_type_name(&text(index + 1)?))?;
+            let collation = text(index + 2)?;
+            table.set_collation(
+                &name,
+                Collation::builtin(&collation)
+                    .ok_or_else(|| anyhow::anyhow!("No such collation sequence: {}", collation))?,
+            )?;
+        }
+        let mut root = Page::from_bytes(PageType::Root, 0, &pager.read(1)?)?;
+        // empty pages were removed from the table, the others are in rowid order
+        for page_number in 2..pager.page_count() {
+            let page = Page::from_bytes(
+                PageType::Leaf,
+                page_number as usize - 1,
+                &pager.read(page_number)?,
+            )?;
+            if page.n_records() > 0 {
+                root.children.push(Rc::new(RefCell::new(page)));
+            }
+        }
+        root.children
+            .sort_by_key(|page| page.borrow().first_rowid());
+        table.root = Rc::new(RefCell::new(root));
+        table.current_page = table
+            .root
+            .borrow()
+            .children
+            .last()
+            .map_or_else(|| Rc::clone(&table.root), Rc::clone);
+        table.page_ids = ThreadSafeIdGenerator::new(pager.page_count() as usize - 1);
+        table.row_ids = ThreadSafeIdGenerator::new(
+            schema.values.get(1).and_then(Value::as_i64).unwrap_or(1) as usize,
+        );
+        table.saved = true;
+        Ok(table)
+    }
+
+    /// the name, the next rowid, and name, affinity and collation per column
+    fn schema_page(&self) -> anyhow::Result<Vec<u8>> {
+        let mut schema = Record::default();
+        schema.add_value(Value::from_text(self.name.clone()));
+        schema.add_value(Value::from_i64(self.row_ids.peek() as i64));
+        for ((name, affinity), collation) in
+            self.cols.iter().zip(&self.affinities).zip(&self.collations)
+        {
+            schema.add_value(Value::from_text(name.clone()));
+            schema.add_value(Value::from_text(format!("{:?}", affinity)));
+            schema.add_value(Value::from_text(collation.name()));
+        }
+        let bytes: Vec<u8> = schema.into();
+        if bytes.len() > FILE_PAGE_SIZE {
+            return Err(anyhow::anyhow!(
+                "Table {} has too many columns to save",
+                self.name
+            ));
+        }
+        Ok(bytes)
+    }
+
     // work in progress
     // pub fn where_clause(&self, colindex: usize, value: &Value) -> Option<&Record> {
     //     for record in self.iter() {
@@ -142,10 +613,58 @@ impl Table {
     // }
 }
 
+/// the bytes of the record, as long as they fit in a page
+fn encode(record: &Record) -> anyhow::Result<Vec<u8>> {
+    let bytes: Vec<u8> = record.clone().into();
+    if bytes.len() > MAX_RECORD_SIZE {
+        return Err(anyhow::anyhow!(
+            "Record {} of {} bytes is larger than a page, which holds at most {}",
+            record.rowid,
+            bytes.len(),
+            MAX_RECORD_SIZE
+        ));
+    }
+    Ok(bytes)
+}
+
+/// a copy-on-write copy: the pages share their data with the original,
+/// a page is copied when either table changes it
+impl Clone for Table {
+    fn clone(&self) -> Self {
+        let root = Rc::new(RefCell::new(self.root.borrow().copy_on_write()));
+        let current_page = self
+            .root
+            .borrow()
+            .children
+            .iter()
+            .position(|page| Rc::ptr_eq(page, &self.current_page))
+            .map_or_else(
+                || Rc::clone(&root),
+                |index| Rc::clone(&root.borrow().children[index]),
+            );
+        Self {
+            name: self.name.clone(),
+            cols_by_name: self.cols_by_name.clone(),
+            cols: self.cols.clone(),
+            collations: self.collations.clone(),
+            affinities: self.affinities.clone(),
+            root,
+            views: self.views.clone(),
+            page_ids: self.page_ids.clone(),
+            row_ids: self.row_ids.clone(),
+            current_page,
+            constraints: self.constraints.clone(),
+            saved: self.saved,
+            freed: self.freed.clone(),
+        }
+    }
+}
+
 // iterators
 
 pub struct TableIter {
     root_page: Rc<RefCell<Page>>,
+    page_index: usize, // 0 is the root page, n is the n-th child page
     index: usize,
 }
 
@@ -153,8 +672,26 @@ impl Iterator for TableIter {
     type Item = Record;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.index += 1;
-        self.root_page.borrow().get(self.index - 1)
+        loop {
+            let root = self.root_page.borrow();
+            let record = if self.page_index == 0 {
+                root.get(self.index)
+            } else {
+                root.children
+                    .get(self.page_index - 1)?
+                    .borrow()
+                    .get(self.index)
+            };
+            if record.is_some() {
+                self.index += 1;
+                return record;
+            }
+            if self.page_index >= root.children.len() {
+                return None;
+            }
+            self.page_index += 1;
+            self.index = 0;
+        }
     }
 }
 
@@ -194,66 +731,248 @@ impl<'a> Iterator for OwnedColIter<'a> {
     }
 }
 
-/// keys for indexes. Allow compound keys
-// move to separate file
-#[derive(Debug)]
-pub struct Key {
-    values: Vec<Value>,
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// enough records for several pages
+    fn table() -> Table {
+        let mut table = Table::new("t");
+        table.add_column("n", false);
+        table.add_column("text", false);
+        for n in 0..1000 {
+            let mut record = Record::default();
+            record.add_value(n);
+            record.add_value(format!("value {}", n));
+            table.append(record).unwrap();
+        }
+        table
+    }
 
-impl Key {
-    pub fn integer(integer: usize) -> Self {
-        Self {
-            values: vec![integer.into()],
+    fn numbers(table: &Table) -> Vec<i64> {
+        table.iter().map(|r| r.get(0).as_i64().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_save_and_crash() {
+        let file = crate::pager::TempFile::new("table");
+        let path = file.path.clone();
+        let mut pager = Pager::open(&path).unwrap();
+        let mut table = table();
+        table.set_affinity("n", Affinity::Integer).unwrap();
+        table.save(&mut pager).unwrap();
+        pager.checkpoint().unwrap();
+        for rowid in 1..=500 {
+            let mut record = table.get(rowid).unwrap();
+            record.values[1] = Value::from_text(format!("updated value {}", rowid));
+            table.update(record).unwrap();
+        }
+        table.save(&mut pager).unwrap();
+        let wal_path = pager.wal_path().to_path_buf();
+        drop(pager);
+        let database = std::fs::read(&path).unwrap();
+        let log = std::fs::read(&wal_path).unwrap();
+
+        // the update is there completely or not at all
+        for cut in crate::pager::crash_offsets(log.len(), 257) {
+            std::fs::write(&path, &database).unwrap();
+            std::fs::write(&wal_path, &log[..cut]).unwrap();
+            let mut pager = Pager::open(&path).unwrap();
+            let mut opened = Table::open(&mut pager).unwrap();
+            assert_eq!(opened.name(), "t");
+            assert_eq!(opened.affinity(0), Affinity::Integer);
+            assert_eq!(numbers(&opened), (0..1000).collect::<Vec<i64>>());
+            let expected = if cut == log.len() {
+                "updated value 1"
+            } else {
+                "value 0"
+            };
+            assert_eq!(opened.get(1).unwrap().get(1).to_string(), expected);
+            assert_eq!(opened.get(1000).unwrap().get(1).to_string(), "value 999");
+            opened.append(Record::default()).unwrap();
+            assert_eq!(opened.iter().last().unwrap().rowid, 1001);
         }
     }
 
-    pub fn compound(keys: Vec<Value>) -> Self {
-        Self { values: keys }
+    #[test]
+    fn test_save_changed_pages() {
+        let file = crate::pager::TempFile::new("table-changes");
+        let mut pager = Pager::open(&file.path).unwrap();
+        let mut table = table();
+        table.save(&mut pager).unwrap();
+        pager.checkpoint().unwrap();
+        let pages = pager.page_count();
+
+        // one record changes: one page is written
+        let mut record = table.get(500).unwrap();
+        record.values[1] = Value::from_text("changed");
+        table.update(record).unwrap();
+        table.save(&mut pager).unwrap();
+        assert_eq!(pager.log_pages(), 1);
+        // nothing changes: nothing is written
+        table.save(&mut pager).unwrap();
+        assert_eq!(pager.log_pages(), 1);
+        // a new record also changes the next rowid in the description of the table
+        table.append(Record::default()).unwrap();
+        table.save(&mut pager).unwrap();
+        assert_eq!(pager.log_pages(), 3);
+
+        // removed pages are emptied, the split ones are stored at the end
+        for rowid in 100..=300 {
+            table.delete(rowid);
+        }
+        for rowid in 700..=720 {
+            let mut record = table.get(rowid).unwrap();
+            record.values[1] = Value::from_text("x".repeat(100));
+            table.update(record).unwrap();
+        }
+        table.save(&mut pager).unwrap();
+        pager.checkpoint().unwrap();
+        assert!(pager.page_count() > pages);
+        let opened = Table::open(&mut pager).unwrap();
+        let expected: Vec<u64> = table.iter().map(|r| r.rowid).collect();
+        assert_eq!(
+            opened.iter().map(|r| r.rowid).collect::<Vec<u64>>(),
+            expected
+        );
+        assert_eq!(opened.get(500).unwrap().get(1).to_string(), "changed");
+        assert_eq!(opened.get(710).unwrap().get(1).to_string(), "x".repeat(100));
+        assert!(opened.get(200).is_none());
+
+        // another table replaces the one in the file completely
+        let mut other = Table::new("other");
+        other.add_column("n", false);
+        other.append(Record::default()).unwrap();
+        other.save(&mut pager).unwrap();
+        let opened = Table::open(&mut pager).unwrap();
+        assert_eq!(opened.name(), "other");
+        assert_eq!(opened.iter().count(), 1);
     }
-}
-impl Ord for Key {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+
+    #[test]
+    fn test_multibyte_rowids() {
+        let table = table();
+        assert_eq!(numbers(&table), (0..1000).collect::<Vec<i64>>());
+        assert_eq!(table.get(1000).unwrap().get(1).to_string(), "value 999");
     }
-}
-impl Eq for Key {}
 
-impl PartialEq for Key {
-    fn eq(&self, other: &Self) -> bool {
-        if self.values.len() != other.values.len() {
-            false
-        } else {
-            for (l, r) in self.values.iter().zip(&other.values) {
-                if l != r {
-                    return false;
-                }
-            }
-            true
+    #[test]
+    fn test_insert_keeps_rowid() {
+        let mut copy = table().empty_copy();
+        for record in table().iter().filter(|record| record.rowid % 100 == 0) {
+            copy.insert(record).unwrap();
         }
+        assert_eq!(copy.get(500).unwrap().get(0).as_i64(), Some(499));
+        assert!(copy.get(1).is_none());
+        assert_eq!(copy.append(Record::default()).unwrap(), 1001);
     }
-}
 
-impl PartialOrd for Key {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let len = self.values.len().min(other.values.len());
-        for i in 0..len {
-            let ord = self
-                .values
-                .get(i)
-                .unwrap()
-                .partial_cmp(other.values.get(i).unwrap());
-
-            match ord {
-                Some(Ordering::Less) => {
-                    return Some(Ordering::Less);
-                }
-                Some(Ordering::Greater) => {
-                    return Some(Ordering::Greater);
-                }
-                _ => {}
-            }
+    #[test]
+    fn test_delete() {
+        let mut table = table();
+        let pages = table.root.borrow().children.len();
+        for rowid in 11..=990 {
+            assert!(table.delete(rowid));
+        }
+        assert!(!table.delete(500));
+        let expected: Vec<i64> = (0..10).chain(990..1000).collect();
+        assert_eq!(numbers(&table), expected);
+        // empty pages are removed
+        assert!(table.root.borrow().children.len() < pages);
+        assert_eq!(table.get(991).unwrap().get(0).as_i64(), Some(990));
+        assert!(table.get(500).is_none());
+        // inserts continue after the last rowid
+        let mut record = Record::default();
+        record.add_value(1000);
+        record.add_value("last");
+        table.append(record).unwrap();
+        assert_eq!(table.get(1001).unwrap().get(1).to_string(), "last");
+    }
+
+    #[test]
+    fn test_update() {
+        let mut table = table();
+        table.create_index("by_text", "text").unwrap();
+        // records grow until the pages have to be split
+        for rowid in 1..=1000 {
+            let mut record = table.get(rowid).unwrap();
+            record.values[1] = Value::from_text("x".repeat(20 + rowid as usize % 50));
+            assert!(table.update(record).unwrap());
         }
-        None
+        let mut record = table.get(3).unwrap();
+        record.values[1] = Value::from_text("short");
+        assert!(table.update(record).unwrap());
+        assert_eq!(numbers(&table), (0..1000).collect::<Vec<i64>>());
+        for rowid in 1..=1000 {
+            assert_eq!(table.get(rowid).unwrap().rowid, rowid);
+        }
+        assert_eq!(table.get(3).unwrap().get(1).to_string(), "short");
+        let missing = Record {
+            rowid: 2000,
+            ..Default::default()
+        };
+        assert!(!table.update(missing).unwrap());
+
+        // the index finds the new values only
+        let scan = crate::index::IndexScan {
+            index: "by_text".to_string(),
+            equal: vec![Value::from_text("short")],
+            lower: std::ops::Bound::Unbounded,
+            upper: std::ops::Bound::Unbounded,
+            descending: false,
+        };
+        assert_eq!(numbers(&table.scan_index(&scan).unwrap()), vec![2]);
+        let scan = crate::index::IndexScan {
+            equal: vec![Value::from_text("value 2")],
+            ..scan
+        };
+        assert!(numbers(&table.scan_index(&scan).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_clone_copies_on_write() {
+        let table = table();
+        let mut copy = table.clone();
+        let mut record = copy.get(500).unwrap();
+        record.values[1] = Value::from_text("changed");
+        assert!(copy.update(record).unwrap());
+        assert_eq!(table.get(500).unwrap().get(1).to_string(), "value 499");
+        assert_eq!(copy.get(500).unwrap().get(1).to_string(), "changed");
+        // only the page that was written has data of its own
+        let pages = &table.root.borrow().children;
+        let copies = &copy.root.borrow().children;
+        assert_eq!(pages.len(), copies.len());
+        let written: Vec<bool> = pages
+            .iter()
+            .zip(copies)
+            .map(|(page, copy)| !page.borrow().shares_data(&copy.borrow()))
+            .collect();
+        assert_eq!(written.iter().filter(|written| **written).count(), 1);
+    }
+
+    #[test]
+    fn test_record_larger_than_page() {
+        let mut table = table();
+        // a record that fills a page on its own gets a page of its own
+        let mut record = table.get(500).unwrap();
+        record.values[1] = Value::from_text("x".repeat(4000));
+        assert!(table.update(record.clone()).unwrap());
+        assert_eq!(table.get(500).unwrap().get(1).to_string().len(), 4000);
+        assert_eq!(numbers(&table), (0..1000).collect::<Vec<i64>>());
+
+        let mut record = table.get(501).unwrap();
+        record.values[1] = Value::from_text("x".repeat(5000));
+        assert!(table.update(record.clone()).is_err());
+        assert_eq!(table.get(501).unwrap().get(1).to_string(), "value 500");
+        record.rowid = 2000;
+        assert!(table.insert(record.clone()).is_err());
+        record.values[0] = Value::from_text("x".repeat(40000));
+        record.values[1] = Value::from_text("x".repeat(40000));
+        assert!(table.insert(record.clone()).is_err());
+        record.rowid = 501;
+        assert!(table.update(record).is_err());
+        assert!(table.get(2000).is_none());
+        // the failed insert does not use up a rowid
+        assert_eq!(table.append(Record::default()).unwrap(), 1001);
     }
 }
@@ -0,0 +1,150 @@
+use std::collections::BTreeSet;
+
+use anyhow::anyhow;
+
+use crate::affinity::Affinity;
+use crate::collation::Collated;
+use crate::record::Record;
+use crate::sql::ast::SetOperator;
+use crate::table::Table;
+
+impl Table {
+    /// appends the records of other to those of self
+    /// the records of other get new rowids, so that they do not collide with those of self
+    /// both tables must have the same columns, in any order
+    /// the result has the name, columns and collations of self
+    pub fn union(&self, other: &Table) -> anyhow::Result<Table> {
+        if self.cols.len() != other.cols.len() {
+            return Err(anyhow!(
+                "Cannot union tables with {} and {} columns",
+                self.cols.len(),
+                other.cols.len()
+            ));
+        }
+        let indexes = self
+            .cols
+            .iter()
+            .map(|name| {
+                other.find_index(name).ok_or_else(|| {
+                    anyhow!("Cannot union tables: no column {} in the other table", name)
+                })
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        let mut result = self.empty_copy();
+        for record in self.iter() {
+            result.insert(record)?;
+        }
+        for record in other.iter() {
+            let mut appended = Record::default();
+            for index in &indexes {
+                appended.add_value(record.get(*index).clone());
+            }
+            result.append(appended)?;
+        }
+        Ok(result)
+    }
+
+    /// the result of a compound select: the records of self combined with those of other
+    /// columns are matched by position; rows are compared using the collations of self
+    /// columns with a declared type on both sides must have the same one
+    /// records of other get new rowids, so that they do not collide with those of self
+    pub(crate) fn combine(&self, other: &Table, operator: SetOperator) -> anyhow::Result<Table> {
+        if self.cols.len() != other.cols.len() {
+            return Err(anyhow!(
+                "Selects to the left and right of {} do not have the same number of result columns",
+                operator
+            ));
+        }
+        for index in 0..self.cols.len() {
+            let (left, right) = (self.affinity(index), other.affinity(index));
+            if left != Affinity::Blob && right != Affinity::Blob && left != right {
+                return Err(anyhow!(
+                    "Selects to the left and right of {} have different types in column {}",
+                    operator,
+                    self.cols[index]
+                ));
+            }
+        }
+        let mut result = self.empty_copy();
+        if operator == SetOperator::UnionAll {
+            for record in self.iter() {
+                result.insert(record)?;
+            }
+            for record in other.iter() {
+                result.append(record)?;
+            }
+            return Ok(result);
+        }
+        let right: BTreeSet<Vec<Collated>> = other.iter().map(|r| self.key(&r)).collect();
+        let mut seen = BTreeSet::new();
+        for record in self.iter() {
+            let key = self.key(&record);
+            let wanted = match operator {
+                SetOperator::Intersect => right.contains(&key),
+                SetOperator::Except => !right.contains(&key),
+                _ => true,
+            };
+            if wanted && seen.insert(key) {
+                result.insert(record)?;
+            }
+        }
+        if operator == SetOperator::Union {
+            for record in other.iter() {
+                if seen.insert(self.key(&record)) {
+                    result.append(record)?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// the record as a key, compared by the collations of the columns
+    fn key(&self, record: &Record) -> Vec<Collated> {
+        (0..self.cols.len())
+            .map(|index| Collated {
+                value: record.get(index).clone(),
+                collation: self.collation(index).clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_union() {
+        let left = Table::from_csv("a,b\n1,x\n2,y\n", Some(","));
+        let right = Table::from_csv("b,a\nz,3\n", Some(","));
+        let result = left.union(&right).unwrap();
+        assert_eq!(result.cols, vec!["a", "b"]);
+        let rows: Vec<String> = result
+            .iter()
+            .map(|r| format!("{}{}", r.get(0), r.get(1)))
+            .collect();
+        assert_eq!(rows, vec!["1x", "2y", "3z"]);
+
+        let other = Table::from_csv("a,c\n3,z\n", Some(","));
+        assert!(left.union(&other).is_err());
+        let wider = Table::from_csv("a,b,c\n3,z,0\n", Some(","));
+        assert!(left.union(&wider).is_err());
+    }
+
+    #[test]
+    fn test_combine_types() {
+        let mut left = Table::from_csv("a\n1\n", Some(","));
+        let mut right = Table::from_csv("a\nx\n", Some(","));
+        // without declared types the values may differ
+        assert!(left.combine(&right, SetOperator::Union).is_ok());
+        // declared types are compared also when there are no rows
+        left.set_affinity("a", Affinity::Integer).unwrap();
+        right.set_affinity("a", Affinity::Text).unwrap();
+        assert!(left
+            .empty_copy()
+            .combine(&right.empty_copy(), SetOperator::Union)
+            .is_err());
+        right.set_affinity("a", Affinity::Integer).unwrap();
+        assert!(left.combine(&right, SetOperator::Except).is_ok());
+    }
+}
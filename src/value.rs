@@ -1,63 +1,174 @@
-use std::{cmp::Ordering, fmt::Display};
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 
+use crate::datetime::{self, Interval, Temporal};
+use crate::decimal::{self, Decimal};
 use crate::varint;
 use anyhow::anyhow;
 use byteorder::{BigEndian, ByteOrder};
 
-#[derive(Debug, Clone, PartialEq, Eq, Ord)]
+#[derive(Debug, Clone)]
 pub struct Value {
     pub(crate) datatype: u64,
     pub(crate) datatype_bytes: Vec<u8>,
     pub(crate) data: Vec<u8>,
 }
 
+/// total order across storage classes, like SQLite: NULL < numbers < text < blob
+/// with dates/times and intervals after the numbers
+/// integers and floats are compared numerically (3 == 3.0), text and blobs bytewise
+/// NaN sorts before all other numbers
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (l, r) = (self.storage_class(), other.storage_class());
+        if l != r {
+            return l.cmp(&r);
+        }
+        match (self.numeric(), other.numeric()) {
+            (Some(l), Some(r)) => l.cmp(&r),
+            _ if l == StorageClass::Null => Ordering::Equal,
+            _ if l == StorageClass::Temporal || l == StorageClass::Interval => {
+                datetime::compare(self, other).unwrap_or_else(|| self.data.cmp(&other.data))
+            }
+            _ => self.data.cmp(&other.data),
+        }
+    }
+}
+
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.datatype {
-            13.. if self.datatype % 2 == 1 => Some(self.to_string().cmp(&other.to_string())),
-            12.. if self.datatype % 2 == 0 => None, // can't use blob as key
-            8..=9 => integer_cmp(self, other),
-            7 => {
-                let l: anyhow::Result<f64> = self.into();
-                let r: anyhow::Result<f64> = other.into();
-                if let Ok(l) = l {
-                    if let Ok(r) = r {
-                        l.partial_cmp(&r)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+/// consistent with Eq: a float with an integral value hashes like the integer,
+/// a decimal like the integer or float with the same value
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let class = self.storage_class();
+        class.hash(state);
+        match self.numeric() {
+            Some(Numeric::Integer(i)) => i.hash(state),
+            Some(Numeric::Float(f)) if f.is_nan() => {}
+            Some(Numeric::Float(f)) => match float_as_integer(f) {
+                Some(i) => i.hash(state),
+                None => f.to_bits().hash(state),
+            },
+            // like the integer or float it equals, if there is one
+            Some(Numeric::Decimal(d)) => match d.to_i64() {
+                Some(i) => i.hash(state),
+                None if d.cmp_f64(d.to_f64()) == Ordering::Equal => {
+                    d.to_f64().to_bits().hash(state)
                 }
-            }
-            1..=6 => integer_cmp(self, other),
-            0 => None,
-            _ => None,
+                None => d.normalized().hash(state),
+            },
+            None if class == StorageClass::Null => {}
+            None => match (Temporal::from_value(self), Interval::from_value(self)) {
+                (Some(temporal), _) => temporal.sort_key().hash(state),
+                (_, Some(interval)) => interval.sort_key().hash(state),
+                _ => self.data.hash(state),
+            },
         }
     }
 }
 
-fn integer_cmp(l: &Value, r: &Value) -> Option<Ordering> {
-    let l: anyhow::Result<i64> = l.into();
-    let r: anyhow::Result<i64> = r.into();
-    if let Ok(l) = l {
-        if let Ok(r) = r {
-            l.partial_cmp(&r)
-        } else {
-            None
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum StorageClass {
+    Null,
+    Numeric,
+    Temporal,
+    Interval,
+    Text,
+    Blob,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Numeric {
+    Integer(i64),
+    Float(f64),
+    Decimal(Decimal),
+}
+
+impl Ord for Numeric {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (*self, *other) {
+            (Numeric::Integer(l), Numeric::Integer(r)) => l.cmp(&r),
+            (Numeric::Float(l), Numeric::Float(r)) => match (l.is_nan(), r.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                _ => l.partial_cmp(&r).unwrap_or(Ordering::Equal), // -0.0 == 0.0
+            },
+            (Numeric::Integer(l), Numeric::Float(r)) => compare_integer_float(l, r),
+            (Numeric::Float(l), Numeric::Integer(r)) => compare_integer_float(r, l).reverse(),
+            (Numeric::Decimal(l), Numeric::Decimal(r)) => l.cmp(&r),
+            (Numeric::Decimal(l), Numeric::Integer(r)) => l.cmp(&Decimal::from(r)),
+            (Numeric::Integer(l), Numeric::Decimal(r)) => Decimal::from(l).cmp(&r),
+            (Numeric::Decimal(l), Numeric::Float(r)) => l.cmp_f64(r),
+            (Numeric::Float(l), Numeric::Decimal(r)) => r.cmp_f64(l).reverse(),
         }
-    } else {
-        None
     }
 }
 
+impl PartialOrd for Numeric {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Numeric {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Numeric {}
+
+/// exact comparison, without converting the integer to a float and losing precision
+fn compare_integer_float(i: i64, f: f64) -> Ordering {
+    if f.is_nan() {
+        return Ordering::Greater;
+    }
+    if f >= 9223372036854775808.0 {
+        return Ordering::Less;
+    }
+    if f < -9223372036854775808.0 {
+        return Ordering::Greater;
+    }
+    let truncated = f.trunc();
+    match i.cmp(&(truncated as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(f - truncated)).unwrap_or(Ordering::Equal),
+        ordering => ordering,
+    }
+}
+
+/// the integer with the same value as the float, if there is one
+fn float_as_integer(f: f64) -> Option<i64> {
+    (f.fract() == 0.0 && (-9223372036854775808.0..9223372036854775808.0).contains(&f))
+        .then_some(f as i64)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Datatype {
     Text,
     Blob,
     Integer,
     Float,
+    Decimal,
     Null,
+    Date,
+    Time,
+    Timestamp,
+    Interval,
 }
 
 impl Value {
@@ -69,8 +180,9 @@ impl Value {
         }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn from_raw(datatype_bytes: Vec<u8>, data: Vec<u8>) -> Self {
-        Self{
+        Self {
             datatype: varint::read(&datatype_bytes).1,
             datatype_bytes,
             data,
@@ -78,8 +190,8 @@ impl Value {
     }
 
     /// get the length of the encoding of the value
-    pub fn bytes_len(&self) -> u16 {
-        (self.datatype_bytes.len() + self.data.len()) as u16
+    pub fn bytes_len(&self) -> usize {
+        self.datatype_bytes.len() + self.data.len()
     }
 
     // can this be a constant?
@@ -105,6 +217,17 @@ impl Value {
         Self::new(datatype, data)
     }
 
+    /// booleans are stored as the integers 0 and 1 (serial types 8 and 9), like in SQLite
+    pub fn from_bool(value: bool) -> Self {
+        Self::new(if value { 9 } else { 8 }, vec![])
+    }
+
+    /// sql truth value: None for NULL, numbers are true when not zero,
+    /// text is converted to a number first
+    pub fn as_bool(&self) -> Option<bool> {
+        crate::vm::eval::truth(self)
+    }
+
     pub fn from_text(value: impl Into<String>) -> Self {
         let value: String = value.into();
         let datatype = (13 + value.len() * 2) as u64;
@@ -112,32 +235,103 @@ impl Value {
         Self::new(datatype, data)
     }
 
+    pub fn from_blob(value: &[u8]) -> Self {
+        Self::new((12 + value.len() * 2) as u64, value.to_vec())
+    }
+
+    /// the integer, None for other values
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.datatype {
+            1 => Some(BigEndian::read_int(&self.data, 1)),
+            2 => Some(BigEndian::read_int(&self.data, 2)),
+            3 => Some(BigEndian::read_int(&self.data, 3)),
+            4 => Some(BigEndian::read_int(&self.data, 4)),
+            5 => Some(BigEndian::read_int(&self.data, 6)),
+            6 => Some(BigEndian::read_int(&self.data, 8)),
+            8 => Some(0),
+            9 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// the number as a float (integers and decimals included), None for other values
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.numeric()? {
+            Numeric::Integer(i) => Some(i as f64),
+            Numeric::Float(f) => Some(f),
+            Numeric::Decimal(d) => Some(d.to_f64()),
+        }
+    }
+
+    /// the text, None for other values and text that is not valid utf-8
+    pub fn as_str(&self) -> Option<&str> {
+        if self.is_text() {
+            std::str::from_utf8(&self.data).ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.datatype == 0
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.datatype >= 13 && !self.datatype.is_multiple_of(2)
+    }
+
     pub fn datatype(&self) -> anyhow::Result<Datatype> {
         match self.datatype {
-            13.. if self.datatype % 2 == 1 => Ok(Datatype::Text),
-            12.. if self.datatype % 2 == 0 => Ok(Datatype::Blob),
+            13.. if !self.datatype.is_multiple_of(2) => Ok(Datatype::Text),
+            12.. if self.datatype.is_multiple_of(2) => Ok(Datatype::Blob),
             8..=9 => Ok(Datatype::Integer),
             7 => Ok(Datatype::Float),
             1..=6 => Ok(Datatype::Integer),
             0 => Ok(Datatype::Null),
+            decimal::DECIMAL => Ok(Datatype::Decimal),
+            datetime::TEMPORAL => match Temporal::from_value(self) {
+                Some(Temporal::Date(_)) => Ok(Datatype::Date),
+                Some(Temporal::Time(_)) => Ok(Datatype::Time),
+                Some(Temporal::Timestamp(_)) => Ok(Datatype::Timestamp),
+                None if Interval::from_value(self).is_some() => Ok(Datatype::Interval),
+                None => Err(anyhow!("Illegal date/time value")),
+            },
             _ => Err(anyhow!("Illegal type '{}'", self.datatype)),
         }
     }
 
+    pub(crate) fn storage_class(&self) -> StorageClass {
+        match self.datatype {
+            0 => StorageClass::Null,
+            1..=9 | decimal::DECIMAL => StorageClass::Numeric,
+            datetime::TEMPORAL if Interval::from_value(self).is_some() => StorageClass::Interval,
+            datetime::TEMPORAL => StorageClass::Temporal,
+            13.. if !self.datatype.is_multiple_of(2) => StorageClass::Text,
+            _ => StorageClass::Blob,
+        }
+    }
+
+    fn numeric(&self) -> Option<Numeric> {
+        match self.datatype {
+            7 => Some(Numeric::Float(BigEndian::read_f64(&self.data))),
+            decimal::DECIMAL => Decimal::from_value(self).map(Numeric::Decimal),
+            1..=9 => self.as_i64().map(Numeric::Integer),
+            _ => None,
+        }
+    }
+
     pub fn string_len(&self) -> usize {
         match self.datatype {
-            13.. if self.datatype % 2 == 1 => ((self.datatype - 13) >> 1) as usize,
-            12.. if self.datatype % 2 == 0 => ((self.datatype - 12) >> 1) as usize,
+            13.. if !self.datatype.is_multiple_of(2) => ((self.datatype - 13) >> 1) as usize,
+            12.. if self.datatype.is_multiple_of(2) => ((self.datatype - 12) >> 1) as usize,
             8..=9 => 1,
             7 => {
                 let f = BigEndian::read_f64(&self.data);
                 format!("{}", f).len()
             }
-            1..=6 => {
-                let f = BigEndian::read_i64(&self.data);
-                format!("{}", f).len()
-            }
+            1..=6 => format!("{}", self.as_i64().unwrap_or_default()).len(),
             0 => 4, // NULL
+            datetime::TEMPORAL | decimal::DECIMAL => self.to_string().len(),
             _ => 0, // should be Err
         }
     }
@@ -145,8 +339,8 @@ impl Value {
 
 fn int_datatype(encoded_len: usize) -> u64 {
     match encoded_len {
-        ..5 => encoded_len as u64,
-        ..7 => 5,
+        0..5 => encoded_len as u64,
+        5..7 => 5,
         _ => 6,
     }
 }
@@ -166,11 +360,11 @@ fn encode(v: i64, len: usize) -> Vec<u8> {
 fn encoding_len(v: i64) -> usize {
     let u = if v < 0 { !v } else { v };
     match u {
-        ..128 => 1,
-        ..32768 => 2,
-        ..8388607 => 3,
-        ..2147483648 => 4,
-        ..140737488355327 => 6,
+        0..128 => 1,
+        128..32768 => 2,
+        32768..8388607 => 3,
+        8388607..2147483648 => 4,
+        2147483648..140737488355327 => 6,
         _ => 8,
     }
 }
@@ -182,116 +376,177 @@ impl Display for Value {
     }
 }
 
-impl Into<Value> for &str {
-    fn into(self) -> Value {
-        if let Ok(f) = self.parse::<f64>() {
-            Value::from_f64(f)
-        } else if let Ok(i) = self.parse::<i64>() {
+/// integers, decimals ('19.99', exact) and floats ('1e-3') are recognized, other values are text
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        if let Ok(i) = value.parse::<i64>() {
             Value::from_i64(i)
+        } else if let Some(d) = Decimal::parse(value) {
+            d.into()
+        } else if let Ok(f) = value.parse::<f64>() {
+            Value::from_f64(f)
         } else {
-            Value::from_text(strip_quotes(self))
+            Value::from_text(strip_quotes(value))
         }
     }
 }
 
-impl Into<Value> for String {
-    fn into(self) -> Value {
-        if let Ok(i) = self.parse::<i64>() {
-            Value::from_i64(i)
-        } else if let Ok(f) = self.parse::<f64>() {
-            Value::from_f64(f)
-        } else {
-            Value::from_text(strip_quotes(self))
-        }
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::from(value.as_str())
     }
 }
 
-impl Into<Value> for f64 {
-    fn into(self) -> Value {
-        Value::from_f64(self)
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::from_bool(value)
     }
 }
 
-impl Into<Value> for i64 {
-    fn into(self) -> Value {
-        Value::from_i64(self)
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::from_f64(value)
     }
 }
 
-impl Into<Value> for usize {
-    fn into(self) -> Value {
-        Value::from_i64(self as i64)
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::from_i64(value)
     }
 }
 
-impl Into<Value> for i32 {
-    fn into(self) -> Value {
-        Value::from_i64(self as i64)
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Value::from_blob(value)
     }
 }
 
-impl Into<String> for Value {
-    fn into(self) -> String {
-        (&self).into()
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::from_blob(&value)
     }
 }
 
-impl Into<String> for &Value {
-    fn into(self) -> String {
-        match self.datatype {
-            13.. if self.datatype % 2 == 1 => String::from_utf8_lossy(&self.data).into_owned(), // valid?
-            12.. if self.datatype % 2 == 0 => String::from_utf8_lossy(&self.data).into_owned(),
+/// None is NULL
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or_else(Value::null, Into::into)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::from_i64(value as i64)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::from_i64(value as i64)
+    }
+}
+
+impl From<Value> for String {
+    fn from(value: Value) -> String {
+        (&value).into()
+    }
+}
+
+impl From<&Value> for String {
+    fn from(value: &Value) -> String {
+        match value.datatype {
+            13.. if !value.datatype.is_multiple_of(2) => {
+                String::from_utf8_lossy(&value.data).into_owned()
+            } // valid?
+            12.. if value.datatype.is_multiple_of(2) => {
+                String::from_utf8_lossy(&value.data).into_owned()
+            }
             8 => "0".to_string(),
             9 => "1".to_string(),
-            7 => {
-                let f: anyhow::Result<f64> = self.into();
-                format!("{}", f.unwrap())
-            }
-            1..=6 => {
-                let i: anyhow::Result<i64> = self.into();
-                format!("{}", i.unwrap())
+            7 if value.data.len() == 8 => format!("{}", BigEndian::read_f64(&value.data)),
+            1..=6 => value
+                .as_i64()
+                .map_or_else(|| "Illegal integer".to_string(), |i| i.to_string()),
+            0 => "NULL".to_string(), // NULL
+            decimal::DECIMAL => Decimal::from_value(value)
+                .map_or_else(|| "Illegal decimal".to_string(), |d| d.to_string()),
+            datetime::TEMPORAL => {
+                match (Temporal::from_value(value), Interval::from_value(value)) {
+                    (Some(temporal), _) => temporal.to_string(),
+                    (_, Some(interval)) => interval.to_string(),
+                    _ => "Illegal date/time".to_string(),
+                }
             }
-            0 => "NULL".to_string(),                          // NULL
-            _ => format!("Illegal type '{}'", self.datatype), // should be Err
+            _ => format!("Illegal type '{}'", value.datatype), // should be Err
         }
     }
 }
 
-impl Into<anyhow::Result<f64>> for Value {
-    fn into(self) -> anyhow::Result<f64> {
-        (&self).into()
+/// NULL is an error, use Option<T> for values that can be NULL
+impl TryFrom<&Value> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> anyhow::Result<i64> {
+        value
+            .as_i64()
+            .ok_or_else(|| anyhow!("not an integer: {}", value))
     }
 }
 
-impl Into<anyhow::Result<f64>> for &Value {
-    fn into(self) -> anyhow::Result<f64> {
-        if self.datatype == 7 {
-            Ok(BigEndian::read_f64(&self.data))
-        } else {
-            Err(anyhow!("not a float"))
+impl TryFrom<&Value> for f64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> anyhow::Result<f64> {
+        value
+            .as_f64()
+            .ok_or_else(|| anyhow!("not a number: {}", value))
+    }
+}
+
+/// numbers are true when not zero, other values are an error
+impl TryFrom<&Value> for bool {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> anyhow::Result<bool> {
+        match value.numeric() {
+            Some(_) => Ok(value.as_bool() == Some(true)),
+            None => Err(anyhow!("not a boolean: {}", value)),
         }
     }
 }
 
-impl Into<anyhow::Result<i64>> for Value {
-    fn into(self) -> anyhow::Result<i64> {
-        (&self).into()
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &'a Value) -> anyhow::Result<&'a str> {
+        value.as_str().ok_or_else(|| anyhow!("not text: {}", value))
     }
 }
 
-impl Into<anyhow::Result<i64>> for &Value {
-    fn into(self) -> anyhow::Result<i64> {
-        match self.datatype {
-            0 => Err(anyhow!("value is NULL")),
-            1 => Ok(BigEndian::read_int(&self.data, 1) as i64),
-            2 => Ok(BigEndian::read_int(&self.data, 2) as i64),
-            3 => Ok(BigEndian::read_int(&self.data, 3) as i64),
-            4 => Ok(BigEndian::read_int(&self.data, 4) as i64),
-            5 => Ok(BigEndian::read_int(&self.data, 6) as i64),
-            6 => Ok(BigEndian::read_int(&self.data, 8) as i64),
-            8 => Ok(0),
-            9 => Ok(1),
-            _ => Err(anyhow!("not an integer")),
+/// the bytes of a blob or text
+impl TryFrom<&Value> for Vec<u8> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> anyhow::Result<Vec<u8>> {
+        match value.storage_class() {
+            StorageClass::Blob | StorageClass::Text => Ok(value.data.clone()),
+            _ => Err(anyhow!("not a blob: {}", value)),
+        }
+    }
+}
+
+/// None for NULL
+impl<'a, T> TryFrom<&'a Value> for Option<T>
+where
+    T: TryFrom<&'a Value, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: &'a Value) -> anyhow::Result<Option<T>> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            T::try_from(value).map(Some)
         }
     }
 }
@@ -380,4 +635,88 @@ mod test {
         assert_eq!(i.string_len(), 11);
         assert_eq!(i.datatype().unwrap(), Datatype::Text);
     }
+
+    #[test]
+    fn test_order_across_storage_classes() {
+        let blob = Value::new(14, vec![0]);
+        let mut values: Vec<Value> = vec![
+            blob.clone(),
+            "a".into(),
+            2.5.into(),
+            Value::null(),
+            3.into(),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                Value::null(),
+                2.5.into(),
+                3.into(),
+                Value::from_text("a"),
+                blob
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_float_equality() {
+        let i: Value = 3.into();
+        let f: Value = 3.0.into();
+        assert_eq!(i, f);
+        let mut hashes = std::collections::HashSet::new();
+        hashes.insert(i);
+        assert!(hashes.contains(&f));
+        // exact, 2^53 + 1 is not representable as a float
+        let large = Value::from_i64(9007199254740993);
+        assert!(large > Value::from_f64(9007199254740992.0));
+        assert!(Value::from_f64(f64::NAN) < Value::from_i64(i64::MIN));
+    }
+
+    #[test]
+    fn test_decimal_float_equality() {
+        let decimal = |text: &str| Value::from(Decimal::parse(text).unwrap());
+        let mut hashes = std::collections::HashSet::new();
+        hashes.insert(decimal("2.50"));
+        assert_eq!(decimal("2.50"), Value::from_f64(2.5));
+        assert!(hashes.contains(&Value::from_f64(2.5)));
+        // 0.1 is not representable as a float, the nearest float is slightly larger
+        assert!(decimal("0.1") < Value::from_f64(0.1));
+        assert!(decimal("-0.1") > Value::from_f64(-0.1));
+        hashes.insert(decimal("0.1"));
+        assert!(!hashes.contains(&Value::from_f64(0.1)));
+        assert!(hashes.contains(&decimal("0.100")));
+        // transitive: the float is rounded down from 2^53 + 1
+        let large = decimal("9007199254740993.0");
+        assert_eq!(large, Value::from_i64(9007199254740993));
+        assert!(large > Value::from_f64(9007199254740992.0));
+        assert!(decimal("1.0") < Value::from_f64(f64::INFINITY));
+        // too far apart to align the scales
+        assert!(Value::from(Decimal::new(i128::MAX, 0)) > Value::from(Decimal::new(1, 38)));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let answer = Value::from(42);
+        assert_eq!(i64::try_from(&answer).unwrap(), 42);
+        assert_eq!(f64::try_from(&answer).unwrap(), 42.0);
+        assert!(bool::try_from(&answer).unwrap());
+        assert!(<&str>::try_from(&answer).is_err());
+
+        let text = Value::from_text("abc");
+        assert_eq!(text.as_str(), Some("abc"));
+        assert_eq!(<&str>::try_from(&text).unwrap(), "abc");
+        assert!(i64::try_from(&text).is_err());
+
+        let blob = Value::from(&[1u8, 2][..]);
+        assert_eq!(Vec::<u8>::try_from(&blob).unwrap(), vec![1, 2]);
+        assert_eq!(blob.as_str(), None);
+
+        let null = Value::from(None::<i64>);
+        assert!(null.is_null());
+        assert_eq!(Option::<i64>::try_from(&null).unwrap(), None);
+        assert_eq!(Option::<i64>::try_from(&answer).unwrap(), Some(42));
+        assert!(i64::try_from(&null).is_err());
+        assert_eq!(Value::from(Some(true)), Value::from(1));
+    }
 }
@@ -2,7 +2,6 @@ const SLOT_2_0: u64 = 0x001fc07f;
 const SLOT_4_2_0: u64 = 0xf01fc07f;
 
 /// varints as implemented in `SQLite`
-
 pub fn write(value: u64) -> Vec<u8> {
     let mut v = value;
     if (v & ((0xff00_0000) << 32)) == 0 {
@@ -50,7 +49,7 @@ pub fn read(data: &[u8]) -> (usize, u64) {
     }
 
     a &= SLOT_2_0;
-    b = b << 14;
+    b <<= 14;
     b |= data[3] as u64;
     if (b & 0x80) == 0 {
         b &= SLOT_2_0;
@@ -60,13 +59,13 @@ pub fn read(data: &[u8]) -> (usize, u64) {
 
     b &= SLOT_2_0;
     let mut s = a;
-    a = a << 14;
+    a <<= 14;
     let m = data[4] as u64;
     a |= m;
     if (a & 0x80) == 0 {
-        b = b << 7;
+        b <<= 7;
         a |= b;
-        s = s >> 18;
+        s >>= 18;
         return (5,(s << 32) | a);
     }
 
@@ -75,18 +74,18 @@ pub fn read(data: &[u8]) -> (usize, u64) {
     if (b & 0x80) == 0 {
         a &= SLOT_2_0;
         a = (a << 7) | b;
-        s = s >> 18;
+        s >>= 18;
         return (6,(s << 32) | a);
     }
 
-    a = a << 14;
+    a <<= 14;
     a |= data[6] as u64;
     if (a & 0x80) == 0 {
         a &= SLOT_4_2_0;
         b &= SLOT_2_0;
-        b = b << 7;
+        b <<= 7;
         a |= b;
-        s = s >> 11;
+        s >>= 11;
         return (7,(s << 32) | a);
     }
 
@@ -95,19 +94,19 @@ pub fn read(data: &[u8]) -> (usize, u64) {
     if (b & 0x80) == 0 {
         b &= SLOT_4_2_0;
         a = (a << 7) | b;
-        s = s >> 14;
+        s >>= 14;
         return (8,(s << 32) | a);
     }
 
-    a = a << 15;
+    a <<= 15;
     a |= data[8] as u64;
     b &= SLOT_2_0;
-    b = b << 8;
+    b <<= 8;
     a |= b;
-    s = s << 14;
+    s <<= 14;
     b = m;
     b &= 0x7f;
-    b = b >> 3;
+    b >>= 3;
     s |= b;
     (9,(s << 32) | a)
 }
@@ -0,0 +1,481 @@
+use anyhow::anyhow;
+
+use crate::database::Database;
+use crate::groupby::{is_aggregate, Aggregate};
+use crate::record::Record;
+use crate::sql::ast::{Distinct, Expression, OrderingTerm, ResultColumn, Select, TableReference};
+use crate::table::Table;
+use crate::value::Datatype;
+
+use super::eval::evaluate;
+
+use super::planner::plan_index;
+use super::subquery::{self, outer_references};
+use super::Opcode;
+
+/// translates a select statement to vm code
+/// load table -> apply index -> filter (where) -> aggregate -> filter (having) -> sort
+/// -> distinct on -> project -> distinct -> limit
+/// without aggregation, sorting or distinct, the limit comes before the projection
+/// and the filter stops when it has found enough records
+pub(crate) fn compile(select: &Select, database: &Database) -> anyhow::Result<Vec<Opcode>> {
+    if !select.compound.is_empty() {
+        return compile_compound(select, database);
+    }
+    let grouped = is_grouped(select, database);
+    let mut order_by = select
+        .order_by
+        .iter()
+        .map(|term| resolve_result_column(term, &select.columns))
+        .collect::<anyhow::Result<Vec<OrderingTerm>>>()?;
+    // like order by terms, distinct on expressions can refer to the select list
+    let mut distinct_on = match &select.distinct {
+        Distinct::On(expressions) => expressions
+            .iter()
+            .map(|expression| {
+                let term = OrderingTerm {
+                    expression: expression.clone(),
+                    descending: false,
+                };
+                Ok(resolve_result_column(&term, &select.columns)?.expression)
+            })
+            .collect::<anyhow::Result<Vec<Expression>>>()?,
+        _ => vec![],
+    };
+
+    let where_clause = select
+        .where_clause
+        .as_ref()
+        .map(|where_clause| fold_constants(where_clause, database));
+
+    let mut code = vec![];
+    if let Some(TableReference::Function(name, args, _)) = &select.from {
+        code.push(Opcode::TableFunction(name.clone(), args.clone()));
+    }
+    if let Some(TableReference::Table(name, _)) = &select.from {
+        code.push(Opcode::LoadTable(name.clone()));
+        if let Some(table) = database.table(name) {
+            // the order of the records is lost in aggregation, so only the where clause counts then
+            let wanted_order = if grouped { &[][..] } else { &order_by[..] };
+            if let Some((scan, ordered)) = plan_index(table, where_clause.as_ref(), wanted_order) {
+                code.push(Opcode::ApplyIndex(scan));
+                if ordered && !grouped {
+                    order_by.clear();
+                }
+            }
+        }
+    }
+    let (offset, limit) = limits(select, database)?;
+    let limited = offset > 0 || limit.is_some();
+    let streaming = !grouped && order_by.is_empty() && select.distinct == Distinct::All;
+    if let Some(where_clause) = where_clause {
+        if contains_aggregate(&where_clause, database) {
+            return Err(anyhow!("Aggregate functions are not allowed in where"));
+        }
+        let needed = limit
+            .filter(|_| streaming)
+            .map(|limit| limit.saturating_add(offset));
+        code.push(Opcode::Filter(where_clause, needed));
+    }
+
+    let mut columns = select.columns.clone();
+    let mut having = select.having.clone();
+
+    if grouped {
+        let aggregate = aggregate(select, &order_by, &distinct_on, database)?;
+
+        // after aggregation, aggregated expressions are read from the columns named after them
+        let aggregated: Vec<Expression> = aggregate
+            .grouping
+            .iter()
+            .chain(&aggregate.columns)
+            .cloned()
+            .collect();
+        let rewrite = |expression: &Expression| {
+            expression.transform(&|e| {
+                aggregated
+                    .contains(e)
+                    .then(|| Expression::Column(e.to_string()))
+            })
+        };
+        columns = columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::Star => ResultColumn::Star,
+                ResultColumn::Expression(expression, _) => {
+                    ResultColumn::Expression(rewrite(expression), Some(column.name()))
+                }
+            })
+            .collect();
+        having = having.as_ref().map(rewrite);
+        order_by = order_by
+            .iter()
+            .map(|term| OrderingTerm {
+                expression: rewrite(&term.expression),
+                descending: term.descending,
+            })
+            .collect();
+        distinct_on = distinct_on.iter().map(rewrite).collect();
+
+        code.push(Opcode::Aggregate(aggregate));
+    } else if having.is_some() {
+        return Err(anyhow!("A group by clause is required before having"));
+    }
+
+    if let Some(having) = having {
+        code.push(Opcode::Filter(fold_constants(&having, database), None));
+    }
+    if !order_by.is_empty() {
+        code.push(Opcode::Sort(order_by));
+    }
+    if !distinct_on.is_empty() {
+        code.push(Opcode::Distinct(distinct_on));
+    }
+    // folded columns keep the name of their expression
+    let columns = columns
+        .iter()
+        .map(|column| match column {
+            ResultColumn::Star => ResultColumn::Star,
+            ResultColumn::Expression(expression, _) => {
+                ResultColumn::Expression(fold_constants(expression, database), Some(column.name()))
+            }
+        })
+        .collect();
+    if streaming && limited {
+        code.push(Opcode::Limit(offset, limit));
+    }
+    code.push(Opcode::Project(columns));
+    if select.distinct == Distinct::Rows {
+        code.push(Opcode::Distinct(vec![]));
+    }
+    if !streaming && limited {
+        code.push(Opcode::Limit(offset, limit));
+    }
+    Ok(code)
+}
+
+/// the first select -> combine with the next, from left to right -> sort
+/// the order by refers to the result columns
+fn compile_compound(select: &Select, database: &Database) -> anyhow::Result<Vec<Opcode>> {
+    let first = Select {
+        compound: vec![],
+        order_by: vec![],
+        limit: None,
+        offset: None,
+        ..select.clone()
+    };
+    let mut code = compile(&first, database)?;
+    for (operator, part) in &select.compound {
+        code.push(Opcode::Combine(*operator, part.clone()));
+    }
+    if !select.order_by.is_empty() {
+        let terms = select
+            .order_by
+            .iter()
+            .map(|term| {
+                let expression = resolve_result_column(term, &select.columns)?.expression;
+                let column = select.columns.iter().find(
+                    |column| matches!(column, ResultColumn::Expression(e, _) if *e == expression),
+                );
+                Ok(OrderingTerm {
+                    expression: match column {
+                        Some(column) => Expression::Column(column.name()),
+                        None => expression,
+                    },
+                    descending: term.descending,
+                })
+            })
+            .collect::<anyhow::Result<Vec<OrderingTerm>>>()?;
+        code.push(Opcode::Sort(terms));
+    }
+    let (offset, limit) = limits(select, database)?;
+    if offset > 0 || limit.is_some() {
+        code.push(Opcode::Limit(offset, limit));
+    }
+    Ok(code)
+}
+
+/// the number of records of the table in from that the select reads at most:
+/// offset + limit when it reads them in order, without filtering, aggregation, sorting or distinct
+pub(crate) fn needed_records(
+    select: &Select,
+    database: &Database,
+) -> anyhow::Result<Option<usize>> {
+    if !select.compound.is_empty()
+        || select.where_clause.is_some()
+        || !select.order_by.is_empty()
+        || select.distinct != Distinct::All
+        || is_grouped(select, database)
+    {
+        return Ok(None);
+    }
+    let (offset, limit) = limits(select, database)?;
+    Ok(limit.map(|limit| limit.saturating_add(offset)))
+}
+
+/// the offset and the limit of the select, which must be constant non-negative integers
+fn limits(select: &Select, database: &Database) -> anyhow::Result<(usize, Option<usize>)> {
+    let count = |expression: &Expression, clause: &str| match fold_constants(expression, database) {
+        Expression::Literal(value) if value.datatype()? == Datatype::Integer => {
+            usize::try_from(i64::try_from(&value)?)
+                .map_err(|_| anyhow!("{} must be a non-negative integer", clause))
+        }
+        _ => Err(anyhow!("{} must be a non-negative integer", clause)),
+    };
+    let offset = match &select.offset {
+        Some(offset) => count(offset, "Offset")?,
+        None => 0,
+    };
+    let limit = match &select.limit {
+        Some(limit) => Some(count(limit, "Limit")?),
+        None => None,
+    };
+    Ok((offset, limit))
+}
+
+/// replaces constant subexpressions by their value: operators and deterministic functions
+/// with constant operands. Expressions that fail are kept, to fail when they are evaluated
+fn fold_constants(expression: &Expression, database: &Database) -> Expression {
+    let (table, record) = (Table::new(""), Record::default());
+    expression.transform(&|e| {
+        if let Expression::InSelect(operand, select, negated) = e {
+            // x in (select ..), with a subquery that does not refer to the enclosing query,
+            // is evaluated once, as x in (value, ..)
+            if outer_references(select, database).is_empty() && !is_constant(operand, database) {
+                let list = subquery::values(select, database, &table, &record);
+                return list.ok().map(|list| {
+                    Expression::InList(
+                        Box::new(fold_constants(operand, database)),
+                        list.into_iter().map(Expression::Literal).collect(),
+                        *negated,
+                    )
+                });
+            }
+        }
+        if matches!(e, Expression::Literal(_)) || !is_constant(e, database) {
+            return None;
+        }
+        evaluate(e, database, &table, &record)
+            .ok()
+            .map(Expression::Literal)
+    })
+}
+
+fn is_constant(expression: &Expression, database: &Database) -> bool {
+    let mut constant = true;
+    expression.walk(&mut |e| {
+        constant &= match e {
+            Expression::Function(name, _) => database.functions().is_deterministic(name),
+            // a literal would lose the collation
+            Expression::Column(_) | Expression::Star | Expression::Collate(..) => false,
+            Expression::Subquery(select)
+            | Expression::Exists(select)
+            | Expression::InSelect(_, select, _) => outer_references(select, database).is_empty(),
+            _ => true,
+        }
+    });
+    constant
+}
+
+/// 'order by 2' and 'order by alias' refer to the select list
+fn resolve_result_column(
+    term: &OrderingTerm,
+    columns: &[ResultColumn],
+) -> anyhow::Result<OrderingTerm> {
+    let expression = match &term.expression {
+        Expression::Literal(value) if value.datatype()? == Datatype::Integer => {
+            let position = i64::try_from(value)?;
+            match columns.get((position - 1) as usize) {
+                Some(ResultColumn::Expression(expression, _)) if position > 0 => expression.clone(),
+                _ => return Err(anyhow!("Order by term {} is out of range", position)),
+            }
+        }
+        Expression::Column(name) => columns
+            .iter()
+            .find_map(|column| match column {
+                ResultColumn::Expression(expression, Some(alias)) if alias == name => {
+                    Some(expression.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| term.expression.clone()),
+        expression => expression.clone(),
+    };
+    Ok(OrderingTerm {
+        expression,
+        descending: term.descending,
+    })
+}
+
+fn is_grouped(select: &Select, database: &Database) -> bool {
+    !select.group_by.is_empty()
+        || select.columns.iter().any(|column| match column {
+            ResultColumn::Expression(expression, _) => contains_aggregate(expression, database),
+            ResultColumn::Star => false,
+        })
+        || select
+            .having
+            .as_ref()
+            .is_some_and(|having| contains_aggregate(having, database))
+        || select
+            .order_by
+            .iter()
+            .any(|term| contains_aggregate(&term.expression, database))
+}
+
+fn contains_aggregate(expression: &Expression, database: &Database) -> bool {
+    let mut found = false;
+    expression.walk(&mut |e| {
+        if let Expression::Function(name, _) = e {
+            found |= is_aggregate(name, database) || name == "grouping";
+        }
+    });
+    found
+}
+
+fn aggregate(
+    select: &Select,
+    order_by: &[OrderingTerm],
+    distinct_on: &[Expression],
+    database: &Database,
+) -> anyhow::Result<Aggregate> {
+    // expand all grouping elements to grouping sets: the cross product of the sets of the elements
+    let mut sets: Vec<Vec<Expression>> = vec![vec![]];
+    for element in &select.group_by {
+        let element_sets = element.grouping_sets();
+        sets = sets
+            .iter()
+            .flat_map(|set| {
+                element_sets.iter().map(move |element_set| {
+                    let mut combined = set.clone();
+                    combined.extend(element_set.iter().cloned());
+                    combined
+                })
+            })
+            .collect();
+    }
+
+    let mut grouping: Vec<Expression> = vec![];
+    for expression in sets.iter().flatten() {
+        if contains_aggregate(expression, database) {
+            return Err(anyhow!("Aggregate functions are not allowed in group by"));
+        }
+        if !grouping.contains(expression) {
+            grouping.push(expression.clone());
+        }
+    }
+    let sets = sets
+        .iter()
+        .map(|set| {
+            let mut indexes: Vec<usize> = vec![];
+            for expression in set {
+                let index = grouping.iter().position(|g| g == expression).unwrap();
+                if !indexes.contains(&index) {
+                    indexes.push(index);
+                }
+            }
+            indexes
+        })
+        .collect();
+
+    let mut columns = vec![];
+    let expressions = select
+        .columns
+        .iter()
+        .filter_map(|column| match column {
+            ResultColumn::Expression(expression, _) => Some(expression),
+            ResultColumn::Star => None,
+        })
+        .chain(&select.having)
+        .chain(order_by.iter().map(|term| &term.expression))
+        .chain(distinct_on);
+    for expression in expressions {
+        collect_aggregated(expression, &grouping, &mut columns, database)?;
+    }
+
+    Ok(Aggregate {
+        grouping,
+        sets,
+        columns,
+    })
+}
+
+/// finds the aggregate functions, grouping() calls and ungrouped columns in the expression
+fn collect_aggregated(
+    expression: &Expression,
+    grouping: &[Expression],
+    columns: &mut Vec<Expression>,
+    database: &Database,
+) -> anyhow::Result<()> {
+    if grouping.contains(expression) {
+        return Ok(());
+    }
+    match expression {
+        Expression::Function(name, args) if is_aggregate(name, database) || name == "grouping" => {
+            if name == "grouping" {
+                if args.is_empty() {
+                    return Err(anyhow!("grouping() needs at least one argument"));
+                }
+                if let Some(arg) = args.iter().find(|arg| !grouping.contains(arg)) {
+                    return Err(anyhow!("Argument {} of grouping() is not in group by", arg));
+                }
+            } else if args.iter().any(|arg| contains_aggregate(arg, database)) {
+                return Err(anyhow!("Aggregate function calls cannot be nested"));
+            }
+            if !columns.contains(expression) {
+                columns.push(expression.clone());
+            }
+        }
+        Expression::Column(_) => {
+            if !columns.contains(expression) {
+                columns.push(expression.clone());
+            }
+        }
+        Expression::Unary(_, operand)
+        | Expression::IsNull(operand, _)
+        | Expression::IsTruth(operand, ..)
+        | Expression::Collate(operand, _)
+        | Expression::Cast(operand, ..)
+        | Expression::Distinct(operand) => {
+            collect_aggregated(operand, grouping, columns, database)?
+        }
+        Expression::Binary(left, _, right) => {
+            collect_aggregated(left, grouping, columns, database)?;
+            collect_aggregated(right, grouping, columns, database)?;
+        }
+        Expression::Function(_, args) => {
+            for arg in args {
+                collect_aggregated(arg, grouping, columns, database)?;
+            }
+        }
+        Expression::Case(operand, branches, otherwise) => {
+            let branches = branches.iter().flat_map(|(when, then)| [when, then]);
+            for expression in operand
+                .iter()
+                .chain(otherwise)
+                .map(|e| &**e)
+                .chain(branches)
+            {
+                collect_aggregated(expression, grouping, columns, database)?;
+            }
+        }
+        Expression::InList(operand, list, _) => {
+            for expression in std::iter::once(&**operand).chain(list) {
+                collect_aggregated(expression, grouping, columns, database)?;
+            }
+        }
+        // the columns of the enclosing query that the subquery refers to
+        Expression::InSelect(_, select, _)
+        | Expression::Subquery(select)
+        | Expression::Exists(select) => {
+            if let Expression::InSelect(operand, ..) = expression {
+                collect_aggregated(operand, grouping, columns, database)?;
+            }
+            for name in outer_references(select, database) {
+                collect_aggregated(&Expression::Column(name), grouping, columns, database)?;
+            }
+        }
+        Expression::Literal(_) | Expression::Star => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use std::cmp::Ordering;
+
+use anyhow::anyhow;
+
+use crate::affinity::{self, Affinity};
+use crate::collation::Collation;
+use crate::database::Database;
+use crate::datetime::{self, Interval};
+use crate::decimal::Decimal;
+use crate::groupby::is_aggregate;
+use crate::json;
+use crate::record::Record;
+use crate::sql::ast::Expression;
+use crate::sql::tokens::TokenType;
+use crate::table::Table;
+use crate::value::{Datatype, Value};
+
+use super::subquery;
+
+/// evaluates the expression for a record in the table
+pub(crate) fn evaluate(
+    expression: &Expression,
+    database: &Database,
+    table: &Table,
+    record: &Record,
+) -> anyhow::Result<Value> {
+    match expression {
+        Expression::Literal(value) => Ok(value.clone()),
+        // table.column is found by its column name, when the table has no column with that name
+        Expression::Column(name) => table
+            .find_index(name)
+            .or_else(|| table.find_index(name.split_once('.')?.1))
+            .map(|index| record.get(index).clone())
+            .ok_or_else(|| anyhow!("No such column: {}", name)),
+        Expression::Unary(op, operand) => unary(*op, &evaluate(operand, database, table, record)?),
+        // x regexp p
+        Expression::Binary(left, TokenType::Regexp, right) => {
+            let args = [
+                evaluate(left, database, table, record)?,
+                evaluate(right, database, table, record)?,
+            ];
+            database.functions().call("regexp_like", &args)
+        }
+        Expression::Binary(left, op @ (TokenType::Arrow | TokenType::DoubleArrow), right) => {
+            let json = evaluate(left, database, table, record)?;
+            let path = evaluate(right, database, table, record)?;
+            json::extract_operator(&json, &path, *op == TokenType::Arrow)
+        }
+        Expression::Binary(left, op, right) => {
+            let collation = comparison_collation(left, right, database, table)?;
+            let left = evaluate(left, database, table, record)?;
+            let right = evaluate(right, database, table, record)?;
+            binary(*op, &left, &right, &collation)
+        }
+        Expression::IsNull(operand, negated) => {
+            let is_null = evaluate(operand, database, table, record)?.is_null();
+            Ok(boolean(is_null != *negated))
+        }
+        Expression::IsTruth(operand, wanted, negated) => {
+            // never NULL: NULL is neither true nor false
+            let truth = truth(&evaluate(operand, database, table, record)?);
+            Ok(boolean((truth == Some(*wanted)) != *negated))
+        }
+        Expression::Function(name, _) if is_aggregate(name, database) || name == "grouping" => {
+            Err(anyhow!("Misuse of aggregate function {}()", name))
+        }
+        Expression::Function(name, args) => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, database, table, record))
+                .collect::<anyhow::Result<Vec<Value>>>()?;
+            database.functions().call(name, &args)
+        }
+        Expression::Collate(operand, _) => evaluate(operand, database, table, record),
+        Expression::Cast(operand, type_name, fallible) => {
+            let value = evaluate(operand, database, table, record)?;
+            match affinity::cast(&value, type_name) {
+                Err(_) if *fallible => Ok(Value::null()),
+                result => result,
+            }
+        }
+        Expression::Case(operand, branches, otherwise) => {
+            let operand = match operand {
+                Some(operand) => Some((operand, evaluate(operand, database, table, record)?)),
+                None => None,
+            };
+            for (when, then) in branches {
+                let condition = evaluate(when, database, table, record)?;
+                // case x when a: x = a
+                let condition = match &operand {
+                    Some((expression, value)) => {
+                        let collation = comparison_collation(expression, when, database, table)?;
+                        binary(TokenType::Equals, value, &condition, &collation)?
+                    }
+                    None => condition,
+                };
+                if truth(&condition) == Some(true) {
+                    return evaluate(then, database, table, record);
+                }
+            }
+            match otherwise {
+                Some(otherwise) => evaluate(otherwise, database, table, record),
+                None => Ok(Value::null()),
+            }
+        }
+        Expression::InList(operand, list, negated) => {
+            let value = evaluate(operand, database, table, record)?;
+            let list = list
+                .iter()
+                .map(|e| evaluate(e, database, table, record))
+                .collect::<anyhow::Result<Vec<Value>>>()?;
+            let collation = expression_collation(operand, database, table)?;
+            Ok(negate(contains(&value, &list, &collation)?, *negated))
+        }
+        Expression::InSelect(operand, select, negated) => {
+            let value = evaluate(operand, database, table, record)?;
+            let list = subquery::values(select, database, table, record)?;
+            let collation = expression_collation(operand, database, table)?;
+            Ok(negate(contains(&value, &list, &collation)?, *negated))
+        }
+        // the first value of the first row, NULL when there are no rows
+        Expression::Subquery(select) => Ok(subquery::values(select, database, table, record)?
+            .into_iter()
+            .next()
+            .unwrap_or_else(Value::null)),
+        Expression::Exists(select) => {
+            let result = subquery::run(select, database, table, record)?;
+            Ok(boolean(result.iter().next().is_some()))
+        }
+        Expression::Distinct(_) => Err(anyhow!("'distinct' is not allowed here")),
+        Expression::Star => Err(anyhow!("'*' is not allowed here")),
+    }
+}
+
+/// value in (list), with three valued logic: NULL when value is NULL or the list contains NULL
+/// and has no equal value
+fn contains(value: &Value, list: &[Value], collation: &Collation) -> anyhow::Result<Value> {
+    if list.is_empty() {
+        return Ok(boolean(false));
+    }
+    let mut unknown = false;
+    for element in list {
+        match truth(&binary(TokenType::Equals, value, element, collation)?) {
+            Some(true) => return Ok(boolean(true)),
+            Some(false) => {}
+            None => unknown = true,
+        }
+    }
+    Ok(if unknown {
+        Value::null()
+    } else {
+        boolean(false)
+    })
+}
+
+fn negate(value: Value, negated: bool) -> Value {
+    match truth(&value) {
+        Some(b) if negated => boolean(!b),
+        _ => value,
+    }
+}
+
+/// the collation of an expression, and whether it is explicit ('collate name')
+/// or implicit (the collation of a column)
+pub(crate) fn collation_of(
+    expression: &Expression,
+    database: &Database,
+    table: &Table,
+) -> anyhow::Result<Option<(Collation, bool)>> {
+    Ok(match expression {
+        Expression::Collate(_, name) => Some((database.collation(name)?, true)),
+        Expression::Column(name) => table
+            .find_index(name)
+            .map(|index| (table.collation(index).clone(), false)),
+        _ => None,
+    })
+}
+
+/// the collation for comparing two expressions, like in SQLite:
+/// explicit before implicit, left before right, binary when neither has one
+pub(crate) fn comparison_collation(
+    left: &Expression,
+    right: &Expression,
+    database: &Database,
+    table: &Table,
+) -> anyhow::Result<Collation> {
+    let left = collation_of(left, database, table)?;
+    let right = collation_of(right, database, table)?;
+    Ok(match (left, right) {
+        (Some((collation, true)), _) | (_, Some((collation, true))) => collation,
+        (Some((collation, _)), _) | (_, Some((collation, _))) => collation,
+        (None, None) => Collation::binary(),
+    })
+}
+
+/// the collation for ordering or grouping on an expression
+pub(crate) fn expression_collation(
+    expression: &Expression,
+    database: &Database,
+    table: &Table,
+) -> anyhow::Result<Collation> {
+    Ok(collation_of(expression, database, table)?
+        .map(|(collation, _)| collation)
+        .unwrap_or_else(Collation::binary))
+}
+
+/// the declared type of a column reference, Blob (none declared) for other expressions
+pub(crate) fn expression_affinity(expression: &Expression, table: &Table) -> Affinity {
+    match expression {
+        Expression::Column(name) => table
+            .find_index(name)
+            .map_or(Affinity::Blob, |index| table.affinity(index)),
+        _ => Affinity::Blob,
+    }
+}
+
+/// sql truth value: NULL is unknown, numbers are true when not zero
+pub(crate) fn truth(value: &Value) -> Option<bool> {
+    match number(value)? {
+        Number::Integer(i) => Some(i != 0),
+        Number::Float(f) => Some(f != 0.0),
+        Number::Decimal(d) => Some(d.mantissa() != 0),
+    }
+}
+
+pub(crate) fn boolean(value: bool) -> Value {
+    Value::from_bool(value)
+}
+
+fn unary(op: TokenType, operand: &Value) -> anyhow::Result<Value> {
+    match op {
+        TokenType::Not => Ok(truth(operand).map_or_else(Value::null, |b| boolean(!b))),
+        TokenType::Minus if Interval::from_value(operand).is_some() => {
+            Ok(datetime::negate(operand).unwrap_or_else(Value::null))
+        }
+        TokenType::Minus => Ok(match number(operand) {
+            None => Value::null(),
+            Some(Number::Integer(i)) => i
+                .checked_neg()
+                .map_or_else(|| Value::from_f64(-(i as f64)), Value::from_i64),
+            Some(Number::Float(f)) => Value::from_f64(-f),
+            Some(Number::Decimal(d)) => d
+                .checked_neg()
+                .map_or_else(|| Value::from_f64(-d.to_f64()), Value::from),
+        }),
+        _ => Err(anyhow!("Invalid unary operator {:?}", op)),
+    }
+}
+
+fn binary(
+    op: TokenType,
+    left: &Value,
+    right: &Value,
+    collation: &Collation,
+) -> anyhow::Result<Value> {
+    match op {
+        // three valued logic: false and NULL is false, true or NULL is true
+        TokenType::And => Ok(match (truth(left), truth(right)) {
+            (Some(false), _) | (_, Some(false)) => boolean(false),
+            (Some(true), Some(true)) => boolean(true),
+            _ => Value::null(),
+        }),
+        TokenType::Or => Ok(match (truth(left), truth(right)) {
+            (Some(true), _) | (_, Some(true)) => boolean(true),
+            (Some(false), Some(false)) => boolean(false),
+            _ => Value::null(),
+        }),
+        TokenType::Equals
+        | TokenType::BangEquals
+        | TokenType::Unequal
+        | TokenType::Less
+        | TokenType::LessEqual
+        | TokenType::Greater
+        | TokenType::GreaterEqual => {
+            if left.is_null() || right.is_null() {
+                return Ok(Value::null());
+            }
+            // text that looks like a date is compared as a date with dates
+            let ordering = match (datetime::coerce(left, right), datetime::coerce(right, left)) {
+                (Some(left), _) => left.cmp(right),
+                (_, Some(right)) => left.cmp(&right),
+                _ => collation.compare(left, right),
+            };
+            Ok(boolean(match op {
+                TokenType::Equals => ordering == Ordering::Equal,
+                TokenType::BangEquals | TokenType::Unequal => ordering != Ordering::Equal,
+                TokenType::Less => ordering == Ordering::Less,
+                TokenType::LessEqual => ordering != Ordering::Greater,
+                TokenType::Greater => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash
+            if is_temporal(left) || is_temporal(right) =>
+        {
+            let op = match op {
+                TokenType::Plus => '+',
+                TokenType::Minus => '-',
+                TokenType::Star => '*',
+                _ => '/',
+            };
+            Ok(datetime::arithmetic(op, left, right).unwrap_or_else(Value::null))
+        }
+        TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+            match (number(left), number(right)) {
+                (Some(l), Some(r)) => Ok(arithmetic(op, l, r)),
+                _ => Ok(Value::null()),
+            }
+        }
+        _ => Err(anyhow!("Invalid binary operator {:?}", op)),
+    }
+}
+
+/// integer arithmetic when both sides are integers, exact decimal arithmetic for decimals
+/// with decimals or integers (both falling back to float on overflow),
+/// float arithmetic otherwise. Division by zero is NULL
+pub(crate) fn arithmetic(op: TokenType, left: Number, right: Number) -> Value {
+    if let (Number::Integer(l), Number::Integer(r)) = (left, right) {
+        let result = match op {
+            TokenType::Plus => l.checked_add(r),
+            TokenType::Minus => l.checked_sub(r),
+            TokenType::Star => l.checked_mul(r),
+            _ if r == 0 => return Value::null(),
+            _ => l.checked_div(r),
+        };
+        if let Some(result) = result {
+            return Value::from_i64(result);
+        }
+    }
+    if let (Some(l), Some(r)) = (left.as_decimal(), right.as_decimal()) {
+        let result = match op {
+            TokenType::Plus => l.checked_add(&r),
+            TokenType::Minus => l.checked_sub(&r),
+            TokenType::Star => l.checked_mul(&r),
+            _ if r.mantissa() == 0 => return Value::null(),
+            _ => l.checked_div(&r),
+        };
+        if let Some(result) = result {
+            return result.into();
+        }
+    }
+    let (l, r) = (left.as_f64(), right.as_f64());
+    match op {
+        TokenType::Plus => Value::from_f64(l + r),
+        TokenType::Minus => Value::from_f64(l - r),
+        TokenType::Star => Value::from_f64(l * r),
+        _ if r == 0.0 => Value::null(),
+        _ => Value::from_f64(l / r),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Number {
+    Integer(i64),
+    Float(f64),
+    Decimal(Decimal),
+}
+
+impl Number {
+    pub(crate) fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f) => f,
+            Number::Decimal(d) => d.to_f64(),
+        }
+    }
+
+    /// exact decimal value, None for floats
+    pub(crate) fn as_decimal(self) -> Option<Decimal> {
+        match self {
+            Number::Integer(i) => Some(Decimal::from(i)),
+            Number::Float(_) => None,
+            Number::Decimal(d) => Some(d),
+        }
+    }
+}
+
+/// dates, times, timestamps and intervals
+fn is_temporal(value: &Value) -> bool {
+    matches!(
+        value.datatype(),
+        Ok(Datatype::Date | Datatype::Time | Datatype::Timestamp | Datatype::Interval)
+    )
+}
+
+/// numeric interpretation of a value, None for NULL
+/// text that does not look like a number is 0, like in SQLite
+pub(crate) fn number(value: &Value) -> Option<Number> {
+    match value.datatype().ok()? {
+        Datatype::Null => None,
+        Datatype::Integer => value.as_i64().map(Number::Integer),
+        Datatype::Float => value.as_f64().map(Number::Float),
+        Datatype::Text => {
+            let text = value.to_string();
+            let text = text.trim();
+            if let Ok(i) = text.parse::<i64>() {
+                Some(Number::Integer(i))
+            } else if let Some(d) = Decimal::parse(text) {
+                Some(Number::Decimal(d))
+            } else {
+                Some(Number::Float(text.parse::<f64>().unwrap_or(0.0)))
+            }
+        }
+        Datatype::Decimal => Decimal::from_value(value).map(Number::Decimal),
+        Datatype::Blob => Some(Number::Integer(0)),
+        Datatype::Date | Datatype::Time | Datatype::Timestamp | Datatype::Interval => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let seven = binary(
+            TokenType::Slash,
+            &15.into(),
+            &2.into(),
+            &Collation::binary(),
+        )
+        .unwrap();
+        assert_eq!(seven.to_string(), "7");
+        let half = binary(
+            TokenType::Slash,
+            &1.0.into(),
+            &2.into(),
+            &Collation::binary(),
+        )
+        .unwrap();
+        assert_eq!(half.to_string(), "0.5");
+        assert!(
+            binary(TokenType::Slash, &1.into(), &0.into(), &Collation::binary())
+                .unwrap()
+                .is_null()
+        );
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let null = Value::null();
+        let t = boolean(true);
+        let f = boolean(false);
+        assert!(binary(TokenType::And, &null, &t, &Collation::binary())
+            .unwrap()
+            .is_null());
+        assert_eq!(
+            binary(TokenType::And, &null, &f, &Collation::binary()).unwrap(),
+            f
+        );
+        assert_eq!(
+            binary(TokenType::Or, &null, &t, &Collation::binary()).unwrap(),
+            t
+        );
+        assert!(
+            binary(TokenType::Equals, &null, &null, &Collation::binary())
+                .unwrap()
+                .is_null()
+        );
+    }
+}
@@ -1,41 +1,199 @@
-use std::collections::HashMap;
+use anyhow::anyhow;
 
+use crate::database::Database;
+use crate::groupby::Aggregate;
+use crate::index::IndexScan;
+use crate::json;
+use crate::record::Record;
+use crate::sql::ast::{Expression, OrderingTerm, ResultColumn, Select, SetOperator};
 use crate::table::Table;
-use crate::value::Value;
 
-// beginnetje
-struct Vm {
-    tables: HashMap<String, Table>,
-    stack: Vec<Value>,
+pub(crate) mod compiler;
+pub(crate) mod eval;
+mod planner;
+mod subquery;
+
+use eval::{evaluate, expression_affinity, expression_collation, truth};
+
+/// executes compiled queries
+/// every opcode takes the table in the register and replaces it with its result
+pub(crate) struct Vm<'a> {
+    database: &'a Database,
     code: Vec<Opcode>,
-    table_register: String,
+    register: Register<'a>,
     ip: usize,
 }
 
-enum Opcode {
+#[derive(Debug)]
+pub(crate) enum Opcode {
     LoadTable(String),
-    ApplyIndex(String),
-    FetchRow,
-    FilterRow,
-    IncRowPointer,
-}
-
-impl Vm {
-    fn run(&mut self) {
-        for op in &self.code {
-            // match op {
-            //     Opcode::LoadTable(name) => {
-            //         if !self.tables.contains_key(name) {
-            //             let table = self.load_table(name).unwrap();
-            //             self.tables.insert(name.clone(), table);
-            //         }
-            //         self.table_register = name.clone();
-            //     }
-            // }
+    TableFunction(String, Vec<Expression>), // a table-valued function, like json_each
+    ApplyIndex(IndexScan),
+    Filter(Expression, Option<usize>), // stops after the number of matching records
+    Aggregate(Aggregate),
+    Sort(Vec<OrderingTerm>),
+    Distinct(Vec<Expression>), // keeps the first record for every key, all columns when empty
+    Project(Vec<ResultColumn>),
+    Limit(usize, Option<usize>),       // offset, limit
+    Combine(SetOperator, Box<Select>), // with the result of the select
+}
+
+enum Register<'a> {
+    Borrowed(&'a Table),
+    Owned(Box<Table>), // boxed, tables are large
+}
+
+impl<'a> Vm<'a> {
+    pub(crate) fn new(database: &'a Database, code: Vec<Opcode>) -> Self {
+        Self {
+            database,
+            code,
+            register: Register::Owned(Box::new(single_row())),
+            ip: 0,
+        }
+    }
+
+    /// use table as input, instead of loading one
+    pub(crate) fn with_input(mut self, table: &'a Table) -> Self {
+        self.register = Register::Borrowed(table);
+        self
+    }
+
+    pub(crate) fn run(mut self) -> anyhow::Result<Table> {
+        while self.ip < self.code.len() {
+            let input = match &self.register {
+                Register::Borrowed(table) => *table,
+                Register::Owned(table) => table,
+            };
+            let output = match &self.code[self.ip] {
+                Opcode::LoadTable(name) => Register::Borrowed(
+                    self.database
+                        .table(name)
+                        .ok_or_else(|| anyhow!("No such table: {}", name))?,
+                ),
+                Opcode::TableFunction(name, args) => {
+                    // the arguments cannot refer to columns, there is no table yet
+                    let args = args
+                        .iter()
+                        .map(|arg| evaluate(arg, self.database, input, &Record::default()))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    let table = json::table_function(name, &args).unwrap_or_else(|| {
+                        Err(anyhow!("No such table-valued function: {}", name))
+                    })?;
+                    Register::Owned(Box::new(table))
+                }
+                Opcode::ApplyIndex(scan) => Register::Owned(Box::new(input.scan_index(scan)?)),
+                Opcode::Filter(predicate, limit) => {
+                    Register::Owned(Box::new(filter(input, predicate, *limit, self.database)?))
+                }
+                Opcode::Aggregate(aggregate) => {
+                    Register::Owned(Box::new(input.aggregate(aggregate, self.database)?))
+                }
+                Opcode::Sort(terms) => Register::Owned(Box::new(input.sort(terms, self.database)?)),
+                Opcode::Distinct(expressions) => {
+                    Register::Owned(Box::new(input.distinct(expressions, self.database)?))
+                }
+                Opcode::Project(columns) => {
+                    Register::Owned(Box::new(project(input, columns, self.database)?))
+                }
+                Opcode::Limit(offset, count) => {
+                    Register::Owned(Box::new(limit(input, *offset, *count)?))
+                }
+                Opcode::Combine(operator, select) => {
+                    let code = compiler::compile(select, self.database)?;
+                    let other = Vm::new(self.database, code).run()?;
+                    Register::Owned(Box::new(input.combine(&other, *operator)?))
+                }
+            };
+            self.register = output;
+            self.ip += 1;
+        }
+        match self.register {
+            Register::Owned(table) => Ok(*table),
+            Register::Borrowed(table) => project(table, &[ResultColumn::Star], self.database),
         }
     }
+}
 
-    fn load_table(&self, name: &String) -> anyhow::Result<Table> {
-        Ok(Table::from_csv_file(name, None)?)
+/// the input for a select without from: one row without columns
+fn single_row() -> Table {
+    let mut table = Table::new("");
+    table
+        .append(Record::default())
+        .expect("an empty record fits in a page");
+    table
+}
+
+fn filter(
+    table: &Table,
+    predicate: &Expression,
+    limit: Option<usize>,
+    database: &Database,
+) -> anyhow::Result<Table> {
+    let mut result = table.empty_copy();
+    let mut count = 0;
+    for record in table.iter() {
+        if limit.is_some_and(|limit| count >= limit) {
+            break;
+        }
+        if truth(&evaluate(predicate, database, table, &record)?) == Some(true) {
+            result.insert(record)?;
+            count += 1;
+        }
+    }
+    Ok(result)
+}
+
+/// skips offset records and returns at most count of the rest, without reading further
+fn limit(table: &Table, offset: usize, count: Option<usize>) -> anyhow::Result<Table> {
+    let mut result = table.empty_copy();
+    for record in table.iter().skip(offset).take(count.unwrap_or(usize::MAX)) {
+        result.insert(record)?;
+    }
+    Ok(result)
+}
+
+/// result columns keep the collation of a column or 'collate' expression
+/// and the declared type of a column
+fn project(table: &Table, columns: &[ResultColumn], database: &Database) -> anyhow::Result<Table> {
+    let mut result = Table::new("");
+    let mut collations = vec![];
+    let mut affinities = vec![];
+    for column in columns {
+        match column {
+            ResultColumn::Star => {
+                for (index, name) in table.iter_colums().enumerate() {
+                    result.add_column(name, true);
+                    collations.push(table.collation(index).clone());
+                    affinities.push(table.affinity(index));
+                }
+            }
+            ResultColumn::Expression(expression, _) => {
+                result.add_column(column.name(), true);
+                collations.push(expression_collation(expression, database, table)?);
+                affinities.push(expression_affinity(expression, table));
+            }
+        }
+    }
+    for (index, (collation, affinity)) in collations.into_iter().zip(affinities).enumerate() {
+        let name = result.cols[index].clone();
+        result.set_collation(&name, collation)?;
+        result.set_affinity(&name, affinity)?;
+    }
+    for record in table.iter() {
+        let mut projected = Record {
+            rowid: record.rowid,
+            ..Record::default()
+        };
+        for column in columns {
+            match column {
+                ResultColumn::Star => projected.values.extend(record.values.iter().cloned()),
+                ResultColumn::Expression(expression, _) => {
+                    projected.add_value(evaluate(expression, database, table, &record)?)
+                }
+            }
+        }
+        result.insert(projected)?;
     }
+    Ok(result)
 }
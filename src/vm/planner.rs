@@ -0,0 +1,209 @@
+use std::ops::Bound;
+
+use crate::datetime::parse_iso;
+use crate::index::IndexScan;
+use crate::sql::ast::{Expression, OrderingTerm};
+use crate::sql::tokens::TokenType;
+use crate::table::Table;
+use crate::value::Value;
+
+/// chooses an index on the table for the where clause and the order by
+/// returns the scan, and whether it yields the records in the requested order
+/// indexes are only used with constants: 'a = 1', 'a > 1 and a <= 10', '1 < a'
+pub(crate) fn plan_index(
+    table: &Table,
+    where_clause: Option<&Expression>,
+    order_by: &[OrderingTerm],
+) -> Option<(IndexScan, bool)> {
+    let mut conjuncts = vec![];
+    if let Some(where_clause) = where_clause {
+        split_and(where_clause, &mut conjuncts);
+    }
+
+    let mut names: Vec<&String> = table.views.keys().collect();
+    names.sort(); // deterministic choice between equally good indexes
+
+    let mut best: Option<(usize, IndexScan, bool)> = None;
+    for name in names {
+        let columns: Vec<&str> = table.views[name]
+            .columns()
+            .iter()
+            .map(|i| table.cols[*i].as_str())
+            .collect();
+
+        let mut equal = vec![];
+        for column in &columns {
+            match conjuncts
+                .iter()
+                .find_map(|c| constant_comparison(c, column, TokenType::Equals))
+            {
+                Some(value) => equal.push(value),
+                None => break,
+            }
+        }
+        let (lower, upper) = match columns.get(equal.len()) {
+            Some(column) => (
+                lower_bound(&conjuncts, column),
+                upper_bound(&conjuncts, column),
+            ),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+
+        let score = equal.len() * 2
+            + matches!(lower, Bound::Included(_) | Bound::Excluded(_)) as usize
+            + matches!(upper, Bound::Included(_) | Bound::Excluded(_)) as usize;
+        let descending = provides_order(&columns, equal.len(), order_by);
+        if score == 0 && descending.is_none() {
+            continue;
+        }
+
+        let ordered = descending.is_some();
+        let better = match &best {
+            None => true,
+            Some((best_score, _, best_ordered)) => {
+                score > *best_score || (score == *best_score && ordered && !best_ordered)
+            }
+        };
+        if better {
+            let scan = IndexScan {
+                index: name.clone(),
+                equal,
+                lower,
+                upper,
+                descending: descending.unwrap_or(false),
+            };
+            best = Some((score, scan, ordered));
+        }
+    }
+    best.map(|(_, scan, ordered)| (scan, ordered))
+}
+
+fn split_and<'a>(expression: &'a Expression, conjuncts: &mut Vec<&'a Expression>) {
+    if let Expression::Binary(left, TokenType::And, right) = expression {
+        split_and(left, conjuncts);
+        split_and(right, conjuncts);
+    } else {
+        conjuncts.push(expression);
+    }
+}
+
+/// for 'column op constant' returns the constant
+/// 'constant op column' is read as 'column mirrored(op) constant'
+/// text that looks like a date is compared as a date with date values, which the index does not know
+fn constant_comparison(expression: &Expression, column: &str, op: TokenType) -> Option<Value> {
+    let Expression::Binary(left, found, right) = expression else {
+        return None;
+    };
+    let is_date_text = |value: &Value| value.is_text() && parse_iso(&value.to_string()).is_some();
+    match (left.as_ref(), right.as_ref()) {
+        (Expression::Literal(value), _) | (_, Expression::Literal(value))
+            if is_date_text(value) =>
+        {
+            None
+        }
+        (Expression::Column(name), Expression::Literal(value))
+            if name == column && *found == op && !value.is_null() =>
+        {
+            Some(value.clone())
+        }
+        (Expression::Literal(value), Expression::Column(name))
+            if name == column && mirror(*found) == op && !value.is_null() =>
+        {
+            Some(value.clone())
+        }
+        _ => None,
+    }
+}
+
+fn mirror(op: TokenType) -> TokenType {
+    match op {
+        TokenType::Less => TokenType::Greater,
+        TokenType::LessEqual => TokenType::GreaterEqual,
+        TokenType::Greater => TokenType::Less,
+        TokenType::GreaterEqual => TokenType::LessEqual,
+        op => op,
+    }
+}
+
+fn lower_bound(conjuncts: &[&Expression], column: &str) -> Bound<Value> {
+    for conjunct in conjuncts {
+        if let Some(value) = constant_comparison(conjunct, column, TokenType::Greater) {
+            return Bound::Excluded(value);
+        }
+        if let Some(value) = constant_comparison(conjunct, column, TokenType::GreaterEqual) {
+            return Bound::Included(value);
+        }
+    }
+    Bound::Unbounded
+}
+
+fn upper_bound(conjuncts: &[&Expression], column: &str) -> Bound<Value> {
+    for conjunct in conjuncts {
+        if let Some(value) = constant_comparison(conjunct, column, TokenType::Less) {
+            return Bound::Excluded(value);
+        }
+        if let Some(value) = constant_comparison(conjunct, column, TokenType::LessEqual) {
+            return Bound::Included(value);
+        }
+    }
+    Bound::Unbounded
+}
+
+/// Some(descending) when the order by terms are index columns, in one direction,
+/// starting at or before the first column that is not fixed by equality
+fn provides_order(columns: &[&str], n_equal: usize, order_by: &[OrderingTerm]) -> Option<bool> {
+    let descending = order_by.first()?.descending;
+    let mut names = vec![];
+    for term in order_by {
+        match &term.expression {
+            Expression::Column(name) if term.descending == descending => names.push(name.as_str()),
+            _ => return None,
+        }
+    }
+    (0..=n_equal.min(columns.len()))
+        .any(|start| columns[start..].starts_with(&names))
+        .then_some(descending)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sql::parser::parse_expression;
+
+    fn table() -> Table {
+        let mut table = Table::from_csv("a,b,c\n1,2,3\n", Some(","));
+        table.create_index("idx_a", "a").unwrap();
+        table.create_index("idx_b_c", "b, c").unwrap();
+        table
+    }
+
+    #[test]
+    fn test_prefers_most_selective_index() {
+        let where_clause = parse_expression("a > 1 and b = 2 and 3 <= c").unwrap();
+        let (scan, ordered) = plan_index(&table(), Some(&where_clause), &[]).unwrap();
+        assert_eq!(scan.index, "idx_b_c");
+        assert_eq!(scan.equal, vec![2.into()]);
+        assert_eq!(scan.lower, Bound::Included(3.into()));
+        assert_eq!(scan.upper, Bound::Unbounded);
+        assert!(!ordered);
+    }
+
+    #[test]
+    fn test_order_by_index() {
+        let order_by = vec![OrderingTerm {
+            expression: Expression::Column("c".to_string()),
+            descending: true,
+        }];
+        let where_clause = parse_expression("b = 2").unwrap();
+        let (scan, ordered) = plan_index(&table(), Some(&where_clause), &order_by).unwrap();
+        assert_eq!(scan.index, "idx_b_c");
+        assert!(scan.descending);
+        assert!(ordered);
+    }
+
+    #[test]
+    fn test_no_usable_index() {
+        let where_clause = parse_expression("c = 1 or a = 2").unwrap();
+        assert!(plan_index(&table(), Some(&where_clause), &[]).is_none());
+    }
+}
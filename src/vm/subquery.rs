@@ -0,0 +1,165 @@
+use anyhow::anyhow;
+
+use crate::database::Database;
+use crate::json;
+use crate::record::Record;
+use crate::sql::ast::{Expression, ResultColumn, Select, TableReference};
+use crate::table::Table;
+use crate::value::Value;
+
+use super::{compiler, Vm};
+
+/// the names a select can refer to: the columns of its table and the aliases in its select list
+#[derive(Debug, Clone)]
+struct Scope {
+    table: Option<String>,
+    columns: Vec<String>,
+}
+
+impl Scope {
+    fn new(select: &Select, database: &Database) -> Self {
+        let table = select
+            .from
+            .as_ref()
+            .and_then(|from| from.qualifier())
+            .map(String::from);
+        let mut columns = match &select.from {
+            Some(TableReference::Table(name, _)) => database
+                .table(name)
+                .map(|table| table.cols.clone())
+                .unwrap_or_default(),
+            Some(TableReference::Function(..)) => json::COLUMNS.map(String::from).to_vec(),
+            None => vec![],
+        };
+        for column in &select.columns {
+            if let ResultColumn::Expression(_, Some(alias)) = column {
+                columns.push(alias.clone());
+            }
+        }
+        Self { table, columns }
+    }
+
+    /// 'a', or 'table.a' for the table of the scope
+    fn resolves(&self, name: &str) -> bool {
+        self.columns.iter().any(|column| column == name)
+            || name.split_once('.').is_some_and(|(table, column)| {
+                self.table.as_deref() == Some(table) && self.columns.iter().any(|c| c == column)
+            })
+    }
+}
+
+/// the column names in the subquery (and its subqueries) that refer to the enclosing query
+pub(crate) fn outer_references(select: &Select, database: &Database) -> Vec<String> {
+    let mut found = vec![];
+    collect_outer_references(select, database, &[], &mut found);
+    found
+}
+
+fn collect_outer_references(
+    select: &Select,
+    database: &Database,
+    scopes: &[Scope],
+    found: &mut Vec<String>,
+) {
+    for (_, part) in &select.compound {
+        collect_outer_references(part, database, scopes, found);
+    }
+    let scopes = [scopes, &[Scope::new(select, database)]].concat();
+    for expression in select.expressions() {
+        expression.walk(&mut |e| match e {
+            Expression::Column(name)
+                if !scopes.iter().any(|scope| scope.resolves(name)) && !found.contains(name) =>
+            {
+                found.push(name.clone())
+            }
+            Expression::Subquery(select)
+            | Expression::Exists(select)
+            | Expression::InSelect(_, select, _) => {
+                collect_outer_references(select, database, &scopes, found)
+            }
+            _ => {}
+        });
+    }
+}
+
+/// runs the subquery for a record of the enclosing query
+/// references to the enclosing query are replaced by the values in the record
+pub(crate) fn run(
+    select: &Select,
+    database: &Database,
+    outer: &Table,
+    record: &Record,
+) -> anyhow::Result<Table> {
+    let correlation = Correlation {
+        database,
+        scopes: vec![],
+        outer,
+        record,
+    };
+    let select = correlation.select(select);
+    Vm::new(database, compiler::compile(&select, database)?).run()
+}
+
+/// the values of the only column in the result of the subquery
+pub(crate) fn values(
+    select: &Select,
+    database: &Database,
+    outer: &Table,
+    record: &Record,
+) -> anyhow::Result<Vec<Value>> {
+    let result = run(select, database, outer, record)?;
+    if result.cols.len() != 1 {
+        return Err(anyhow!(
+            "Subquery returns {} columns - expected 1",
+            result.cols.len()
+        ));
+    }
+    Ok(result.iter().map(|record| record.get(0).clone()).collect())
+}
+
+struct Correlation<'a> {
+    database: &'a Database,
+    scopes: Vec<Scope>, // of the subquery and the subqueries around the current one
+    outer: &'a Table,
+    record: &'a Record,
+}
+
+impl Correlation<'_> {
+    fn select(&self, select: &Select) -> Select {
+        let mut scopes = self.scopes.clone();
+        scopes.push(Scope::new(select, self.database));
+        let inner = Correlation { scopes, ..*self };
+        let mut result = select.transform(&|e| Some(inner.expression(e)));
+        for (_, part) in &mut result.compound {
+            **part = self.select(part);
+        }
+        result
+    }
+
+    fn expression(&self, expression: &Expression) -> Expression {
+        expression.transform(&|e| match e {
+            Expression::Column(name) if !self.scopes.iter().any(|s| s.resolves(name)) => {
+                self.outer_value(name)
+            }
+            Expression::Subquery(select) => {
+                Some(Expression::Subquery(Box::new(self.select(select))))
+            }
+            Expression::Exists(select) => Some(Expression::Exists(Box::new(self.select(select)))),
+            Expression::InSelect(operand, select, negated) => Some(Expression::InSelect(
+                Box::new(self.expression(operand)),
+                Box::new(self.select(select)),
+                *negated,
+            )),
+            _ => None,
+        })
+    }
+
+    /// 'a' or 'table.a' in the enclosing query, None when it has no such column
+    fn outer_value(&self, name: &str) -> Option<Expression> {
+        let index = self.outer.find_index(name).or_else(|| {
+            let (_, column) = name.split_once('.')?;
+            self.outer.find_index(column)
+        })?;
+        Some(Expression::Literal(self.record.get(index).clone()))
+    }
+}